@@ -0,0 +1,71 @@
+//! Measure the heap used by the tail of a dictionary with many shared
+//! endings, before and after `Trie::optimize_tail`.
+//!
+//! Run with `cargo bench --bench tail_memory --no-default-features --features std`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::mem::size_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use datrie::{AlphaMap, AsAlphaChar, Trie};
+
+/// Allocator that keeps the number of bytes currently allocated
+struct Counting;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const ENDINGS: [&str; 8] = [
+    "ation", "ingly", "ness", "ment", "ful", "less", "ously", "ity",
+];
+
+fn main() {
+    let mut words = Vec::new();
+    for a in 'a'..='z' {
+        for b in 'a'..='z' {
+            for c in 'a'..='z' {
+                for ending in ENDINGS {
+                    words.push(format!("{}{}{}{}", a, b, c, ending).as_str().as_alphachar());
+                }
+            }
+        }
+    }
+
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let mut trie = Trie::new(AlphaMap::from_ranges([0x61..=0x7a]).unwrap());
+    for (i, word) in words.iter().enumerate() {
+        trie.store(word, i as i32);
+    }
+    let built = ALLOCATED.load(Ordering::Relaxed) - before;
+    let stats = trie.stats();
+
+    trie.optimize_tail();
+    let optimized = ALLOCATED.load(Ordering::Relaxed) - before;
+    let optimized_stats = trie.stats();
+
+    // reference counts in front of each suffix, which a boxed suffix would
+    // not have
+    let counts = stats.num_keys * 2 * size_of::<usize>();
+    println!("Keys: {}", stats.num_keys);
+    println!(
+        "Suffix bytes: {} ({} stored after optimize_tail)",
+        stats.suffix_bytes, optimized_stats.stored_suffix_bytes
+    );
+    println!("Heap after storing: {} bytes", built);
+    println!("Of which reference counts: {} bytes", counts);
+    println!("Heap after optimize_tail: {} bytes", optimized);
+}
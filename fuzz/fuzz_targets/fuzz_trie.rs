@@ -1,7 +1,7 @@
 #![no_main]
 
 use arbitrary::Arbitrary;
-use datrie::{AlphaChar, AlphaMap, Trie, ALPHA_CHAR_ERROR};
+use datrie::{AlphaChar, AlphaMap, Key, Trie};
 use libfuzzer_sys::fuzz_target;
 use std::collections::HashMap;
 use std::ops::RangeInclusive;
@@ -13,32 +13,18 @@ struct Input {
 }
 
 fuzz_target!(|input: Input| {
-    if input.am_range.contains(&ALPHA_CHAR_ERROR) {
-        // This should be banned in add_range?
-        return;
-    }
-    if input.am_range.clone().count() > (u8::MAX - 1) as usize {
-        // This should be banned in add_range?
+    let mut am = AlphaMap::default();
+    if am.add_range(input.am_range.clone()).is_err() {
         return;
     }
-
-    let mut am = AlphaMap::default();
-    am.add_range(input.am_range.clone());
-    let mut trie = Trie::<Option<i32>>::new(am);
+    let mut trie = Trie::<Option<i32>>::new(am.clone());
 
     for item in input.data.iter() {
-        // validate that the ac doesn't have inner null bytes, except the last byte must be 0
-        match item.0.iter().position(|v| *v == 0) {
-            Some(v) if v == item.0.len() - 1 => {}
-            _ => return,
+        let Ok(key) = Key::new(item.0.clone(), &am) else {
+            return;
         };
-        // validate that the ac is in the range
-        match item.0.iter().position(|v| !input.am_range.contains(v)) {
-            Some(_) => return,
-            _ => {}
-        }
 
-        trie.store(item.0, *item.1);
+        trie.store(&key, *item.1);
     }
 
     for item in trie.iter() {
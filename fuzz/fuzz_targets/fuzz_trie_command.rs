@@ -1,7 +1,7 @@
 #![no_main]
 
 use arbitrary::Arbitrary;
-use datrie::{AlphaChar, AlphaMap, Trie, ALPHA_CHAR_ERROR};
+use datrie::{AlphaChar, AlphaMap, Key, Trie};
 use libfuzzer_sys::fuzz_target;
 use std::hint::black_box;
 use std::io::Cursor;
@@ -33,62 +33,41 @@ enum Command {
     SerdeTest,
 }
 
-fn validate_key(input: &Input, key: &Vec<AlphaChar>) -> bool {
-    match key.iter().position(|v| *v == 0) {
-        Some(v) if v == key.len() - 1 => {}
-        _ => return false,
-    };
-    // validate that the ac is in the range
-    match key.iter().position(|v| !input.am_range.contains(v)) {
-        Some(_) => return false,
-        _ => {}
-    }
-
-    true
-}
-
 fuzz_target!(|input: Input| {
-    if input.am_range.contains(&ALPHA_CHAR_ERROR) {
-        // This should be banned in add_range?
-        return;
-    }
-    if input.am_range.clone().count() > (u8::MAX - 1) as usize {
-        // This should be banned in add_range?
+    let mut am = AlphaMap::default();
+    if am.add_range(input.am_range.clone()).is_err() {
         return;
     }
-
-    let mut am = AlphaMap::default();
-    am.add_range(input.am_range.clone());
-    let mut trie = Trie::<Option<i32>>::new(am);
+    let mut trie = Trie::<Option<i32>>::new(am.clone());
 
     for command in input.commands.iter() {
         match command {
             Command::Store { key, data } => {
-                if !validate_key(&input, key) {
+                let Ok(key) = Key::new(key.clone(), &am) else {
                     return;
-                }
-                trie.store(key, *data);
+                };
+                trie.store(&key, *data);
             }
             Command::StoreIfAbsent { key, data } => {
-                if !validate_key(&input, key) {
+                let Ok(key) = Key::new(key.clone(), &am) else {
                     return;
-                }
-                trie.store(key, *data);
+                };
+                trie.store(&key, *data);
             }
             Command::Root => {
                 black_box(trie.root());
             }
             Command::Retrieve { key } => {
-                if !validate_key(&input, key) {
+                let Ok(key) = Key::new(key.clone(), &am) else {
                     return;
-                }
-                trie.retrieve(key);
+                };
+                trie.retrieve(&key);
             }
             Command::Delete { key } => {
-                if !validate_key(&input, key) {
+                let Ok(key) = Key::new(key.clone(), &am) else {
                     return;
-                }
-                trie.delete(key);
+                };
+                trie.delete(&key);
             }
             Command::SerdeTest => {
                 let mut buf: Vec<u8> = Vec::new();
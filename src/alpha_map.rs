@@ -1,12 +1,16 @@
 use alloc::boxed::Box;
 use alloc::vec;
 use alloc::vec::Vec;
-use core::iter;
 use core::ops::RangeInclusive;
+use core::{fmt, iter};
+#[cfg(feature = "std")]
+use std::fs::File;
 #[cfg(feature = "std")]
 use std::io;
 #[cfg(feature = "std")]
-use std::io::{Read, Write};
+use std::io::{BufReader, Read, Write};
+#[cfg(feature = "std")]
+use std::path::Path;
 
 #[cfg(feature = "std")]
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
@@ -15,27 +19,299 @@ use rangemap::RangeInclusiveSet;
 use crate::types::*;
 use crate::types::{TrieChar, TRIE_CHAR_TERM};
 
+/// Error in the ranges given to an [AlphaMap]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AlphaMapError {
+    /// The range ends before it starts
+    InvalidRange(RangeInclusive<AlphaChar>),
+    /// The range has more characters than trie characters available
+    RangeTooLarge(RangeInclusive<AlphaChar>),
+    /// The range contains [ALPHA_CHAR_ERROR], which cannot be a character
+    ContainsErrorChar(RangeInclusive<AlphaChar>),
+    /// The alphabet has the given number of characters, more than the
+    /// [TRIE_CHAR_MAX] trie characters available
+    TooManyChars(usize),
+    /// The alphabet has no characters
+    Empty,
+    /// The character is given more than once in a collation
+    DuplicateChar(AlphaChar),
+}
+
+impl fmt::Display for AlphaMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlphaMapError::InvalidRange(range) => write!(f, "invalid range {:?}", range),
+            AlphaMapError::RangeTooLarge(range) => write!(f, "range {:?} too large", range),
+            AlphaMapError::ContainsErrorChar(range) => {
+                write!(f, "range {:?} include ALPHA_CHAR_ERROR", range)
+            }
+            AlphaMapError::TooManyChars(count) => write!(
+                f,
+                "alphabet has {} characters, more than the limit of {}",
+                count, TRIE_CHAR_MAX
+            ),
+            AlphaMapError::Empty => write!(f, "alphabet has no characters"),
+            AlphaMapError::DuplicateChar(ac) => {
+                write!(f, "character {:#x} appears more than once", ac)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AlphaMapError {}
+
 #[derive(Clone, Default)]
 pub struct AlphaMap {
     alpha_begin: AlphaChar,
     alpha_end: AlphaChar,
     ranges: RangeInclusiveSet<AlphaChar>,
+    /// Characters to take the first trie characters, in this order. See
+    /// [with_collation](Self::with_collation).
+    collation: Box<[AlphaChar]>,
     alpha_to_trie_map: Box<[TrieIndex]>,
     trie_to_alpha_map: Box<[AlphaChar]>,
 }
 
+#[cfg(feature = "std")]
 const ALPHAMAP_SIGNATURE: u32 = 0xd9fcd9fc;
 
+// A trie file may start with a version header of VERSION_SIGNATURE followed by
+// the version as u32. Files without it, such as all those written by
+// libdatrie, start right away with ALPHAMAP_SIGNATURE and are version 1.
+#[cfg(feature = "std")]
+const VERSION_SIGNATURE: u32 = 0xd9f0d9f0;
+/// Latest version of the trie file format that can be read. Version 2 adds
+/// the collation of the alphabet after its ranges, and is only written for
+/// alphabets with a collation.
+#[cfg(feature = "std")]
+pub(crate) const FORMAT_VERSION: u32 = 2;
+
+impl fmt::Debug for AlphaMap {
+    /// Print the ranges of the alphabet, without the derived lookup tables
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("AlphaMap");
+        debug.field("ranges", &self.ranges);
+        if !self.collation.is_empty() {
+            debug.field("collation", &self.collation);
+        }
+        debug.finish()
+    }
+}
+
 impl AlphaMap {
-    pub fn add_range(&mut self, range: RangeInclusive<AlphaChar>) {
-        self.ranges.insert(range);
+    /// Add the characters in `range` to the alphabet.
+    ///
+    /// Each character, including 0, takes one of the [TRIE_CHAR_MAX] trie
+    /// characters, so this fails if the whole alphabet would have more
+    /// characters than that. A byte alphabet is therefore 0x01..=0xff, as
+    /// keys always end with the terminating 0 anyway. The alphabet is left unchanged
+    /// on error.
+    pub fn add_range(&mut self, range: RangeInclusive<AlphaChar>) -> Result<(), AlphaMapError> {
+        Self::check_range(&range)?;
+        let mut ranges = self.ranges.clone();
+        ranges.insert(range);
+        Self::check_total(&ranges)?;
+        self.ranges = ranges;
+        self.recalc_work_area();
+        Ok(())
+    }
+
+    /// Create an alpha map of all the given ranges. Unlike calling
+    /// [add_range](Self::add_range) for each range, the character mapping is
+    /// only computed once.
+    pub fn from_ranges<I: IntoIterator<Item = RangeInclusive<AlphaChar>>>(
+        ranges: I,
+    ) -> Result<Self, AlphaMapError> {
+        let mut alpha_map = Self::default();
+        for range in ranges {
+            Self::check_range(&range)?;
+            alpha_map.ranges.insert(range);
+        }
+        Self::check_total(&alpha_map.ranges)?;
+        alpha_map.recalc_work_area();
+        Ok(alpha_map)
+    }
+
+    /// Create an alpha map of the characters in `order`, whose trie
+    /// characters are assigned in that order instead of the order of the
+    /// character values. Tries using the alpha map then iterate their keys
+    /// in the order of the collation, such as the dictionary order of a
+    /// language.
+    ///
+    /// Characters added later with [add_range](Self::add_range) come after
+    /// those in `order`, in ascending order.
+    ///
+    /// A trie file only keeps the ranges of its alphabet in the format of
+    /// libdatrie, so tries with a collation are saved in version 2 of the
+    /// format with the order after the ranges, which libdatrie cannot read.
+    /// [ROTrie::range](crate::ROTrie::range) still compares the keys by the
+    /// character values, so it visits the whole trie.
+    ///
+    /// ```
+    /// use datrie::{AlphaCharToString, AlphaMap, AsAlphaChar, Trie};
+    ///
+    /// let order = "zyx".as_alphachar();
+    /// let mut trie = Trie::new(AlphaMap::with_collation(&order[..3]).unwrap());
+    /// for word in ["xy", "zx", "yz"] {
+    ///     trie.store(&word.as_alphachar(), 1);
+    /// }
+    /// let keys: Vec<String> = trie
+    ///     .iter()
+    ///     .map(|(key, _)| key.as_slice().ac_to_string().unwrap())
+    ///     .collect();
+    /// assert_eq!(keys, ["zx", "yz", "xy"]);
+    /// ```
+    pub fn with_collation(order: &[AlphaChar]) -> Result<Self, AlphaMapError> {
+        if order.is_empty() {
+            return Err(AlphaMapError::Empty);
+        }
+        let mut alpha_map = Self::default();
+        for ac in order.iter().copied() {
+            Self::check_range(&(ac..=ac))?;
+            alpha_map.ranges.insert(ac..=ac);
+        }
+        Self::check_total(&alpha_map.ranges)?;
+        alpha_map.set_collation(order)?;
+        alpha_map.recalc_work_area();
+        Ok(alpha_map)
+    }
+
+    /// Set the collation to `order`. The terminating 0 always comes first,
+    /// so it is left out.
+    fn set_collation(&mut self, order: &[AlphaChar]) -> Result<(), AlphaMapError> {
+        let mut sorted = order.to_vec();
+        sorted.sort_unstable();
+        if let Some(pair) = sorted.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(AlphaMapError::DuplicateChar(pair[0]));
+        }
+        self.collation = order.iter().copied().filter(|ac| *ac != 0).collect();
+        Ok(())
+    }
+
+    fn check_range(range: &RangeInclusive<AlphaChar>) -> Result<(), AlphaMapError> {
+        if range.is_empty() {
+            return Err(AlphaMapError::InvalidRange(range.clone()));
+        }
+        if count_trie_chars(iter::once(range)) > TRIE_CHAR_MAX as usize {
+            return Err(AlphaMapError::RangeTooLarge(range.clone()));
+        }
+        if range.contains(&ALPHA_CHAR_ERROR) {
+            return Err(AlphaMapError::ContainsErrorChar(range.clone()));
+        }
+        Ok(())
+    }
+
+    /// Check that all of `ranges` fit in the trie characters
+    fn check_total(ranges: &RangeInclusiveSet<AlphaChar>) -> Result<(), AlphaMapError> {
+        let count = count_trie_chars(ranges.iter());
+        if count > TRIE_CHAR_MAX as usize {
+            return Err(AlphaMapError::TooManyChars(count));
+        }
+        Ok(())
+    }
+
+    /// Iterate the character ranges of the alphabet, in ascending order.
+    /// Adjacent or overlapping ranges that were added are merged.
+    pub fn ranges(&self) -> impl Iterator<Item = RangeInclusive<AlphaChar>> + '_ {
+        self.ranges.iter().cloned()
+    }
+
+    /// Get the number of characters in the alphabet
+    pub fn total_chars(&self) -> usize {
+        self.ranges
+            .iter()
+            .map(|range| *range.end() as usize - *range.start() as usize + 1)
+            .sum()
+    }
+
+    /// Check whether the trie characters follow a collation rather than the
+    /// order of the character values
+    pub(crate) fn is_collated(&self) -> bool {
+        !self.collation.is_empty()
+    }
+
+    /// Remove the characters in `range` from the alphabet.
+    ///
+    /// Trie characters are assigned densely over the whole alphabet, so this
+    /// renumbers the characters after the removed range. A trie built with
+    /// the old alphabet, including one saved to a file, cannot be used with
+    /// the new one.
+    pub fn remove_range(&mut self, range: RangeInclusive<AlphaChar>) {
+        self.collation = self
+            .collation
+            .iter()
+            .copied()
+            .filter(|ac| !range.contains(ac))
+            .collect();
+        self.ranges.remove(range);
         self.recalc_work_area()
     }
 
+    /// Create an alpha map of exactly the characters in `ranges`, checking
+    /// that they fit in the trie characters
+    pub(crate) fn from_range_set(
+        ranges: RangeInclusiveSet<AlphaChar>,
+    ) -> Result<Self, AlphaMapError> {
+        let mut alpha_map = Self {
+            ranges,
+            ..Default::default()
+        };
+        if let Some(range) = alpha_map.ranges.get(&ALPHA_CHAR_ERROR) {
+            return Err(AlphaMapError::ContainsErrorChar(range.clone()));
+        }
+        Self::check_total(&alpha_map.ranges)?;
+        alpha_map.recalc_work_area();
+        Ok(alpha_map)
+    }
+
+    /// Read only the alphabet of a trie saved at `path`. A trie created with
+    /// this alpha map encodes keys the same way as the saved trie.
+    ///
+    /// ```no_run
+    /// use datrie::{AlphaMap, AsAlphaChar, Trie};
+    ///
+    /// let dict = Trie::<i32>::from_file("dict.tri")?;
+    /// let mut overlay = Trie::<i32>::new(AlphaMap::from_trie_file("dict.tri")?);
+    /// overlay.store(&"word".as_alphachar(), 1);
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn from_trie_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut fp = BufReader::new(File::open(path)?);
+        Self::read(&mut fp)
+    }
+
+    /// Read the format version of the trie file the stream is at, consuming
+    /// the version header if there is one
+    #[cfg(feature = "std")]
+    pub(crate) fn read_format_version<T: Read>(stream: &mut T) -> io::Result<u32> {
+        match stream.read_u32::<BigEndian>()? {
+            ALPHAMAP_SIGNATURE => Ok(1),
+            VERSION_SIGNATURE => stream.read_u32::<BigEndian>(),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid signature",
+            )),
+        }
+    }
+
     #[cfg(feature = "std")]
     pub(crate) fn read<T: Read>(stream: &mut T) -> io::Result<Self> {
-        // check signature
-        if stream.read_u32::<BigEndian>()? != ALPHAMAP_SIGNATURE {
+        // check signature, after the version header if any
+        let mut signature = stream.read_u32::<BigEndian>()?;
+        let mut version = 1;
+        if signature == VERSION_SIGNATURE {
+            version = stream.read_u32::<BigEndian>()?;
+            if !(1..=FORMAT_VERSION).contains(&version) {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("unsupported format version {}", version),
+                ));
+            }
+            signature = stream.read_u32::<BigEndian>()?;
+        }
+        if signature != ALPHAMAP_SIGNATURE {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 "invalid signature",
@@ -55,19 +331,38 @@ impl AlphaMap {
                 return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid range"));
             }
             let range = begin..=end;
-            if range.clone().count() >= u8::MAX as usize {
-                return Err(io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "range too large",
-                ));
+            if let Err(err) = Self::check_range(&range) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, err));
             }
-            if range.clone().contains(&ALPHA_CHAR_ERROR) {
+            alphamap.ranges.insert(range);
+        }
+        if let Err(err) = Self::check_total(&alphamap.ranges) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+        }
+
+        // Read collation
+        if version >= 2 {
+            let total = stream.read_i32::<BigEndian>()?;
+            if !(0..=TRIE_CHAR_MAX as i32).contains(&total) {
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidData,
-                    "range include ALPHA_CHAR_ERROR",
+                    "invalid collation length",
                 ));
             }
-            alphamap.ranges.insert(range);
+            let mut order = Vec::with_capacity(total as usize);
+            for _ in 0..total {
+                let ac = stream.read_i32::<BigEndian>()? as AlphaChar;
+                if !alphamap.ranges.contains(&ac) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "collation character outside of the alphabet",
+                    ));
+                }
+                order.push(ac);
+            }
+            if let Err(err) = alphamap.set_collation(&order) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, err));
+            }
         }
 
         // work area
@@ -78,6 +373,11 @@ impl AlphaMap {
 
     #[cfg(feature = "std")]
     pub(crate) fn serialize<T: Write>(&self, buf: &mut T) -> io::Result<()> {
+        // stay readable by libdatrie unless the collation must be saved
+        if !self.collation.is_empty() {
+            buf.write_u32::<BigEndian>(VERSION_SIGNATURE)?;
+            buf.write_u32::<BigEndian>(2)?;
+        }
         buf.write_u32::<BigEndian>(ALPHAMAP_SIGNATURE)?;
         buf.write_i32::<BigEndian>(self.ranges.len() as i32)?;
 
@@ -86,13 +386,27 @@ impl AlphaMap {
             buf.write_i32::<BigEndian>(*range.end() as i32)?;
         }
 
+        if !self.collation.is_empty() {
+            buf.write_i32::<BigEndian>(self.collation.len() as i32)?;
+            for ac in self.collation.iter() {
+                buf.write_i32::<BigEndian>(*ac as i32)?;
+            }
+        }
+
         Ok(())
     }
 
+    #[cfg(feature = "std")]
     pub(crate) fn serialized_size(&self) -> usize {
+        let collation_size = match self.collation.len() {
+            0 => 0,
+            // version header, collation count and characters
+            len => 8 + size_of::<i32>() + size_of::<AlphaChar>() * len,
+        };
         return 4 // ALPHAMAP_SIGNATURE
             + size_of::<i32>() // ranges_count
-            + (size_of::<AlphaChar>() * 2 * self.ranges.len());
+            + (size_of::<AlphaChar>() * 2 * self.ranges.len())
+            + collation_size;
     }
 
     fn recalc_work_area(&mut self) {
@@ -106,7 +420,8 @@ impl AlphaMap {
         let alpha_begin = *alpha_first.start();
 
         self.alpha_begin = alpha_begin;
-        // Count the total member within all self.ranges ranges
+        // Count the total member within all self.ranges ranges. Unlike
+        // count_trie_chars, 0 is counted as it takes a trie character below.
         let mut n_trie: usize = self
             .ranges
             .iter()
@@ -125,16 +440,33 @@ impl AlphaMap {
         let mut alpha_to_trie_map = vec![TRIE_INDEX_MAX; n_alpha].into_boxed_slice();
         let mut trie_to_alpha_map = vec![ALPHA_CHAR_ERROR; n_trie].into_boxed_slice();
 
+        // the collation comes first, then the rest of the ranges. Ranges are
+        // sorted, so trie characters follow the order of alphabet characters
+        // outside of the collation, which keeps trie iteration in order.
+        let mut in_collation = self.collation.to_vec();
+        in_collation.sort_unstable();
+        let order = self
+            .collation
+            .iter()
+            .copied()
+            .filter(|a| self.ranges.contains(a))
+            .chain(
+                self.ranges
+                    .iter()
+                    .flat_map(|range| range.clone())
+                    .filter(|a| in_collation.binary_search(a).is_err()),
+            );
         let mut trie_char: TrieIndex = 0;
-        for range in self.ranges.iter() {
-            for a in range.clone() {
-                if trie_char == TRIE_CHAR_TERM as TrieIndex {
-                    trie_char += 1;
-                }
-                alpha_to_trie_map[(a - alpha_begin) as usize] = trie_char as TrieIndex;
-                trie_to_alpha_map[trie_char as usize] = a;
+        // as in libdatrie, 0 takes a trie character of its own, though keys
+        // are always terminated with TRIE_CHAR_TERM, see char_to_trie. This
+        // keeps the trie characters of files with 0 in the alphabet.
+        for a in order {
+            if trie_char == TRIE_CHAR_TERM as TrieIndex {
                 trie_char += 1;
             }
+            alpha_to_trie_map[(a - alpha_begin) as usize] = trie_char as TrieIndex;
+            trie_to_alpha_map[trie_char as usize] = a;
+            trie_char += 1;
         }
         trie_to_alpha_map[TRIE_CHAR_TERM as usize] = 0;
 
@@ -142,21 +474,51 @@ impl AlphaMap {
         self.trie_to_alpha_map = trie_to_alpha_map;
     }
 
+    /// Check that every character of `key` up to its terminating 0 is in
+    /// the alphabet, returning the first one that is not.
+    pub fn validate_key(&self, key: &[AlphaChar]) -> Result<(), AlphaChar> {
+        match key
+            .iter()
+            .copied()
+            .take_while(|ac| *ac != 0)
+            .find(|ac| self.char_to_trie(*ac).is_none())
+        {
+            Some(ac) => Err(ac),
+            None => Ok(()),
+        }
+    }
+
     pub(crate) fn char_to_trie(&self, ac: AlphaChar) -> Option<TrieIndex> {
         if ac == 0 {
             return Some(TRIE_CHAR_TERM as TrieIndex);
         }
 
         if (self.alpha_begin..=self.alpha_end).contains(&ac) {
+            // characters in the gaps between ranges are marked with TRIE_INDEX_MAX,
+            // which would otherwise alias TRIE_CHAR_MAX once cast to TrieChar
             return self
                 .alpha_to_trie_map
                 .get((ac - self.alpha_begin) as usize)
-                .copied();
+                .copied()
+                .filter(|tc| *tc != TRIE_INDEX_MAX);
         }
 
         None
     }
 
+    /// Map the smallest alphabet character which is at least `ac`
+    pub(crate) fn ceil_char_to_trie(&self, ac: AlphaChar) -> Option<TrieIndex> {
+        if let Some(tc) = self.char_to_trie(ac) {
+            return Some(tc);
+        }
+        let next = self
+            .ranges
+            .iter()
+            .map(|range| *range.start())
+            .find(|start| *start > ac)?;
+        self.char_to_trie(next)
+    }
+
     pub(crate) fn char_to_trie_str(&self, str: &[AlphaChar]) -> Option<Vec<TrieChar>> {
         str.iter()
             .copied()
@@ -171,6 +533,50 @@ impl AlphaMap {
             .copied()
             .unwrap_or(ALPHA_CHAR_ERROR)
     }
+
+    /// Check that every character of `key` survives being encoded to trie
+    /// characters and decoded back. The key is checked up to its terminator.
+    pub fn roundtrip_ok(&self, key: &[AlphaChar]) -> bool {
+        key.iter().copied().take_while(|ac| *ac != 0).all(|ac| {
+            self.char_to_trie(ac)
+                .is_some_and(|tc| self.trie_to_char(tc as TrieChar) == ac)
+        })
+    }
+}
+
+/// Create an alpha map of exactly the characters in the string, such as
+/// `AlphaMap::try_from("abcdefghijklmnopqrstuvwxyz")`
+impl TryFrom<&str> for AlphaMap {
+    type Error = AlphaMapError;
+
+    fn try_from(chars: &str) -> Result<Self, AlphaMapError> {
+        if chars.is_empty() {
+            return Err(AlphaMapError::Empty);
+        }
+        Self::from_ranges(chars.chars().map(|c| c as AlphaChar..=c as AlphaChar))
+    }
+}
+
+/// Alpha maps are equal if they have the same characters in the same
+/// collation, regardless of how their ranges were added
+impl PartialEq for AlphaMap {
+    fn eq(&self, other: &Self) -> bool {
+        self.ranges.iter().eq(other.ranges.iter())
+            && self.trie_to_alpha_map == other.trie_to_alpha_map
+    }
+}
+
+impl Eq for AlphaMap {}
+
+/// Count the characters of `ranges` against the [TRIE_CHAR_MAX] limit. 0 is
+/// counted too, as it takes a trie character of its own when in the
+/// alphabet, see [recalc_work_area](AlphaMap::recalc_work_area). A byte
+/// alphabet of 0x00..=0xff is therefore too large, as its last character
+/// would alias [TRIE_CHAR_TERM].
+fn count_trie_chars<'a, I: Iterator<Item = &'a RangeInclusive<AlphaChar>>>(ranges: I) -> usize {
+    ranges
+        .map(|range| *range.end() as usize - *range.start() as usize + 1)
+        .sum()
 }
 
 pub trait ToAlphaChars {
@@ -202,6 +608,255 @@ impl<T: Iterator<Item = AlphaChar>> ToTrieChar for T {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::alpha_map::{AlphaMap, AlphaMapError};
+    use crate::types::{AlphaChar, ALPHA_CHAR_ERROR};
+
+    #[test]
+    fn test_roundtrip() {
+        let mut alpha_map = AlphaMap::default();
+        alpha_map.add_range(0x0061..=0x007a).unwrap();
+        alpha_map.add_range(0x0e01..=0x0e3a).unwrap();
+
+        for ac in (0x0061..=0x007a).chain(0x0e01..=0x0e3a) {
+            assert!(
+                alpha_map.roundtrip_ok(&[ac, 0]),
+                "{:#x} does not roundtrip",
+                ac
+            );
+        }
+        assert!(alpha_map.roundtrip_ok(&[0]));
+        assert!(!alpha_map.roundtrip_ok(&['A' as AlphaChar, 0]));
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut alpha_map = AlphaMap::default();
+        assert_eq!(alpha_map.ranges().count(), 0);
+        assert_eq!(alpha_map.total_chars(), 0);
+
+        alpha_map.add_range(0x0e01..=0x0e3a).unwrap();
+        alpha_map.add_range(0x0061..=0x0070).unwrap();
+        alpha_map.add_range(0x0071..=0x007a).unwrap();
+        assert_eq!(
+            alpha_map.ranges().collect::<Vec<_>>(),
+            vec![0x0061..=0x007a, 0x0e01..=0x0e3a]
+        );
+        assert_eq!(alpha_map.total_chars(), 26 + 58);
+    }
+
+    #[test]
+    fn test_eq() {
+        let mut split = AlphaMap::default();
+        split.add_range(1..=2).unwrap();
+        split.add_range(3..=3).unwrap();
+        let mut joined = AlphaMap::default();
+        joined.add_range(1..=3).unwrap();
+        assert!(split == joined);
+
+        joined.add_range(5..=5).unwrap();
+        assert!(split != joined);
+        joined.remove_range(5..=5);
+        assert!(split == joined);
+        assert!(split != AlphaMap::default());
+    }
+
+    #[test]
+    fn test_from_ranges() {
+        let alpha_map = AlphaMap::from_ranges([0x0e01..=0x0e3a, 0x0061..=0x007a]).unwrap();
+        let mut expected = AlphaMap::default();
+        expected.add_range(0x0061..=0x007a).unwrap();
+        expected.add_range(0x0e01..=0x0e3a).unwrap();
+        assert!(alpha_map == expected);
+        assert_eq!(
+            alpha_map.char_to_trie(0x0e01),
+            expected.char_to_trie(0x0e01)
+        );
+
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 0x7a..=0x61;
+        assert_eq!(
+            AlphaMap::from_ranges([reversed.clone()]).err(),
+            Some(AlphaMapError::InvalidRange(reversed))
+        );
+        assert_eq!(
+            AlphaMap::from_ranges([0x01..=0x100]).err(),
+            Some(AlphaMapError::RangeTooLarge(0x01..=0x100))
+        );
+        assert_eq!(
+            AlphaMap::from_ranges([0x61..=0x7a, ALPHA_CHAR_ERROR..=ALPHA_CHAR_ERROR]).err(),
+            Some(AlphaMapError::ContainsErrorChar(
+                ALPHA_CHAR_ERROR..=ALPHA_CHAR_ERROR
+            ))
+        );
+    }
+
+    #[test]
+    fn test_try_from_str() {
+        let alpha_map = AlphaMap::try_from("zyxabcdefghijklmnopqrstuvw").unwrap();
+        assert_eq!(alpha_map.ranges().collect::<Vec<_>>(), vec![0x61..=0x7a]);
+
+        let alpha_map = AlphaMap::try_from("ab-กขa").unwrap();
+        assert_eq!(
+            alpha_map.ranges().collect::<Vec<_>>(),
+            vec![0x2d..=0x2d, 0x61..=0x62, 0x0e01..=0x0e02]
+        );
+        assert_eq!(alpha_map.total_chars(), 5);
+
+        assert_eq!(AlphaMap::try_from("").err(), Some(AlphaMapError::Empty));
+        let too_many: String = (0x4e00..0x4f00).filter_map(char::from_u32).collect();
+        assert_eq!(
+            AlphaMap::try_from(too_many.as_str()).err(),
+            Some(AlphaMapError::TooManyChars(256))
+        );
+    }
+
+    #[test]
+    fn test_with_collation() {
+        let alpha_map = AlphaMap::with_collation(&[0x63, 0x61, 0x62]).unwrap();
+        assert_eq!(alpha_map.ranges().collect::<Vec<_>>(), vec![0x61..=0x63]);
+        assert_eq!(alpha_map.char_to_trie(0x63), Some(1));
+        assert_eq!(alpha_map.char_to_trie(0x61), Some(2));
+        assert_eq!(alpha_map.trie_to_char(3), 0x62);
+        assert!(alpha_map.roundtrip_ok(&[0x61, 0x62, 0x63, 0]));
+
+        assert_eq!(
+            AlphaMap::with_collation(&[]).err(),
+            Some(AlphaMapError::Empty)
+        );
+        assert_eq!(
+            AlphaMap::with_collation(&[0x61, 0x62, 0x61]).err(),
+            Some(AlphaMapError::DuplicateChar(0x61))
+        );
+        assert_eq!(
+            AlphaMap::with_collation(&[0x61, ALPHA_CHAR_ERROR]).err(),
+            Some(AlphaMapError::ContainsErrorChar(
+                ALPHA_CHAR_ERROR..=ALPHA_CHAR_ERROR
+            ))
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_remove_range_collation() {
+        use std::io::Cursor;
+
+        let mut alpha_map = AlphaMap::with_collation(&[0x63, 0x61, 0x62]).unwrap();
+        alpha_map.remove_range(0x63..=0x63);
+        assert_eq!(alpha_map.char_to_trie(0x63), None);
+        assert_eq!(alpha_map.char_to_trie(0x61), Some(1));
+        assert_eq!(alpha_map.char_to_trie(0x62), Some(2));
+
+        println!("Reading back the pruned collation");
+        let mut buf = Vec::new();
+        alpha_map.serialize(&mut buf).unwrap();
+        let read = AlphaMap::read(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(read, alpha_map);
+
+        println!("Removing the whole collation");
+        alpha_map.remove_range(0x61..=0x62);
+        assert!(!alpha_map.is_collated());
+    }
+
+    #[test]
+    fn test_remove_range() {
+        let mut alpha_map = AlphaMap::default();
+        alpha_map.add_range(0x01..=0x7f).unwrap();
+        alpha_map.remove_range(0x01..=0x1f);
+        alpha_map.remove_range(0x7f..=0x7f);
+
+        assert_eq!(alpha_map.char_to_trie(0x1f), None);
+        assert_eq!(alpha_map.char_to_trie(0x7f), None);
+        assert_eq!(alpha_map.char_to_trie(0x20), Some(1));
+        assert!(alpha_map.roundtrip_ok(&[0x20, 0x7e, 0]));
+
+        alpha_map.remove_range(0x00..=0xff);
+        assert_eq!(alpha_map.char_to_trie(0x20), None);
+    }
+
+    #[test]
+    fn test_char_limit() {
+        let mut alpha_map = AlphaMap::default();
+        alpha_map.add_range(0x100..=0x1fe).unwrap();
+        assert_eq!(
+            alpha_map.add_range(0x200..=0x200),
+            Err(AlphaMapError::TooManyChars(256))
+        );
+        // the alphabet is unchanged on error
+        assert_eq!(alpha_map.char_to_trie(0x200), None);
+        assert_eq!(alpha_map.char_to_trie(0x1fe), Some(0xff));
+        // overlapping ranges take no more characters
+        alpha_map.add_range(0x1f0..=0x1fe).unwrap();
+
+        assert_eq!(
+            AlphaMap::from_ranges([0x100..=0x180, 0x200..=0x280]).err(),
+            Some(AlphaMapError::TooManyChars(0x81 * 2))
+        );
+
+        println!("Checking that 0 is counted in the limit");
+        let mut alpha_map = AlphaMap::default();
+        assert_eq!(
+            alpha_map.add_range(0x00..=0xff),
+            Err(AlphaMapError::RangeTooLarge(0x00..=0xff))
+        );
+        alpha_map.add_range(0x00..=0xfe).unwrap();
+        assert_eq!(alpha_map.char_to_trie(0x00), Some(0));
+        // 0 takes a trie character as in libdatrie, to read its files
+        assert_eq!(alpha_map.trie_to_char(1), 0x00);
+        assert_eq!(alpha_map.char_to_trie(0x01), Some(2));
+        assert_eq!(alpha_map.char_to_trie(0xfe), Some(0xff));
+        assert_eq!(
+            alpha_map.add_range(0xff..=0xff),
+            Err(AlphaMapError::TooManyChars(256))
+        );
+
+        println!("Checking a byte alphabet without 0");
+        let mut alpha_map = AlphaMap::default();
+        alpha_map.add_range(0x01..=0xff).unwrap();
+        assert_eq!(alpha_map.char_to_trie(0x01), Some(1));
+        assert_eq!(alpha_map.char_to_trie(0xff), Some(0xff));
+        assert!(alpha_map.roundtrip_ok(&[0xff, 0x01, 0]));
+    }
+
+    #[test]
+    fn test_add_range_error_char() {
+        let mut alpha_map = AlphaMap::default();
+        alpha_map.add_range(0x61..=0x7a).unwrap();
+        let range = ALPHA_CHAR_ERROR - 5..=ALPHA_CHAR_ERROR;
+        assert_eq!(
+            alpha_map.add_range(range.clone()),
+            Err(AlphaMapError::ContainsErrorChar(range))
+        );
+        // the alphabet is unchanged and still usable
+        assert_eq!(alpha_map.ranges().collect::<Vec<_>>(), vec![0x61..=0x7a]);
+        assert_eq!(alpha_map.char_to_trie(ALPHA_CHAR_ERROR - 5), None);
+        assert!(alpha_map.roundtrip_ok(&[0x61, 0]));
+    }
+
+    #[test]
+    fn test_validate_key() {
+        let alpha_map = AlphaMap::from_ranges([0x61..=0x7a]).unwrap();
+        assert_eq!(alpha_map.validate_key(&[0x61, 0x7a, 0]), Ok(()));
+        assert_eq!(alpha_map.validate_key(&[0x61, 0x36, 0x7b, 0]), Err(0x36));
+        // characters after the terminator are not part of the key
+        assert_eq!(alpha_map.validate_key(&[0x61, 0, 0x36]), Ok(()));
+        assert_eq!(alpha_map.validate_key(&[]), Ok(()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_read_full_range() {
+        let mut alpha_map = AlphaMap::default();
+        alpha_map.add_range(0x01..=0xff).unwrap();
+
+        let mut buf = Vec::new();
+        alpha_map.serialize(&mut buf).unwrap();
+        let alpha_map = AlphaMap::read(&mut buf.as_slice()).expect("Failed to read alpha map");
+        assert_eq!(alpha_map.char_to_trie(0xff), Some(0xff));
+    }
+}
+
 #[cfg(feature = "cffi")]
 mod cffi {
     use crate::alpha_map::*;
@@ -240,7 +895,9 @@ mod cffi {
             return -1;
         }
         let am = unsafe { alpha_map.as_mut() };
-        am.add_range(begin..=end);
-        0
+        match am.add_range(begin..=end) {
+            Ok(()) => 0,
+            Err(_) => -1,
+        }
     }
 }
@@ -1,7 +1,7 @@
 use std::ops::Deref;
 use std::process::exit;
 
-use datrie::{AsAlphaChar, CTrieData, TRIE_DATA_ERROR};
+use datrie::{AsAlphaChar, CTrieData};
 
 use crate::Context;
 
@@ -11,15 +11,21 @@ pub fn add(context: &mut Context, words: Vec<String>) {
         exit(1);
     }
 
-    let words = words.chunks_exact(2);
-
-    for pair in words {
+    // parse everything first so bad input leaves the trie untouched
+    let mut entries = Vec::with_capacity(words.len() / 2);
+    for pair in words.chunks_exact(2) {
         let word = &pair[0];
-        let data = pair[1]
-            .parse::<i32>()
-            .map(|v| CTrieData(v))
-            .unwrap_or(TRIE_DATA_ERROR);
+        let Ok(data) = pair[1].parse::<i32>() else {
+            eprintln!(
+                "add: Invalid data '{}' for '{}'. Data must be a number.",
+                pair[1], word
+            );
+            exit(1);
+        };
+        entries.push((word, CTrieData(data)));
+    }
 
+    for (word, data) in entries {
         let key = word.deref().as_alphachar();
         if !context.trie.store(&key, Some(data)) {
             eprintln!("Failed to add entry '{}' with data {}", word, data.0);
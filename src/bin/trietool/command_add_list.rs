@@ -20,7 +20,8 @@ pub fn add_list(context: &mut Context, list_file: PathBuf, encoding: Option<Stri
     let input = BufReader::new(File::open(&list_file).expect("Cannot open input file"));
 
     // the file format should be CSV or TSV-ish
-    for line in input.split('\n' as u8) {
+    for (line_no, line) in input.split('\n' as u8).enumerate() {
+        let line_no = line_no + 1;
         let line = line.unwrap();
         let split: Vec<&[u8]> = split_pattern.splitn(line.trim_ascii(), 2).collect();
 
@@ -35,19 +36,43 @@ pub fn add_list(context: &mut Context, list_file: PathBuf, encoding: Option<Stri
         };
         let encoded_key = key_str.deref().as_alphachar();
 
-        let data = split
-            .get(1)
-            .map(|v| {
-                String::from_utf8_lossy(v)
-                    .parse::<i32>()
-                    .map(|v| CTrieData(v))
-                    .ok()
-            })
-            .flatten()
-            .unwrap_or(TRIE_DATA_ERROR);
+        // entries without data get TRIE_DATA_ERROR, as in libdatrie
+        let data = match split.get(1).map(|v| String::from_utf8_lossy(v)) {
+            Some(v) => match v.trim().parse::<i32>() {
+                Ok(v) => CTrieData(v),
+                Err(_) => {
+                    eprintln!(
+                        "{}:{}: Invalid data '{}' for '{}'. Skipped.",
+                        list_file.display(),
+                        line_no,
+                        v,
+                        key_str
+                    );
+                    continue;
+                }
+            },
+            None => TRIE_DATA_ERROR,
+        };
+
+        if let Err(ac) = context.trie.check_key(&encoded_key) {
+            eprintln!(
+                "{}:{}: Key '{}' has character U+{:04X} outside of the alphabet. Skipped.",
+                list_file.display(),
+                line_no,
+                key_str,
+                ac
+            );
+            continue;
+        }
 
         if !context.trie.store(&encoded_key, Some(data)) {
-            eprintln!("Failed to add entry '{}' with data {}", key_str, data.0);
+            eprintln!(
+                "{}:{}: Failed to add entry '{}' with data {}",
+                list_file.display(),
+                line_no,
+                key_str,
+                data.0
+            );
         }
     }
 }
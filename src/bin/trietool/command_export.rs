@@ -0,0 +1,24 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use datrie::TRIE_DATA_ERROR;
+
+use crate::utils::escape_key;
+use crate::Context;
+
+pub fn export(context: &Context, file: PathBuf) {
+    let mut output = BufWriter::new(File::create(&file).expect("Cannot create output file"));
+
+    // the trie iterates in key order already, so exports can be diffed
+    for (key, value) in context.trie.iter() {
+        writeln!(
+            output,
+            "{}\t{}",
+            escape_key(&key),
+            value.copied().flatten().unwrap_or(TRIE_DATA_ERROR).0
+        )
+        .expect("Failed to write output file");
+    }
+    output.flush().expect("Failed to write output file");
+}
@@ -0,0 +1,75 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::ops::Deref;
+use std::path::Path;
+use std::process::exit;
+
+use datrie::{AsAlphaChar, CTrieData, TRIE_DATA_ERROR};
+
+use crate::utils::{parse_alpha_map, trie_path, AutoSaveTrie};
+use crate::Cli;
+
+pub fn import(cli: &Cli, alpha_map: &str, file: &Path) {
+    let alpha_map = match parse_alpha_map(alpha_map) {
+        Ok(alpha_map) => alpha_map,
+        Err(err) => {
+            eprintln!("import: {}", err);
+            exit(1);
+        }
+    };
+    let input = BufReader::new(File::open(file).expect("Cannot open input file"));
+    let mut trie = AutoSaveTrie::new(trie_path(cli), alpha_map).expect("Failed to open file");
+
+    // the format is the one written by export
+    for (line_no, line) in input.lines().enumerate() {
+        let line_no = line_no + 1;
+        let line = line.expect("Failed to read input file");
+        if line.is_empty() {
+            continue;
+        }
+        let (key, data) = line.split_once('\t').unwrap_or((&line, ""));
+        let data = match data {
+            "" => TRIE_DATA_ERROR,
+            data => match data.parse::<i32>() {
+                Ok(data) => CTrieData(data),
+                Err(_) => {
+                    eprintln!(
+                        "{}:{}: Invalid data '{}' for '{}'. Skipped.",
+                        file.display(),
+                        line_no,
+                        data,
+                        key
+                    );
+                    continue;
+                }
+            },
+        };
+
+        let key_ac = key.deref().as_alphachar();
+        if let Err(ac) = trie.check_key(&key_ac) {
+            eprintln!(
+                "{}:{}: Key '{}' has character U+{:04X} outside of the alphabet. Skipped.",
+                file.display(),
+                line_no,
+                key,
+                ac
+            );
+            continue;
+        }
+        if !trie.store(&key_ac, Some(data)) {
+            eprintln!(
+                "{}:{}: Failed to add entry '{}' with data {}",
+                file.display(),
+                line_no,
+                key,
+                data.0
+            );
+        }
+    }
+
+    // a trie with entries is saved on drop. Save an empty one here, so the
+    // trie file is always valid.
+    if !trie.is_dirty() {
+        trie.save().expect("Failed to save trie");
+    }
+}
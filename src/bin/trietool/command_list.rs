@@ -1,14 +1,14 @@
-use std::ops::Deref;
-
-use datrie::{AlphaCharToString, TRIE_DATA_ERROR};
+use datrie::TRIE_DATA_ERROR;
 
+use crate::utils::escape_key;
 use crate::Context;
 
 pub fn list(context: &Context) {
+    // the trie iterates in key order already
     for (key, value) in context.trie.iter() {
         println!(
             "{}\t{}",
-            key.deref().ac_to_string().unwrap(),
+            escape_key(&key),
             value.copied().flatten().unwrap_or(TRIE_DATA_ERROR).0
         )
     }
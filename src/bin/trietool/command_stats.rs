@@ -0,0 +1,12 @@
+use std::time::Instant;
+
+use crate::Context;
+
+pub fn stats(context: &Context) {
+    println!("{}", context.trie.stats());
+
+    // time a full pass over the trie to compare dictionary builds
+    let start = Instant::now();
+    let num_keys = context.trie.iter().count();
+    println!("Iterated {} keys in {:?}", num_keys, start.elapsed());
+}
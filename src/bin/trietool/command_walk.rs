@@ -0,0 +1,59 @@
+use std::io::{self, BufRead, Write};
+
+use datrie::{AlphaChar, CTrieData, TrieState, TRIE_DATA_ERROR};
+
+use crate::utils::escape_key;
+use crate::Context;
+
+/// Walk the trie interactively. Each line read from stdin is walked one
+/// character at a time from the current state, and the state is printed
+/// afterward. `:rewind` goes back to the root and `:quit` exits.
+pub fn walk(context: &Context) {
+    let mut state = context.trie.root();
+    let mut walked: Vec<AlphaChar> = Vec::new();
+    print_state(&state, &walked);
+
+    let mut lines = io::stdin().lock().lines();
+    loop {
+        print!("> ");
+        io::stdout().flush().expect("Failed to write output");
+        let Some(line) = lines.next() else {
+            println!();
+            return;
+        };
+
+        match line.expect("Failed to read input").as_str() {
+            ":quit" => return,
+            ":rewind" => {
+                state.rewind();
+                walked.clear();
+            }
+            text => {
+                for c in text.chars() {
+                    let ac = c as AlphaChar;
+                    if context.trie.check_key(&[ac]).is_err() {
+                        eprintln!("walk: '{}' (U+{:04X}) is outside the alphabet.", c, ac);
+                        break;
+                    }
+                    if !state.walk(ac) {
+                        eprintln!("walk: Cannot walk '{}' from '{}'.", c, escape_key(&walked));
+                        break;
+                    }
+                    walked.push(ac);
+                }
+            }
+        }
+        print_state(&state, &walked);
+    }
+}
+
+fn print_state(state: &TrieState<Option<CTrieData>>, walked: &[AlphaChar]) {
+    let walkable: Vec<AlphaChar> = state.walkable_chars_iter().filter(|ac| *ac != 0).collect();
+    println!("key:      '{}'", escape_key(walked));
+    println!("walkable: '{}'", escape_key(&walkable));
+    println!("terminal: {}", state.is_terminal());
+    match state.get_data() {
+        Some(data) => println!("data:     {}", data.unwrap_or(TRIE_DATA_ERROR).0),
+        None => println!("data:     -"),
+    }
+}
@@ -10,8 +10,12 @@ mod command_add;
 mod command_add_list;
 mod command_delete;
 mod command_delete_list;
+mod command_export;
+mod command_import;
 mod command_list;
 mod command_query;
+mod command_stats;
+mod command_walk;
 mod utils;
 
 #[derive(Parser)]
@@ -52,8 +56,20 @@ pub enum Commands {
     },
     #[command(about = "Query WORD data from trie")]
     Query { word: String },
-    #[command(about = "List all words in trie")]
+    #[command(about = "List all words in trie", visible_alias = "dump")]
     List {},
+    #[command(about = "Show size statistics of trie and time iterating it")]
+    Stats {},
+    #[command(about = "Walk trie interactively with characters read from stdin")]
+    Walk {},
+    #[command(about = "Write all words and data in trie to FILE")]
+    Export { file: PathBuf },
+    #[command(about = "Create trie from words and data in FILE written by export")]
+    Import {
+        #[arg(help = "Alphabet as comma-separated hex ranges, such as 61-7a,e01-e5b")]
+        alpha_map: String,
+        file: PathBuf,
+    },
 }
 
 pub struct Context {
@@ -62,6 +78,12 @@ pub struct Context {
 
 fn main() {
     let cli = Cli::parse();
+    // import creates the trie, so it must not load an existing one
+    if let Commands::Import { alpha_map, file } = &cli.command {
+        command_import::import(&cli, alpha_map, file);
+        return;
+    }
+
     let trie = load_trie(&cli);
     let mut context = Context { trie };
 
@@ -78,5 +100,9 @@ fn main() {
         } => command_delete_list::delete_list(&mut context, list_file, encoding),
         Commands::Query { word } => command_query::query(&context, word),
         Commands::List {} => command_list::list(&context),
+        Commands::Stats {} => command_stats::stats(&context),
+        Commands::Walk {} => command_walk::walk(&context),
+        Commands::Export { file } => command_export::export(&context, file),
+        Commands::Import { .. } => unreachable!(),
     }
 }
@@ -8,13 +8,17 @@ use std::path::{Path, PathBuf};
 
 use datrie::alpha_map::AlphaMap;
 use datrie::trie::Trie;
-use datrie::types::AlphaChar;
+use datrie::types::{AlphaChar, DisplayKey};
 use datrie::{CTrieData, TrieDeserializable, TrieSerializable};
 
 use crate::Cli;
 
+pub fn trie_path(cli: &Cli) -> PathBuf {
+    cli.path.join(format!("{}.tri", cli.trie))
+}
+
 pub fn load_trie(cli: &Cli) -> AutoSaveTrie<Option<CTrieData>> {
-    let trie_path = cli.path.join(format!("{}.tri", cli.trie));
+    let trie_path = trie_path(cli);
 
     if let Ok(trie) = AutoSaveTrie::from_file(&trie_path) {
         return trie;
@@ -55,7 +59,9 @@ pub fn load_abm<R: Read>(alpha_map: &mut AlphaMap, stream: &mut BufReader<R>) ->
                 continue;
             }
 
-            alpha_map.add_range(begin_ac..=end_ac);
+            if let Err(err) = alpha_map.add_range(begin_ac..=end_ac) {
+                eprintln!("Cannot add range [{:x},{:x}]: {}", begin_ac, end_ac, err);
+            }
         }
     }
 
@@ -89,6 +95,16 @@ impl<T: Default + TrieSerializable + TrieDeserializable> AutoSaveTrie<T> {
     }
 }
 
+impl<T: Default + TrieSerializable + TrieDeserializable> AutoSaveTrie<T> {
+    /// Write the trie to its file, even if it is not modified. Serializing
+    /// clears the dirty flag, so the trie is not written again on drop.
+    pub fn save(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.trie.serialize(&mut self.file)
+    }
+}
+
 impl<T: Default + TrieSerializable + TrieDeserializable> Deref for AutoSaveTrie<T> {
     type Target = Trie<T>;
 
@@ -106,15 +122,37 @@ impl<T: Default + TrieSerializable + TrieDeserializable> DerefMut for AutoSaveTr
 impl<T: Default + TrieSerializable + TrieDeserializable> Drop for AutoSaveTrie<T> {
     fn drop(&mut self) {
         if self.trie.is_dirty() {
-            self.file.set_len(0).expect("Failed to truncate");
-            self.file.seek(SeekFrom::Start(0)).expect("Failed to seek");
-            self.trie
-                .serialize(&mut self.file)
-                .expect("Failed to save trie");
+            self.save().expect("Failed to save trie");
         }
     }
 }
 
+/// Convert the key to a string, writing characters that are not valid
+/// Unicode as `\u{XXXX}` instead of dropping them
+pub fn escape_key(key: &[AlphaChar]) -> String {
+    DisplayKey(key).to_string()
+}
+
+/// Parse an alphabet given as comma-separated hex ranges, such as
+/// `61-7a,e01-e5b`. A range may also be a single character.
+pub fn parse_alpha_map(spec: &str) -> Result<AlphaMap, String> {
+    let mut ranges = Vec::new();
+    for range in spec
+        .split(',')
+        .map(str::trim)
+        .filter(|range| !range.is_empty())
+    {
+        let (begin, end) = range.split_once('-').unwrap_or((range, range));
+        let parse = |v: &str| {
+            let v = v.trim();
+            AlphaChar::from_str_radix(v.strip_prefix("0x").unwrap_or(v), 16)
+                .map_err(|_| format!("Invalid character '{}' in range '{}'", v, range))
+        };
+        ranges.push(parse(begin)?..=parse(end)?);
+    }
+    AlphaMap::from_ranges(ranges).map_err(|err| err.to_string())
+}
+
 pub trait IconvExt {
     fn decode(&mut self, input: &[u8]) -> Result<Vec<u8>, IconvError>;
 }
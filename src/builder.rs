@@ -0,0 +1,117 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::fmt;
+
+use rangemap::RangeInclusiveSet;
+
+use crate::alpha_map::{AlphaMap, AlphaMapError};
+use crate::trie::{strip_terminator, StoreError, Trie};
+use crate::types::AlphaChar;
+
+/// Build a trie without declaring its alphabet up front.
+///
+/// The characters of the added keys are collected, and the alphabet is made
+/// of exactly those characters when the trie is built. Adding keys with
+/// other characters to the built trie fails.
+///
+/// ```
+/// use datrie::{AsAlphaChar, TrieBuilder};
+///
+/// let mut builder = TrieBuilder::new();
+/// builder.insert(&"hello".as_alphachar(), 1);
+/// builder.insert(&"world".as_alphachar(), 2);
+/// let trie = builder.build().unwrap();
+/// assert_eq!(trie.retrieve(&"world".as_alphachar()), Some(&2));
+/// ```
+pub struct TrieBuilder<TrieData> {
+    chars: RangeInclusiveSet<AlphaChar>,
+    entries: BTreeMap<Vec<AlphaChar>, TrieData>,
+}
+
+impl<TrieData: Default> TrieBuilder<TrieData> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `key` with `data`, overwriting the data of an earlier duplicate.
+    /// The key may be given with or without the terminating 0.
+    pub fn insert(&mut self, key: &[AlphaChar], data: TrieData) {
+        let key = strip_terminator(key);
+        for ac in key.iter().copied() {
+            self.chars.insert(ac..=ac);
+        }
+        self.entries.insert(key.to_vec(), data);
+    }
+
+    /// Get the number of distinct keys added so far
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get the alphabet of the keys added so far, with the characters merged
+    /// into ranges. Fails if there are more characters than an alphabet can
+    /// hold.
+    pub fn alpha_map(&self) -> Result<AlphaMap, AlphaMapError> {
+        AlphaMap::from_range_set(self.chars.clone())
+    }
+
+    /// Build the trie of all added keys
+    pub fn build(self) -> Result<Trie<TrieData>, BuildError> {
+        let alpha_map = AlphaMap::from_range_set(self.chars)?;
+        Ok(Trie::from_sorted_iter(alpha_map, self.entries)?)
+    }
+}
+
+impl<TrieData> Default for TrieBuilder<TrieData> {
+    fn default() -> Self {
+        Self {
+            chars: RangeInclusiveSet::new(),
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl<TrieData: Default> Extend<(Vec<AlphaChar>, TrieData)> for TrieBuilder<TrieData> {
+    fn extend<I: IntoIterator<Item = (Vec<AlphaChar>, TrieData)>>(&mut self, iter: I) {
+        for (key, data) in iter {
+            self.insert(&key, data);
+        }
+    }
+}
+
+/// Reason a [TrieBuilder] could not build its trie
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BuildError {
+    /// The keys have more characters than an alphabet can hold
+    AlphaMap(AlphaMapError),
+    /// The trie cannot grow for the keys
+    Store(StoreError),
+}
+
+impl From<AlphaMapError> for BuildError {
+    fn from(err: AlphaMapError) -> Self {
+        BuildError::AlphaMap(err)
+    }
+}
+
+impl From<StoreError> for BuildError {
+    fn from(err: StoreError) -> Self {
+        BuildError::Store(err)
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::AlphaMap(err) => write!(f, "{}", err),
+            BuildError::Store(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BuildError {}
@@ -46,7 +46,8 @@ c_test_byte_alpha ()
         printf ("Fail to allocate alpha map\n");
         goto err_alpha_map_not_created;
     }
-    if (alpha_map_add_range (alpha_map, 0x00, 0xff) != 0) {
+    /* not 0x00..0xff as in libdatrie, where 0xff aliases the terminator */
+    if (alpha_map_add_range (alpha_map, 0x01, 0xff) != 0) {
         printf ("Fail to add full alpha map range\n");
         goto err_alpha_map_created;
     }
@@ -1,25 +1,77 @@
+use alloc::collections::TryReserveError;
+use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
-use core::cmp;
+use core::{cmp, mem};
+#[cfg(feature = "lazy")]
+use std::collections::HashMap;
 #[cfg(feature = "std")]
 use std::io;
 #[cfg(feature = "std")]
 use std::io::{Read, Write};
+#[cfg(feature = "lazy")]
+use std::io::{Seek, SeekFrom};
 
 #[cfg(feature = "std")]
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
 
 use crate::symbols::Symbols;
+use crate::trie::{CorruptError, StoreError};
 use crate::types::*;
 use crate::types::{TrieChar, TRIE_CHAR_MAX};
 
+#[derive(Clone, Copy)]
 struct DACell {
     base: TrieIndex,
     check: TrieIndex,
 }
 
-pub(crate) struct DArray {
-    cells: Vec<DACell>,
+/// The double-array of a trie, mapping each state and character to the next
+/// state. Only exposed with the `low-level` feature.
+#[derive(Clone)]
+pub struct DArray {
+    cells: Cells,
+}
+
+#[derive(Clone)]
+enum Cells {
+    Owned(Vec<DACell>),
+    /// Cells shared with a snapshot of the trie. They are copied into
+    /// [Cells::Owned] on the first modification, unless no longer shared.
+    Shared(Arc<Vec<DACell>>),
+    /// Cells read directly from a memory-mapped trie file. They are copied
+    /// into [Cells::Owned] on the first modification.
+    #[cfg(feature = "mmap")]
+    Mapped(MappedCells),
+}
+
+#[cfg(feature = "mmap")]
+#[derive(Clone)]
+struct MappedCells {
+    map: Arc<Mmap>,
+    /// Offset of cell 0 in the map
+    offset: usize,
+    len: usize,
+}
+
+#[cfg(feature = "mmap")]
+impl MappedCells {
+    fn get(&self, s: usize) -> Option<DACell> {
+        if s >= self.len {
+            return None;
+        }
+        let mut cell = &self.map[(self.offset + s * 8)..(self.offset + s * 8 + 8)];
+        Some(DACell {
+            base: cell.read_i32::<BigEndian>().unwrap(),
+            check: cell.read_i32::<BigEndian>().unwrap(),
+        })
+    }
+
+    fn to_vec(&self) -> Vec<DACell> {
+        (0..self.len).map(|s| self.get(s).unwrap()).collect()
+    }
 }
 
 const DA_SIGNATURE: u32 = 0xdafcdafc;
@@ -29,6 +81,7 @@ const DA_SIGNATURE: u32 = 0xdafcdafc;
 // - Cell 1: free circular-list pointers
 // - Cell 2: root node
 // - Cell 3: DA pool begin
+const DA_ROOT: TrieIndex = 2;
 const DA_POOL_BEGIN: TrieIndex = 3;
 
 impl DArray {
@@ -37,18 +90,51 @@ impl DArray {
     }
 
     pub(crate) fn get_root(&self) -> TrieIndex {
-        2
+        DA_ROOT
+    }
+
+    fn cell(&self, s: TrieIndex) -> Option<DACell> {
+        match &self.cells {
+            Cells::Owned(cells) => cells.get(s as usize).copied(),
+            Cells::Shared(cells) => cells.get(s as usize).copied(),
+            #[cfg(feature = "mmap")]
+            Cells::Mapped(cells) => cells.get(s as usize),
+        }
+    }
+
+    /// Get the cells for modification, copying mapped or shared cells first
+    fn cells_mut(&mut self) -> &mut Vec<DACell> {
+        #[cfg(feature = "mmap")]
+        if let Cells::Mapped(cells) = &self.cells {
+            self.cells = Cells::Owned(cells.to_vec());
+        }
+        if let Cells::Shared(cells) = &mut self.cells {
+            self.cells = Cells::Owned(Arc::unwrap_or_clone(mem::take(cells)));
+        }
+        match &mut self.cells {
+            Cells::Owned(cells) => cells,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Get a copy of the double-array sharing the cells, which are only
+    /// copied when either side is modified
+    pub(crate) fn share(&mut self) -> Self {
+        if let Cells::Owned(cells) = &mut self.cells {
+            self.cells = Cells::Shared(Arc::new(mem::take(cells)));
+        }
+        self.clone()
     }
 
     /// Get BASE cell value for the given state.
     pub(crate) fn get_base(&self, s: TrieIndex) -> Option<TrieIndex> {
         // TODO: Handle TRIE_INDEX_ERROR?
-        self.cells.get(s as usize).map(|v| v.base)
+        self.cell(s).map(|v| v.base)
     }
 
     /// Set BASE cell for the given state to the given value.
     pub(crate) fn set_base(&mut self, s: TrieIndex, val: TrieIndex) -> Option<()> {
-        match self.cells.get_mut(s as usize) {
+        match self.cells_mut().get_mut(s as usize) {
             Some(cell) => {
                 cell.base = val;
                 Some(())
@@ -59,12 +145,12 @@ impl DArray {
 
     /// Get CHECK cell value for the given state.
     pub(crate) fn get_check(&self, s: TrieIndex) -> Option<TrieIndex> {
-        self.cells.get(s as usize).map(|v| v.check)
+        self.cell(s).map(|v| v.check)
     }
 
     /// Set CHECK cell for the given state to the given value.
     pub(crate) fn set_check(&mut self, s: TrieIndex, val: TrieIndex) -> Option<()> {
-        match self.cells.get_mut(s as usize) {
+        match self.cells_mut().get_mut(s as usize) {
             Some(cell) => {
                 cell.check = val;
                 Some(())
@@ -73,14 +159,30 @@ impl DArray {
         }
     }
 
+    /// Get the number of cells, including the header cells
+    pub(crate) fn num_cells(&self) -> usize {
+        match &self.cells {
+            Cells::Owned(cells) => cells.len(),
+            Cells::Shared(cells) => cells.len(),
+            #[cfg(feature = "mmap")]
+            Cells::Mapped(cells) => cells.len,
+        }
+    }
+
+    /// Get the number of cells in the free list
+    pub(crate) fn num_free_cells(&self) -> usize {
+        (DA_POOL_BEGIN..self.num_cells() as TrieIndex)
+            .filter(|s| self.get_check(*s).is_some_and(|check| check < 0))
+            .count()
+    }
+
     /// Walk the double-array trie from state `s`, using input character `c`.
     /// If there exists an edge from `s` with arc labeled `c`, this function
     /// returns the new state. Otherwise, it returns None.
     #[must_use]
     pub(crate) fn walk(&self, s: TrieIndex, c: TrieChar) -> Option<TrieIndex> {
-        // The C code doesn't handle get_base() error here
-        // either it is infallible or it abuses TRIE_INDEX_ERROR
-        let next = self.get_base(s).unwrap() + c as TrieIndex;
+        // s may be out of range and base may be garbage in a corrupt file
+        let next = self.get_base(s)?.checked_add(c as TrieIndex)?;
         if self.get_check(next) == Some(s) {
             return Some(next);
         }
@@ -90,28 +192,30 @@ impl DArray {
     /// Insert a new arc labelled with character `c` from the trie node
     /// represented by index `s`
     /// Note that it assumes that no such arc exists before inserting.
-    pub(crate) fn insert_branch(&mut self, s: TrieIndex, c: TrieChar) -> Option<TrieIndex> {
+    pub(crate) fn insert_branch(
+        &mut self,
+        s: TrieIndex,
+        c: TrieChar,
+    ) -> Result<TrieIndex, StoreError> {
         let base = self.get_base(s).unwrap();
 
-        let mut next;
+        let next;
         if base > 0 {
-            next = base + c as TrieIndex;
-
-            // if already there, do not actually insert
-            if self.get_check(next) == Some(s) {
-                return Some(next);
-            }
-
-            // if (base + c) > TRIE_INDEX_MAX which means 'next' is overflow,
-            // or cell [next] is not free, relocate to a free slot
-            if base > TRIE_INDEX_MAX - c as TrieIndex || !self.check_free_cell(next) {
-                // relocate BASE[s]
-                let mut symbols = self.output_symbols(s);
-                symbols.add(c);
-                let new_base = self.find_free_base(&symbols)?;
+            match base.checked_add(c as TrieIndex) {
+                // if already there, do not actually insert
+                Some(n) if self.get_check(n) == Some(s) => return Ok(n),
+                Some(n) if self.check_free_cell(n)? => next = n,
+                // if (base + c) > TRIE_INDEX_MAX which means 'next' is overflow,
+                // or cell [next] is not free, relocate to a free slot
+                _ => {
+                    // relocate BASE[s]
+                    let mut symbols = self.output_symbols(s);
+                    symbols.add(c);
+                    let new_base = self.find_free_base(&symbols)?;
 
-                self.relocate_base(s, new_base);
-                next = new_base + c as TrieIndex;
+                    self.relocate_base(s, new_base);
+                    next = new_base + c as TrieIndex;
+                }
             }
         } else {
             let mut symbols = Symbols::default();
@@ -123,30 +227,51 @@ impl DArray {
         }
         self.alloc_cell(next);
         self.set_check(next, s);
-        Some(next)
+        Ok(next)
     }
 
-    fn check_free_cell(&mut self, s: TrieIndex) -> bool {
-        if !self.extend_pool(s) {
-            return false;
+    /// Insert arcs labelled with all of `symbols` from the trie node `s`,
+    /// which must have no children yet. Unlike inserting the arcs one by one,
+    /// the base is found once for all of them so no relocation is needed.
+    /// Returns the new base of `s`.
+    pub(crate) fn insert_branches(
+        &mut self,
+        s: TrieIndex,
+        symbols: &Symbols,
+    ) -> Result<TrieIndex, StoreError> {
+        let base = self.find_free_base(symbols)?;
+        self.set_base(s, base);
+        for sym in symbols.iter().copied() {
+            let next = base + sym as TrieIndex;
+            self.alloc_cell(next);
+            self.set_check(next, s);
+        }
+        Ok(base)
+    }
+
+    /// Check whether cell `s` is free, growing the pool up to it. Only fails
+    /// if the pool cannot be allocated, a cell past the maximum index is
+    /// just not free.
+    fn check_free_cell(&mut self, s: TrieIndex) -> Result<bool, StoreError> {
+        match self.extend_pool(s) {
+            Ok(()) => {}
+            Err(StoreError::PoolExhausted) => return Ok(false),
+            Err(err) => return Err(err),
         }
         match self.get_check(s) {
-            Some(v) if v < 0 => true,
-            _ => false,
+            Some(v) if v < 0 => Ok(true),
+            _ => Ok(false),
         }
     }
 
-    fn has_children(&self, s: TrieIndex) -> bool {
+    pub(crate) fn has_children(&self, s: TrieIndex) -> bool {
         let Some(base) = self.get_base(s) else {
             return false;
         };
         if base < 0 {
             return false;
         }
-        let max_c = cmp::min(
-            TRIE_CHAR_MAX as TrieIndex,
-            self.cells.len() as TrieIndex - base,
-        );
+        let max_c = self.max_trans_char(base);
         for c in 0..=max_c {
             if self.get_check(base + c) == Some(s) {
                 return true;
@@ -155,13 +280,36 @@ impl DArray {
         return false;
     }
 
+    /// Get the largest character `c` for which `base + c` is still inside
+    /// the pool, capped at TRIE_CHAR_MAX. Returns -1 if there is none, so
+    /// `0..=max_c` is always safe to add to `base` without overflowing.
+    fn max_trans_char(&self, base: TrieIndex) -> TrieIndex {
+        let Ok(base) = usize::try_from(base) else {
+            return -1;
+        };
+        match self.num_cells().checked_sub(base + 1) {
+            Some(room) => cmp::min(room, TRIE_CHAR_MAX as usize) as TrieIndex,
+            None => -1,
+        }
+    }
+
+    /// Iterate the characters of the transitions out of `s` in ascending
+    /// order, without collecting them like [output_symbols](Self::output_symbols)
+    pub(crate) fn children(&self, s: TrieIndex) -> impl Iterator<Item = TrieChar> + '_ {
+        let base = self.get_base(s).filter(|base| *base >= 0).unwrap_or(-1);
+        let max_c = match base {
+            -1 => -1,
+            base => self.max_trans_char(base),
+        };
+        (0..=max_c)
+            .filter(move |c| self.get_check(base + c) == Some(s))
+            .map(|c| c as TrieChar)
+    }
+
     pub(crate) fn output_symbols(&self, s: TrieIndex) -> Symbols {
         let mut syms = Symbols::default();
         let base = self.get_base(s).unwrap();
-        let max_c = cmp::min(
-            TrieChar::MAX as TrieIndex,
-            self.cells.len() as TrieIndex - base,
-        );
+        let max_c = self.max_trans_char(base);
         for c in 0..=max_c {
             if self.get_check(base + c) == Some(s) {
                 syms.add_fast(c as TrieChar);
@@ -170,7 +318,7 @@ impl DArray {
         syms
     }
 
-    fn find_free_base(&mut self, symbols: &Symbols) -> Option<TrieIndex> {
+    fn find_free_base(&mut self, symbols: &Symbols) -> Result<TrieIndex, StoreError> {
         // find first free cell that is beyond the first symbol
         let first_sym = symbols.get(0).unwrap();
         let mut s = -self.get_check(self.get_free_list()).unwrap();
@@ -180,42 +328,40 @@ impl DArray {
         if s == self.get_free_list() {
             s = first_sym as TrieIndex + DA_POOL_BEGIN;
             loop {
-                if !self.extend_pool(s) {
-                    return None;
-                }
+                self.extend_pool(s)?;
                 if self.get_check(s).unwrap() < 0 {
                     break;
                 }
+                // extend_pool() refuses TRIE_INDEX_MAX, so this cannot overflow
                 s += 1;
             }
         }
 
         // search for next free cell that fits the symbols set
-        while !self.fit_symbols(s - first_sym as TrieIndex, symbols) {
+        while !self.fit_symbols(s - first_sym as TrieIndex, symbols)? {
             // extend pool before getting exhausted
             if -self.get_check(s).unwrap() == self.get_free_list() {
-                if !self.extend_pool(self.cells.len() as TrieIndex) {
-                    // unlikely
-                    return None;
-                }
+                // unlikely to fail
+                self.extend_pool(self.num_cells() as TrieIndex)?;
             }
             s = -self.get_check(s).unwrap();
         }
 
-        Some(s - first_sym as TrieIndex)
+        Ok(s - first_sym as TrieIndex)
     }
 
-    fn fit_symbols(&mut self, base: TrieIndex, symbols: &Symbols) -> bool {
+    fn fit_symbols(&mut self, base: TrieIndex, symbols: &Symbols) -> Result<bool, StoreError> {
         for sym in symbols.iter().copied() {
             // if (base + sym) > TRIE_INDEX_MAX which means it's overflow,
             // or cell [base + sym] is not free, the symbol is not fit.
-            if base > TRIE_INDEX_MAX - sym as TrieIndex
-                || !self.check_free_cell(base + sym as TrieIndex)
-            {
-                return false;
+            let Some(next) = base.checked_add(sym as TrieIndex) else {
+                return Ok(false);
+            };
+            if !self.check_free_cell(next)? {
+                return Ok(false);
             }
         }
-        true
+        Ok(true)
     }
 
     fn relocate_base(&mut self, s: TrieIndex, new_base: TrieIndex) {
@@ -237,10 +383,7 @@ impl DArray {
             // must be given to new_next
             // preventing the case of TAIL pointer
             if old_next_base > 0 {
-                let max_c = cmp::min(
-                    TRIE_CHAR_MAX as TrieIndex,
-                    self.cells.len() as TrieIndex - old_next_base,
-                );
+                let max_c = self.max_trans_char(old_next_base);
                 for c in 0..=max_c {
                     if self.get_check(old_next_base + c) == Some(old_next) {
                         self.set_check(old_next_base + c, new_next);
@@ -256,28 +399,57 @@ impl DArray {
         self.set_base(s, new_base);
     }
 
-    fn extend_pool(&mut self, to_index: TrieIndex) -> bool {
+    /// Add `additional` free cells to the pool up front, so that later
+    /// insertions need not grow it. A pool that would go past the maximum
+    /// index fails as a capacity overflow.
+    pub(crate) fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        if additional == 0 {
+            return Ok(());
+        }
+        let to_index = self
+            .num_cells()
+            .checked_add(additional - 1)
+            .and_then(|to_index| TrieIndex::try_from(to_index).ok());
+        let Some(to_index) = to_index else {
+            return Err(capacity_overflow());
+        };
+        match self.extend_pool(to_index) {
+            Ok(()) => Ok(()),
+            Err(StoreError::OutOfMemory(err)) => Err(err),
+            Err(_) => Err(capacity_overflow()),
+        }
+    }
+
+    /// Grow the pool to have cell `to_index`. Fails with
+    /// [PoolExhausted](StoreError::PoolExhausted) past the maximum index, or
+    /// [OutOfMemory](StoreError::OutOfMemory) if the cells cannot be
+    /// allocated, leaving the pool unchanged.
+    fn extend_pool(&mut self, to_index: TrieIndex) -> Result<(), StoreError> {
         // Rust: minimum index is now DA_POOL_BEGIN instead of 0
         if to_index < DA_POOL_BEGIN || to_index >= TRIE_INDEX_MAX {
-            return false;
+            return Err(StoreError::PoolExhausted);
         }
-        if (to_index as usize) < self.cells.len() {
-            return true;
+        if (to_index as usize) < self.num_cells() {
+            return Ok(());
         }
         // Hence get_free_list(1) < DA_POOL_BEGIN (3) < self.cells.len() <= to_index < TRIE_INDEX_MAX
         // The compiler still doesn't use this information though...
 
-        let new_begin = self.cells.len() as TrieIndex;
+        let new_begin = self.num_cells() as TrieIndex;
         let free_tail = -self.get_base(self.get_free_list()).unwrap();
 
-        self.cells.reserve(to_index as usize + 1 - self.cells.len());
+        let free_list = self.get_free_list();
+        let cells = self.cells_mut();
+        cells
+            .try_reserve(to_index as usize + 1 - cells.len())
+            .map_err(StoreError::OutOfMemory)?;
         // XXX: The compiler currently don't unroll this loop
         // It could be faster if we generate the first and last item separately
         // but the code will be complicated
         for i in new_begin..=to_index {
             let check = if i == to_index {
                 // Last index
-                -self.get_free_list()
+                -free_list
             } else {
                 -(i + 1)
             };
@@ -287,19 +459,20 @@ impl DArray {
             } else {
                 -(i - 1)
             };
-            self.cells.push(DACell { check, base })
+            cells.push(DACell { check, base })
         }
         // The compiler doesn't seems to use this information to elide bond checks below
-        debug_assert_eq!(self.cells.len(), to_index as usize + 1);
+        debug_assert_eq!(cells.len(), to_index as usize + 1);
 
         // merge the new circular list to the old
         self.set_check(free_tail, -new_begin);
         self.set_base(self.get_free_list(), -to_index);
 
         // update header cell
-        self.cells[0].check = self.cells.len() as TrieIndex;
+        let cells = self.cells_mut();
+        cells[0].check = cells.len() as TrieIndex;
 
-        true
+        Ok(())
     }
 
     /// Prune off a non-separate path up from the final state `s`.
@@ -329,16 +502,46 @@ impl DArray {
     }
 
     fn free_cell(&mut self, cell: TrieIndex) {
-        // find insertion point
-        let mut i = -self.get_check(self.get_free_list()).unwrap();
-        while i != self.get_free_list() && i < cell {
-            i = -self.get_check(i).unwrap();
+        self.free_cells(&[cell])
+    }
+
+    /// Return the sorted `cells` to the free list in a single pass over it
+    fn free_cells(&mut self, cells: &[TrieIndex]) {
+        let free_list = self.get_free_list();
+        let mut i = -self.get_check(free_list).unwrap();
+        for cell in cells.iter().copied() {
+            // find insertion point, which only moves forward as cells are sorted
+            while i != free_list && i < cell {
+                i = -self.get_check(i).unwrap();
+            }
+            let prev = -self.get_base(i).unwrap();
+            self.set_check(cell, -i);
+            self.set_base(cell, -prev);
+            self.set_check(prev, -cell);
+            self.set_base(i, -cell);
         }
-        let prev = -self.get_base(i).unwrap();
-        self.set_check(cell, -i);
-        self.set_base(cell, -prev);
-        self.set_check(prev, -cell);
-        self.set_base(i, -cell);
+    }
+
+    /// Free all the cells below `s`, keeping `s` itself. Returns the tail
+    /// indices of the separate nodes freed, one for each key of the sub-trie.
+    pub(crate) fn free_subtree(&mut self, s: TrieIndex) -> Vec<TrieIndex> {
+        let mut tails = Vec::new();
+        let mut cells = Vec::new();
+        let mut stack = vec![s];
+        while let Some(s) = stack.pop() {
+            // unwrap as an assertion since s is a node of the trie
+            let base = self.get_base(s).unwrap();
+            if base < 0 {
+                tails.push(-base);
+            } else {
+                stack.extend(self.children(s).map(|c| base + c as TrieIndex));
+            }
+            cells.push(s);
+        }
+        cells.swap_remove(0);
+        cells.sort_unstable();
+        self.free_cells(&cells);
+        tails
     }
 
     /// Find the first separate node under a sub-trie rooted at `root` and write to `keybuff`
@@ -357,10 +560,7 @@ impl DArray {
             if base < 0 {
                 break;
             }
-            let max_c = cmp::min(
-                TRIE_CHAR_MAX as TrieIndex,
-                self.cells.len() as TrieIndex - base,
-            );
+            let max_c = self.max_trans_char(base);
             let c = (0..=max_c).find(|c| self.get_check(base + c) == Some(root))?;
             keybuff.push(c as TrieChar);
             root = base + c;
@@ -384,17 +584,14 @@ impl DArray {
     ) -> Option<TrieIndex> {
         let mut sep = sep;
         while sep != root {
-            let parent = self.get_check(sep).unwrap();
-            let base = self.get_base(parent).unwrap();
-            let c = sep - base;
+            let parent = self.get_check(sep)?;
+            let base = self.get_base(parent)?;
+            let c = sep.checked_sub(base)?;
 
             keybuff.pop();
 
             // find next sibling of sep
-            let max_c = cmp::min(
-                TRIE_CHAR_MAX as TrieIndex,
-                self.cells.len() as TrieIndex - base,
-            );
+            let max_c = self.max_trans_char(base);
             for c in (c + 1)..=max_c {
                 if self.get_check(base + c) == Some(parent) {
                     keybuff.push(c as TrieChar);
@@ -406,16 +603,110 @@ impl DArray {
         None
     }
 
+    /// Find the smallest transition character of `s` which is at least `c`
+    pub(crate) fn first_child_from(&self, s: TrieIndex, c: TrieIndex) -> Option<TrieChar> {
+        let base = self.get_base(s)?;
+        if base < 0 {
+            return None;
+        }
+        let max_c = self.max_trans_char(base);
+        (c..=max_c)
+            .find(|c| self.get_check(base + c) == Some(s))
+            .map(|c| c as TrieChar)
+    }
+
+    /// Find the last separate node in a sub-trie rooted at `root`, in the
+    /// same order as [first_separate](Self::first_separate).
+    ///
+    /// On return, `keybuff` is appended with the key characters which walk from
+    /// `root` to the separate node.
+    pub(crate) fn last_separate(
+        &self,
+        root: TrieIndex,
+        keybuff: &mut Vec<TrieChar>,
+    ) -> Option<TrieIndex> {
+        let mut root = root;
+        while let Some(base) = self.get_base(root) {
+            if base < 0 {
+                break;
+            }
+            let max_c = self.max_trans_char(base);
+            let c = (0..=max_c)
+                .rev()
+                .find(|c| self.get_check(base + c) == Some(root))?;
+            keybuff.push(c as TrieChar);
+            root = base + c;
+        }
+        Some(root)
+    }
+
+    /// Find the previous separate node under a sub-trie rooted at `root`
+    /// starting from the current separate node `sep`. This is the reverse of
+    /// [next_separate](Self::next_separate), and `keybuff` is updated the
+    /// same way.
+    pub(crate) fn prev_separate(
+        &self,
+        root: TrieIndex,
+        sep: TrieIndex,
+        keybuff: &mut Vec<TrieChar>,
+    ) -> Option<TrieIndex> {
+        let mut sep = sep;
+        while sep != root {
+            let parent = self.get_check(sep)?;
+            let base = self.get_base(parent)?;
+            let c = sep.checked_sub(base)?;
+
+            keybuff.pop();
+
+            // find previous sibling of sep
+            for c in (0..c).rev() {
+                if self.get_check(base + c) == Some(parent) {
+                    keybuff.push(c as TrieChar);
+                    return self.last_separate(base + c, keybuff);
+                }
+            }
+            sep = parent;
+        }
+        None
+    }
+
+    #[cfg(feature = "std")]
     pub(crate) fn serialized_size(&self) -> usize {
-        if !self.cells.is_empty() {
-            4 * self.cells.len() * 2 // `base` and `check`
+        if self.num_cells() != 0 {
+            4 * self.num_cells() * 2 // `base` and `check`
         } else {
             0
         }
     }
 
+    /// Read a double-array written by [serialize](Self::serialize). Every
+    /// cell is kept as is, including the free list, so that the double-array
+    /// allocates cells exactly as the one that was saved.
     #[cfg(feature = "std")]
     pub(crate) fn read<T: Read>(reader: &mut T) -> io::Result<Self> {
+        let num_cells = Self::read_header(reader)?;
+
+        let mut cells = Vec::with_capacity(num_cells as usize);
+        cells.push(DACell {
+            base: DA_SIGNATURE as TrieIndex,
+            check: num_cells,
+        });
+
+        for _ in 1..(num_cells as usize) {
+            cells.push(DACell {
+                base: reader.read_i32::<BigEndian>()?,
+                check: reader.read_i32::<BigEndian>()?,
+            });
+        }
+
+        Ok(Self {
+            cells: Cells::Owned(cells),
+        })
+    }
+
+    /// Read cell 0 of a double-array, returning the number of cells
+    #[cfg(feature = "std")]
+    fn read_header<T: Read>(reader: &mut T) -> io::Result<TrieIndex> {
         // check signature
         if reader.read_i32::<BigEndian>()? != DA_SIGNATURE as i32 {
             return Err(io::Error::new(
@@ -432,26 +723,50 @@ impl DArray {
                 "invalid cells count",
             ));
         }
+        Ok(num_cells)
+    }
 
-        let mut cells = Vec::with_capacity(num_cells as usize);
-        cells.push(DACell {
-            base: DA_SIGNATURE as TrieIndex,
-            check: num_cells,
-        });
-
-        for _ in 1..(num_cells as usize) {
-            cells.push(DACell {
-                base: reader.read_i32::<BigEndian>()?,
-                check: reader.read_i32::<BigEndian>()?,
-            });
+    /// Use the double-array stored in `map` at `offset` without copying it.
+    /// Returns the double-array and the offset right after it.
+    #[cfg(feature = "mmap")]
+    pub(crate) fn from_mmap(map: Arc<Mmap>, offset: usize) -> io::Result<(Self, usize)> {
+        let mut header = map.get(offset..).unwrap_or_default();
+        if header.read_i32::<BigEndian>()? != DA_SIGNATURE as i32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid signature",
+            ));
         }
 
-        Ok(Self { cells })
+        let num_cells = header.read_i32::<BigEndian>()?;
+        let end = usize::try_from(num_cells)
+            .ok()
+            .and_then(|num_cells| num_cells.checked_mul(8))
+            .and_then(|size| size.checked_add(offset))
+            .filter(|end| *end <= map.len());
+        let Some(end) = end else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid cells count",
+            ));
+        };
+
+        let cells = MappedCells {
+            map,
+            offset,
+            len: num_cells as usize,
+        };
+        Ok((
+            Self {
+                cells: Cells::Mapped(cells),
+            },
+            end,
+        ))
     }
 
     #[cfg(feature = "std")]
     pub(crate) fn serialize<T: Write>(&self, writer: &mut T) -> io::Result<()> {
-        for cell in &self.cells {
+        for cell in (0..self.num_cells()).map(|s| self.cell(s as TrieIndex).unwrap()) {
             writer.write_i32::<BigEndian>(cell.base)?;
             writer.write_i32::<BigEndian>(cell.check)?;
         }
@@ -463,9 +778,10 @@ impl DArray {
     }
 
     pub(crate) fn is_walkable(&self, s: TrieIndex, c: TrieChar) -> bool {
-        self.get_check(self.get_base(s).unwrap_or(TRIE_INDEX_ERROR) + c as TrieIndex)
-            .unwrap_or(TRIE_INDEX_ERROR)
-            == s
+        self.get_base(s)
+            .and_then(|base| base.checked_add(c as TrieIndex))
+            .and_then(|next| self.get_check(next))
+            == Some(s)
     }
 
     pub(crate) fn get_tail_index(&self, s: TrieIndex) -> TrieIndex {
@@ -477,23 +793,343 @@ impl DArray {
     }
 }
 
+impl DArray {
+    /// Check the structure of the double-array, so that walking it cannot go
+    /// out of bounds. `is_tail` tells whether a tail index used by a
+    /// separate node exists.
+    pub(crate) fn validate(
+        &self,
+        mut is_tail: impl FnMut(TrieIndex) -> bool,
+    ) -> Result<(), CorruptError> {
+        let num_cells = self.num_cells();
+        if num_cells < DA_POOL_BEGIN as usize
+            || num_cells > TRIE_INDEX_MAX as usize
+            || self.get_check(0) != Some(num_cells as TrieIndex)
+        {
+            return Err(CorruptError::CellCount);
+        }
+        let in_pool = |s: TrieIndex| s >= DA_POOL_BEGIN && (s as usize) < num_cells;
+
+        // the free list is circular, doubly linked and sorted by index
+        let free_list = self.get_free_list();
+        let mut prev = free_list;
+        let mut num_free = 0;
+        loop {
+            let next = self.get_check(prev).and_then(|check| check.checked_neg());
+            let Some(next) = next.filter(|next| *next == free_list || in_pool(*next)) else {
+                return Err(CorruptError::FreeList(prev));
+            };
+            if self.get_base(next) != Some(-prev) {
+                return Err(CorruptError::FreeList(next));
+            }
+            if next == free_list {
+                break;
+            }
+            if next <= prev {
+                return Err(CorruptError::FreeList(next));
+            }
+            num_free += 1;
+            prev = next;
+        }
+
+        for s in DA_POOL_BEGIN..(num_cells as TrieIndex) {
+            // unwrap as an assertion since s is in the pool
+            let cell = self.cell(s).unwrap();
+            if cell.check < 0 {
+                num_free -= 1;
+                continue;
+            }
+            // the parent must be a branch with an arc leading to s
+            let parent_base = self
+                .get_base(cell.check)
+                .filter(|base| *base > 0 && cell.check >= self.get_root());
+            let is_child = parent_base
+                .and_then(|base| s.checked_sub(base))
+                .is_some_and(|c| (0..=TRIE_CHAR_MAX as TrieIndex).contains(&c));
+            if !is_child {
+                return Err(CorruptError::Check(s));
+            }
+            if cell.base < 0 && !is_tail(-cell.base) {
+                return Err(CorruptError::TailIndex(s));
+            }
+        }
+        if num_free != 0 {
+            return Err(CorruptError::FreeList(free_list));
+        }
+
+        Ok(())
+    }
+
+    /// Create a double-array from its cells as `(base, check)` pairs, in the
+    /// same layout as the file format: cell 1 heads the free list, cell 2 is
+    /// the root and the pool begins at cell 3. Cell 0 is filled in with the
+    /// signature and the number of cells. The cells are not checked here,
+    /// see [ROTrie::from_parts](crate::ROTrie::from_parts).
+    #[cfg(feature = "low-level")]
+    pub fn from_cells(cells: Vec<(TrieIndex, TrieIndex)>) -> Self {
+        let mut cells: Vec<_> = cells
+            .into_iter()
+            .map(|(base, check)| DACell { base, check })
+            .collect();
+        let num_cells = TrieIndex::try_from(cells.len()).unwrap_or(TRIE_INDEX_MAX);
+        if let Some(cell) = cells.first_mut() {
+            cell.base = DA_SIGNATURE as TrieIndex;
+            cell.check = num_cells;
+        }
+        Self {
+            cells: Cells::Owned(cells),
+        }
+    }
+
+    /// Cells of an empty double-array
+    fn header() -> [DACell; 3] {
+        [
+            DACell {
+                base: DA_SIGNATURE as TrieIndex,
+                check: 3, // length of this
+            },
+            DACell {
+                base: -1,
+                check: -1,
+            },
+            DACell {
+                base: DA_POOL_BEGIN,
+                check: 0,
+            },
+        ]
+    }
+
+    /// Free the memory not used by the cells
+    pub(crate) fn shrink_to_fit(&mut self) {
+        match &mut self.cells {
+            Cells::Owned(cells) => cells.shrink_to_fit(),
+            Cells::Shared(_) => {}
+            #[cfg(feature = "mmap")]
+            Cells::Mapped(_) => {}
+        }
+    }
+
+    /// Reset to an empty double-array, keeping the allocated cells capacity
+    pub(crate) fn clear(&mut self) {
+        match &mut self.cells {
+            Cells::Owned(cells) => {
+                cells.clear();
+                cells.extend(Self::header());
+            }
+            Cells::Shared(_) => *self = Self::default(),
+            #[cfg(feature = "mmap")]
+            Cells::Mapped(_) => *self = Self::default(),
+        }
+    }
+}
+
 impl Default for DArray {
     fn default() -> Self {
         Self {
-            cells: vec![
-                DACell {
-                    base: DA_SIGNATURE as TrieIndex,
-                    check: 3, // length of this
-                },
-                DACell {
-                    base: -1,
-                    check: -1,
-                },
-                DACell {
-                    base: DA_POOL_BEGIN,
-                    check: 0,
+            cells: Cells::Owned(Vec::from(Self::header())),
+        }
+    }
+}
+
+/// A double-array left in a trie file, whose cells are read on demand. The
+/// most recently used pages of cells are kept in memory.
+#[cfg(feature = "lazy")]
+pub(crate) struct LazyDArray {
+    /// Stream position of cell 0
+    offset: u64,
+    num_cells: usize,
+    pages: HashMap<usize, LazyPage>,
+    max_pages: usize,
+    /// Counter of cell accesses, to find the least recently used page
+    clock: u64,
+}
+
+#[cfg(feature = "lazy")]
+struct LazyPage {
+    cells: Vec<DACell>,
+    last_used: u64,
+}
+
+#[cfg(feature = "lazy")]
+const LAZY_PAGE_CELLS: usize = 256;
+
+#[cfg(feature = "lazy")]
+impl LazyDArray {
+    /// Skip over the double-array the reader is at, keeping up to
+    /// `max_pages` pages of cells in memory once it is walked
+    pub(crate) fn read<T: Read + Seek>(reader: &mut T, max_pages: usize) -> io::Result<Self> {
+        let offset = reader.stream_position()?;
+        let num_cells = DArray::read_header(reader)? as usize;
+        reader.seek(SeekFrom::Start(
+            offset + (num_cells * size_of::<DACell>()) as u64,
+        ))?;
+
+        Ok(Self {
+            offset,
+            num_cells,
+            pages: HashMap::new(),
+            max_pages: cmp::max(max_pages, 1),
+            clock: 0,
+        })
+    }
+
+    fn cell<T: Read + Seek>(&mut self, reader: &mut T, s: TrieIndex) -> io::Result<Option<DACell>> {
+        let Some(s) = usize::try_from(s).ok().filter(|s| *s < self.num_cells) else {
+            return Ok(None);
+        };
+        let page_no = s / LAZY_PAGE_CELLS;
+        self.clock += 1;
+
+        if !self.pages.contains_key(&page_no) {
+            if self.pages.len() >= self.max_pages {
+                // unwrap as an assertion since there is at least one page
+                let lru = *self
+                    .pages
+                    .iter()
+                    .min_by_key(|(_, page)| page.last_used)
+                    .unwrap()
+                    .0;
+                self.pages.remove(&lru);
+            }
+
+            let start = page_no * LAZY_PAGE_CELLS;
+            let len = cmp::min(LAZY_PAGE_CELLS, self.num_cells - start);
+            let mut buf = vec![0; len * size_of::<DACell>()];
+            reader.seek(SeekFrom::Start(
+                self.offset + (start * size_of::<DACell>()) as u64,
+            ))?;
+            reader.read_exact(&mut buf)?;
+
+            let mut bytes = &buf[..];
+            let mut cells = Vec::with_capacity(len);
+            for _ in 0..len {
+                cells.push(DACell {
+                    base: bytes.read_i32::<BigEndian>()?,
+                    check: bytes.read_i32::<BigEndian>()?,
+                });
+            }
+            self.pages.insert(
+                page_no,
+                LazyPage {
+                    cells,
+                    last_used: 0,
                 },
-            ],
+            );
+        }
+
+        // unwrap as an assertion since the page was just loaded
+        let page = self.pages.get_mut(&page_no).unwrap();
+        page.last_used = self.clock;
+        Ok(Some(page.cells[s % LAZY_PAGE_CELLS]))
+    }
+
+    pub(crate) fn get_root(&self) -> TrieIndex {
+        DA_ROOT
+    }
+
+    pub(crate) fn get_base<T: Read + Seek>(
+        &mut self,
+        reader: &mut T,
+        s: TrieIndex,
+    ) -> io::Result<Option<TrieIndex>> {
+        Ok(self.cell(reader, s)?.map(|cell| cell.base))
+    }
+
+    /// Walk the double-array from `s` by character `c`, as [DArray::walk]
+    pub(crate) fn walk<T: Read + Seek>(
+        &mut self,
+        reader: &mut T,
+        s: TrieIndex,
+        c: TrieChar,
+    ) -> io::Result<Option<TrieIndex>> {
+        let Some(next) = self
+            .get_base(reader, s)?
+            .and_then(|base| base.checked_add(c as TrieIndex))
+        else {
+            return Ok(None);
+        };
+        if self.cell(reader, next)?.map(|cell| cell.check) == Some(s) {
+            return Ok(Some(next));
+        }
+        Ok(None)
+    }
+}
+
+/// Get the error of a pool past the maximum index. The double-array cannot
+/// index that many cells, like a vector that large, and
+/// [TryReserveError] can only be made by a failed reservation.
+fn capacity_overflow() -> TryReserveError {
+    // unwrap_err as an assertion since no vector can be that large
+    Vec::<DACell>::new().try_reserve(usize::MAX).unwrap_err()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::darray::{Cells, DACell, DArray};
+    use crate::trie::StoreError;
+    use crate::types::{TrieChar, TRIE_INDEX_MAX};
+
+    #[test]
+    fn test_index_overflow() {
+        let mut da = DArray::default();
+        let root = da.get_root();
+        let child = da.insert_branch(root, 1).unwrap();
+
+        // a base so large that base + c overflows TrieIndex
+        for base in [TRIE_INDEX_MAX - 1, TRIE_INDEX_MAX] {
+            let mut cells = da.cells_mut().clone();
+            cells[child as usize] = DACell { base, check: root };
+            let mut da = DArray {
+                cells: Cells::Owned(cells),
+            };
+            for c in [0, 1, TrieChar::MAX] {
+                assert!(!da.is_walkable(child, c), "base {} char {}", base, c);
+            }
+            let mut keybuff = Vec::new();
+            assert_eq!(da.first_separate(child, &mut keybuff), None);
+
+            // the overflowing base has no room for the arc, so the base is
+            // relocated instead of wrapping around
+            let next = da.insert_branch(child, TrieChar::MAX).unwrap();
+            assert!(next > 0 && (next as usize) < da.num_cells());
+            assert_eq!(da.walk(child, TrieChar::MAX), Some(next));
+            assert_ne!(da.get_base(child), Some(base));
+        }
+
+        // the pool cannot grow up to TRIE_INDEX_MAX
+        assert_eq!(
+            da.extend_pool(TRIE_INDEX_MAX),
+            Err(StoreError::PoolExhausted)
+        );
+    }
+
+    #[test]
+    fn test_walk_corrupt() {
+        let mut da = DArray::default();
+        let root = da.get_root();
+        let child = da.insert_branch(root, 1).unwrap();
+        assert_eq!(da.walk(root, 1), Some(child));
+
+        // states out of the cells
+        assert_eq!(da.walk(-1, 1), None);
+        assert_eq!(da.walk(TRIE_INDEX_MAX, 1), None);
+        assert_eq!(da.walk(da.num_cells() as i32, 1), None);
+
+        // base past the end of the cells, or overflowing
+        for base in [
+            da.num_cells() as i32,
+            TRIE_INDEX_MAX - 1,
+            TRIE_INDEX_MAX,
+            -5,
+        ] {
+            let mut cells = da.cells_mut().clone();
+            cells[root as usize] = DACell { base, check: 0 };
+            let corrupt = DArray {
+                cells: Cells::Owned(cells),
+            };
+            for c in [0, 1, TrieChar::MAX] {
+                assert_eq!(corrupt.walk(root, c), None, "base {} char {}", base, c);
+            }
         }
     }
 }
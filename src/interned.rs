@@ -0,0 +1,97 @@
+use alloc::vec::Vec;
+
+use crate::alpha_map::AlphaMap;
+use crate::trie::Trie;
+use crate::types::AlphaChar;
+
+/// A trie whose data is kept in an arena beside it, for data that is costly
+/// to clone or to keep in the tail, such as strings.
+///
+/// The tail owns the data of each key, so a trie cannot hold borrowed data.
+/// Instead, the trie maps each key to the index of its value in the arena,
+/// and the values are only moved in once.
+///
+/// ```
+/// use datrie::{AlphaMap, AsAlphaChar, InternedTrie};
+///
+/// let mut alpha_map = AlphaMap::default();
+/// alpha_map.add_range('a' as u32..='z' as u32).unwrap();
+/// let mut meanings = InternedTrie::new(alpha_map);
+/// meanings.store(&"cat".as_alphachar(), String::from("a small feline"));
+/// meanings.store(&"dog".as_alphachar(), String::from("a domestic canine"));
+///
+/// assert_eq!(
+///     meanings.retrieve(&"cat".as_alphachar()).map(String::as_str),
+///     Some("a small feline")
+/// );
+/// // the trie itself only holds indices into the arena
+/// assert_eq!(meanings.trie().retrieve(&"dog".as_alphachar()), Some(&1));
+/// ```
+pub struct InternedTrie<T> {
+    trie: Trie<u32>,
+    values: Vec<T>,
+}
+
+impl<T> InternedTrie<T> {
+    pub fn new(alpha_map: AlphaMap) -> Self {
+        Self {
+            trie: Trie::new(alpha_map),
+            values: Vec::new(),
+        }
+    }
+
+    /// Store `value` for `key`. The value of an existing key is replaced in
+    /// place, so the arena only grows with new keys. Returns false if the
+    /// key cannot be stored, such as when it has characters outside of the
+    /// alphabet, or the arena is full.
+    pub fn store(&mut self, key: &[AlphaChar], value: T) -> bool {
+        if let Some(index) = self.trie.get(key) {
+            self.values[index as usize] = value;
+            return true;
+        }
+        let Ok(index) = u32::try_from(self.values.len()) else {
+            return false;
+        };
+        if !self.trie.store(key, index) {
+            return false;
+        }
+        self.values.push(value);
+        true
+    }
+
+    pub fn retrieve(&self, key: &[AlphaChar]) -> Option<&T> {
+        self.values.get(*self.trie.retrieve(key)? as usize)
+    }
+
+    pub fn retrieve_mut(&mut self, key: &[AlphaChar]) -> Option<&mut T> {
+        self.values.get_mut(self.trie.get(key)? as usize)
+    }
+
+    pub fn contains_key(&self, key: &[AlphaChar]) -> bool {
+        self.trie.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.trie.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trie.is_empty()
+    }
+
+    /// Get the trie mapping the keys to indices into [values](Self::values)
+    pub fn trie(&self) -> &Trie<u32> {
+        &self.trie
+    }
+
+    /// Get the arena of values, in the order their keys were first stored
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Split into the trie and the arena, such as to save the trie and
+    /// the values separately
+    pub fn into_parts(self) -> (Trie<u32>, Vec<T>) {
+        (self.trie, self.values)
+    }
+}
@@ -0,0 +1,138 @@
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Deref;
+
+use crate::alpha_map::AlphaMap;
+use crate::types::AlphaChar;
+
+/// A key checked to follow the crate's key convention for an alphabet: it
+/// ends with a single terminating 0, and every other character is in the
+/// alphabet.
+///
+/// A key dereferences to `[AlphaChar]`, so it can be given to every method
+/// taking a key.
+///
+/// ```
+/// use datrie::{AlphaMap, Key, KeyError, Trie};
+///
+/// let mut alpha_map = AlphaMap::default();
+/// alpha_map.add_range('a' as u32..='z' as u32).unwrap();
+///
+/// let key = Key::new(vec!['a' as u32, 'b' as u32, 0], &alpha_map).unwrap();
+/// assert_eq!(Key::new(vec!['a' as u32], &alpha_map), Err(KeyError::MissingTerminator));
+///
+/// let mut trie = Trie::new(alpha_map);
+/// assert!(trie.store(&key, 1));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Key(Vec<AlphaChar>);
+
+impl Key {
+    /// Check `chars` against `alpha_map`, taking them as a key if they pass
+    pub fn new(chars: Vec<AlphaChar>, alpha_map: &AlphaMap) -> Result<Self, KeyError> {
+        match chars.iter().position(|ac| *ac == 0) {
+            None => return Err(KeyError::MissingTerminator),
+            Some(pos) if pos != chars.len() - 1 => {
+                return Err(KeyError::InteriorTerminator(pos));
+            }
+            Some(_) => {}
+        }
+        alpha_map
+            .validate_key(&chars)
+            .map_err(KeyError::CharNotInAlphaMap)?;
+        Ok(Self(chars))
+    }
+
+    /// Get the key without its terminator
+    pub fn chars(&self) -> &[AlphaChar] {
+        &self.0[..(self.0.len() - 1)]
+    }
+
+    pub fn into_vec(self) -> Vec<AlphaChar> {
+        self.0
+    }
+}
+
+impl Deref for Key {
+    type Target = [AlphaChar];
+
+    fn deref(&self) -> &[AlphaChar] {
+        &self.0
+    }
+}
+
+impl AsRef<[AlphaChar]> for Key {
+    fn as_ref(&self) -> &[AlphaChar] {
+        &self.0
+    }
+}
+
+/// Reason a key was rejected by [Key::new]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyError {
+    /// The key does not end with the terminating 0
+    MissingTerminator,
+    /// The key has a 0 at the given position before its end
+    InteriorTerminator(usize),
+    /// The key has a character outside of the alphabet
+    CharNotInAlphaMap(AlphaChar),
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::MissingTerminator => write!(f, "key does not end with 0"),
+            KeyError::InteriorTerminator(pos) => write!(f, "key has a 0 at position {}", pos),
+            KeyError::CharNotInAlphaMap(ac) => {
+                write!(f, "character {:#x} is outside of the alphabet", ac)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for KeyError {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use crate::alpha_map::AlphaMap;
+    use crate::key::{Key, KeyError};
+    use crate::types::AlphaChar;
+
+    #[test]
+    fn test_key() {
+        let mut alpha_map = AlphaMap::default();
+        alpha_map.add_range(0x61..=0x7a).unwrap();
+
+        let key = Key::new(vec![0x61, 0x62, 0], &alpha_map).unwrap();
+        assert_eq!(&*key, &[0x61, 0x62, 0]);
+        assert_eq!(key.chars(), &[0x61, 0x62]);
+        assert_eq!(
+            Key::new(vec![0], &alpha_map).unwrap().chars(),
+            &[] as &[AlphaChar]
+        );
+
+        assert_eq!(
+            Key::new(vec![], &alpha_map),
+            Err(KeyError::MissingTerminator)
+        );
+        assert_eq!(
+            Key::new(vec![0x61, 0x62], &alpha_map),
+            Err(KeyError::MissingTerminator)
+        );
+        assert_eq!(
+            Key::new(vec![0x61, 0, 0x62, 0], &alpha_map),
+            Err(KeyError::InteriorTerminator(1))
+        );
+        assert_eq!(
+            Key::new(vec![0x61, 0, 0], &alpha_map),
+            Err(KeyError::InteriorTerminator(1))
+        );
+        assert_eq!(
+            Key::new(vec![0x61, 0x36, 0], &alpha_map),
+            Err(KeyError::CharNotInAlphaMap(0x36))
+        );
+    }
+}
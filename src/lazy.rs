@@ -0,0 +1,210 @@
+use core::iter;
+use core::marker::PhantomData;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::alpha_map::AlphaMap;
+use crate::darray::LazyDArray;
+use crate::tail::LazyTail;
+use crate::trie::strip_terminator;
+use crate::types::*;
+
+/// A read-only trie left in its file, for tries too large to be loaded with
+/// [ROTrie::from_reader](crate::ROTrie::from_reader).
+///
+/// Only the alphabet and the position of each tail block are read up front.
+/// The cells of the double-array are read on demand, keeping the most
+/// recently used pages of them in memory, and the tail blocks are read each
+/// time a key reaches them. Lookups take `&mut self` as they move the reader,
+/// and return the I/O errors met on the way.
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// use datrie::{AlphaMap, AsAlphaChar, LazyROTrie, Trie};
+///
+/// let mut alpha_map = AlphaMap::default();
+/// alpha_map.add_range('a' as u32..='z' as u32).unwrap();
+/// let mut trie = Trie::new(alpha_map);
+/// trie.store(&"hello".as_alphachar(), 1);
+///
+/// let mut file = Vec::new();
+/// trie.serialize(&mut file).unwrap();
+///
+/// let mut lazy = LazyROTrie::<_, i32>::new(Cursor::new(file)).unwrap();
+/// assert_eq!(lazy.retrieve(&"hello".as_alphachar()).unwrap(), Some(1));
+/// assert_eq!(lazy.retrieve(&"help".as_alphachar()).unwrap(), None);
+/// ```
+pub struct LazyROTrie<R, TrieData> {
+    reader: R,
+    alpha_map: AlphaMap,
+    da: LazyDArray,
+    tail: LazyTail,
+    data: PhantomData<fn() -> TrieData>,
+}
+
+impl<TrieData: TrieDeserializable + Default> LazyROTrie<BufReader<File>, TrieData> {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek, TrieData: TrieDeserializable + Default> LazyROTrie<R, TrieData> {
+    /// Number of pages of double-array cells kept in memory by [new](Self::new)
+    pub const DEFAULT_CACHE_PAGES: usize = 1024;
+
+    /// Open the trie the reader is at, see [with_cache_pages](Self::with_cache_pages)
+    pub fn new(reader: R) -> io::Result<Self> {
+        Self::with_cache_pages(reader, Self::DEFAULT_CACHE_PAGES)
+    }
+
+    /// Open the trie the reader is at, keeping up to `cache_pages` pages of
+    /// 256 double-array cells in memory. The whole trie is read through
+    /// once to find the tail blocks.
+    pub fn with_cache_pages(mut reader: R, cache_pages: usize) -> io::Result<Self> {
+        let alpha_map = AlphaMap::read(&mut reader)?;
+        let da = LazyDArray::read(&mut reader, cache_pages)?;
+        let tail = LazyTail::read::<_, TrieData>(&mut reader)?;
+
+        Ok(Self {
+            reader,
+            alpha_map,
+            da,
+            tail,
+            data: PhantomData,
+        })
+    }
+
+    pub fn alpha_map(&self) -> &AlphaMap {
+        &self.alpha_map
+    }
+
+    /// Get the reader back. Its position is unspecified.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Same as [ROTrie::retrieve](crate::ROTrie::retrieve), but return a copy
+    /// of the data read from the file
+    pub fn retrieve(&mut self, key: &[AlphaChar]) -> io::Result<Option<TrieData>> {
+        // walk through branches
+        let mut s = self.da.get_root();
+        let mut key_iter = key.iter().copied();
+        let mut last_ch = ALPHA_CHAR_ERROR;
+        for ch in key_iter.by_ref() {
+            last_ch = ch;
+            if self.is_separate(s)? {
+                break;
+            }
+            let Some(tc) = self.alpha_map.char_to_trie(ch) else {
+                return Ok(None);
+            };
+            let Some(next) = self.da.walk(&mut self.reader, s, tc as TrieChar)? else {
+                return Ok(None);
+            };
+            s = next;
+            if ch == 0 {
+                break;
+            }
+        }
+
+        // walk through tail
+        let Some((suffix, data)) = self.get_tail_block(s)? else {
+            return Ok(None);
+        };
+        let mut suffix_idx = 0;
+        // start iterating from the last character
+        for ch in iter::once(last_ch).chain(key_iter) {
+            let Some(tc) = self.alpha_map.char_to_trie(ch) else {
+                return Ok(None);
+            };
+            if suffix_char(&suffix, suffix_idx)? != tc as TrieChar {
+                return Ok(None);
+            }
+            if tc as TrieChar != TRIE_CHAR_TERM {
+                suffix_idx += 1;
+            }
+        }
+
+        Ok(Some(data))
+    }
+
+    /// Check whether `key` is stored in the trie
+    pub fn contains_key(&mut self, key: &[AlphaChar]) -> io::Result<bool> {
+        Ok(self.retrieve(key)?.is_some())
+    }
+
+    /// Same as [ROTrie::walk_prefix](crate::ROTrie::walk_prefix). Returns
+    /// the number of characters matched and whether the matched prefix is
+    /// itself a key.
+    pub fn walk_prefix(&mut self, key: &[AlphaChar]) -> io::Result<(usize, bool)> {
+        let key = strip_terminator(key);
+
+        // walk through branches
+        let mut s = self.da.get_root();
+        let mut matched = 0;
+        while !self.is_separate(s)? {
+            let next = match key.get(matched) {
+                Some(ch) => match self.alpha_map.char_to_trie(*ch) {
+                    Some(tc) => self.da.walk(&mut self.reader, s, tc as TrieChar)?,
+                    None => None,
+                },
+                None => None,
+            };
+            let Some(next) = next else {
+                let is_terminal = self.da.walk(&mut self.reader, s, TRIE_CHAR_TERM)?;
+                return Ok((matched, is_terminal.is_some()));
+            };
+            s = next;
+            matched += 1;
+        }
+
+        // walk through tail
+        let Some((suffix, _)) = self.get_tail_block(s)? else {
+            return Ok((matched, false));
+        };
+        let mut suffix_idx = 0;
+        for ch in &key[matched..] {
+            match self.alpha_map.char_to_trie(*ch) {
+                Some(tc) if suffix_char(&suffix, suffix_idx)? == tc as TrieChar => {
+                    suffix_idx += 1;
+                    matched += 1;
+                }
+                _ => break,
+            }
+        }
+        Ok((matched, suffix_char(&suffix, suffix_idx)? == TRIE_CHAR_TERM))
+    }
+
+    fn is_separate(&mut self, s: TrieIndex) -> io::Result<bool> {
+        Ok(self
+            .da
+            .get_base(&mut self.reader, s)?
+            .is_some_and(|base| base < 0))
+    }
+
+    /// Read the tail block of the separate node `s`
+    fn get_tail_block(&mut self, s: TrieIndex) -> io::Result<Option<(Arc<[TrieChar]>, TrieData)>> {
+        // base may be garbage in a corrupt file
+        let t = self
+            .da
+            .get_base(&mut self.reader, s)?
+            .and_then(|base| base.checked_neg());
+        match t {
+            Some(t) => self.tail.get(&mut self.reader, t),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Get the character at `index` of a suffix read from the file, failing
+/// instead of panicking if the walk goes past its end
+fn suffix_char(suffix: &[TrieChar], index: usize) -> io::Result<TrieChar> {
+    suffix
+        .get(index)
+        .copied()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "walk past the end of a suffix"))
+}
@@ -3,24 +3,48 @@
 extern crate alloc;
 
 pub use types::{
-    AlphaChar, AlphaCharToString, AsAlphaChar, TrieChar, TrieIndex, ALPHA_CHAR_ERROR,
-    TRIE_CHAR_MAX, TRIE_CHAR_TERM, TRIE_INDEX_ERROR, TRIE_INDEX_MAX,
+    AlphaChar, AlphaCharToString, AlphaCharToStringLossy, AsAlphaChar, DisplayKey, TrieChar,
+    TrieIndex, ALPHA_CHAR_ERROR, TRIE_CHAR_MAX, TRIE_CHAR_TERM, TRIE_INDEX_ERROR, TRIE_INDEX_MAX,
 };
 #[cfg(feature = "std")]
 pub use types::{TrieDeserializable, TrieSerializable};
 
-pub use alpha_map::{AlphaMap, ToAlphaChars, ToTrieChar};
+pub use alpha_map::{AlphaMap, AlphaMapError, ToAlphaChars, ToTrieChar};
 
-pub use trie::{ROTrie, Trie, TrieIterator, TrieState};
+pub use builder::{BuildError, TrieBuilder};
+
+pub use interned::InternedTrie;
+
+pub use key::{Key, KeyError};
+
+pub use symbols::WalkableSymbols;
+
+#[cfg(feature = "lazy")]
+pub use lazy::LazyROTrie;
+
+#[cfg(feature = "low-level")]
+pub use darray::DArray;
+#[cfg(feature = "low-level")]
+pub use tail::Tail;
+
+pub use trie::{
+    CorruptError, Entry, NodeKind, OccupiedEntry, ROTrie, StoreError, Trie, TrieCursor,
+    TrieIterator, TrieSnapshot, TrieState, TrieStats, VacantEntry,
+};
 
 pub use types_c::CTrieData;
 pub use types_c::TRIE_DATA_ERROR;
 
 #[cfg_attr(not(feature = "cffi"), deny(unsafe_code))]
 pub mod alpha_map;
+mod builder;
 mod darray;
 #[cfg(feature = "cffi")]
 mod fileutils;
+mod interned;
+mod key;
+#[cfg(feature = "lazy")]
+mod lazy;
 mod symbols;
 mod tail;
 pub mod trie;
@@ -1,10 +1,12 @@
 use crate::types::TrieChar;
 use alloc::slice::Iter;
 use arrayvec::ArrayVec;
+use core::iter::FusedIterator;
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub(crate) struct Symbols {
-    symbols: ArrayVec<TrieChar, { TrieChar::MAX as usize }>,
+    // every TrieChar value including the terminator can be an output symbol
+    symbols: ArrayVec<TrieChar, { TrieChar::MAX as usize + 1 }>,
 }
 
 impl Symbols {
@@ -35,9 +37,65 @@ impl Symbols {
     }
 }
 
+/// Iterator over the characters that can be walked from a
+/// [TrieState](crate::TrieState), as trie characters in ascending order.
+/// They can be mapped back with [ToAlphaChars](crate::ToAlphaChars).
+///
+/// Returned by [TrieState::walkable_symbols](crate::TrieState::walkable_symbols).
+#[derive(Clone, Debug)]
+pub struct WalkableSymbols {
+    symbols: Symbols,
+    front: usize,
+    back: usize,
+}
+
+impl WalkableSymbols {
+    pub(crate) fn new(symbols: Symbols) -> Self {
+        let back = symbols.num();
+        Self {
+            symbols,
+            front: 0,
+            back,
+        }
+    }
+}
+
+impl Iterator for WalkableSymbols {
+    type Item = TrieChar;
+
+    fn next(&mut self) -> Option<TrieChar> {
+        if self.front == self.back {
+            return None;
+        }
+        self.front += 1;
+        self.symbols.get(self.front - 1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.back - self.front, Some(self.back - self.front))
+    }
+}
+
+impl DoubleEndedIterator for WalkableSymbols {
+    fn next_back(&mut self) -> Option<TrieChar> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.symbols.get(self.back)
+    }
+}
+
+impl ExactSizeIterator for WalkableSymbols {}
+
+impl FusedIterator for WalkableSymbols {}
+
 #[cfg(test)]
 mod tests {
-    use crate::symbols::Symbols;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    use crate::symbols::{Symbols, WalkableSymbols};
     use crate::types::TrieChar;
 
     #[test]
@@ -64,12 +122,29 @@ mod tests {
     #[test]
     fn test_symbol_large() {
         let mut symbols = Symbols::default();
-        for i in (0..TrieChar::MAX).rev() {
+        for i in (0..=TrieChar::MAX).rev() {
             symbols.add(i)
         }
-        assert_eq!(symbols.num(), TrieChar::MAX as usize);
-        for i in 0..TrieChar::MAX as usize {
+        assert_eq!(symbols.num(), TrieChar::MAX as usize + 1);
+        for i in 0..=TrieChar::MAX as usize {
             assert_eq!(symbols.get(i), Some(i as TrieChar));
         }
     }
+
+    #[test]
+    fn test_walkable_symbols() {
+        let mut symbols = Symbols::default();
+        for i in [3, 1, 2] {
+            symbols.add(i)
+        }
+        let mut iter = WalkableSymbols::new(symbols);
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next_back(), Some(3));
+        assert_eq!(iter.clone().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.len(), 0);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
 }
@@ -1,26 +1,127 @@
-use alloc::boxed::Box;
+use alloc::sync::Arc;
+#[cfg(feature = "std")]
 use alloc::vec;
 use alloc::vec::Vec;
+use core::iter;
 #[cfg(feature = "std")]
 use std::io;
 #[cfg(feature = "std")]
 use std::io::{Read, Write};
+#[cfg(feature = "lazy")]
+use std::io::{Seek, SeekFrom};
 
 #[cfg(feature = "std")]
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
+use crate::trie::{CorruptError, StoreError};
 use crate::types::*;
 
+/// The suffixes of a trie and their data. Only exposed with the `low-level`
+/// feature.
 #[derive(Default)]
-pub(crate) struct Tail<TrieData> {
-    tails: Vec<TailBlock<TrieData>>,
+pub struct Tail<TrieData> {
+    /// Blocks, which may be shared with a snapshot of the trie
+    tails: Arc<Vec<TailBlock<TrieData>>>,
     first_free: TrieIndex,
+    /// Number of blocks in use, i.e. not in the free list
+    num_used: usize,
+    /// Copy the shared blocks before modifying them. Set by
+    /// [share](Self::share), where the data is known to be cloneable.
+    copy_blocks: Option<CopyBlocks<TrieData>>,
+}
+
+type CopyBlocks<TrieData> = fn(&[TailBlock<TrieData>]) -> Vec<TailBlock<TrieData>>;
+
+/// Cloning copies the blocks, so the clone can be modified without
+/// knowing how to copy them again
+impl<TrieData: Clone> Clone for Tail<TrieData> {
+    fn clone(&self) -> Self {
+        Self {
+            tails: Arc::new(self.tails.to_vec()),
+            first_free: self.first_free,
+            num_used: self.num_used,
+            copy_blocks: None,
+        }
+    }
 }
 
+impl<TrieData: Clone> Tail<TrieData> {
+    /// Get a copy of the tail sharing the blocks, which are only copied when
+    /// either side is modified
+    pub(crate) fn share(&mut self) -> Self {
+        self.copy_blocks = Some(|blocks| blocks.to_vec());
+        Self {
+            tails: Arc::clone(&self.tails),
+            first_free: self.first_free,
+            num_used: self.num_used,
+            copy_blocks: self.copy_blocks,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
 const TAIL_SIGNATURE: u32 = 0xdffcdffc;
+/// Signature of a tail whose data is not the `i32` of libdatrie, followed by
+/// the [DATA_TAG](TrieSerializable::DATA_TAG) of the data as u32
+#[cfg(feature = "std")]
+const TAGGED_TAIL_SIGNATURE: u32 = 0xdffcdffd;
 const TAIL_START_BLOCKNO: TrieIndex = 1;
 
 impl<TrieData: Default> Tail<TrieData> {
+    /// Create a tail from its blocks, where the block at position `i` has
+    /// the tail index `i + 1`. Each block is either a suffix without the
+    /// terminator and its data, or `None` for a free block.
+    #[cfg(feature = "low-level")]
+    pub fn from_blocks(blocks: Vec<Option<(Vec<TrieChar>, TrieData)>>) -> Self {
+        let mut tail = Self::default();
+        let mut tails = Vec::with_capacity(blocks.len());
+        let mut last_free: Option<usize> = None;
+        for (i, block) in blocks.into_iter().enumerate() {
+            match block {
+                Some((mut suffix, data)) => {
+                    suffix.push(TRIE_CHAR_TERM);
+                    tails.push(TailBlock {
+                        next_free: -1,
+                        data,
+                        suffix: Some(suffix.into()),
+                    });
+                    tail.num_used += 1;
+                }
+                None => {
+                    tails.push(TailBlock {
+                        next_free: 0,
+                        data: Default::default(),
+                        suffix: None,
+                    });
+                    // 0 ends the free list, so like in free_block() the
+                    // first block can't be linked into it
+                    if i == 0 {
+                        continue;
+                    }
+                    match last_free {
+                        Some(j) => tails[j].next_free = i as TrieIndex,
+                        None => tail.first_free = i as TrieIndex,
+                    }
+                    last_free = Some(i);
+                }
+            }
+        }
+        tail.tails = Arc::new(tails);
+        tail
+    }
+
+    /// Get the blocks for modification, copying them first if they are
+    /// shared with a snapshot
+    fn tails_mut(&mut self) -> &mut Vec<TailBlock<TrieData>> {
+        if Arc::get_mut(&mut self.tails).is_none() {
+            // unwrap as an assertion since only share() shares the blocks
+            let copy_blocks = self.copy_blocks.unwrap();
+            self.tails = Arc::new(copy_blocks(&self.tails));
+        }
+        // unwrap as an assertion since the blocks are no longer shared
+        Arc::get_mut(&mut self.tails).unwrap()
+    }
+
     pub(crate) fn get_suffix(&self, index: TrieIndex) -> Option<&[TrieChar]> {
         let index = index - TAIL_START_BLOCKNO;
         match self.tails.get(index as usize).map(|v| &v.suffix) {
@@ -29,21 +130,28 @@ impl<TrieData: Default> Tail<TrieData> {
         }
     }
 
-    pub(crate) fn set_suffix(&mut self, index: TrieIndex, suffix: Option<Box<[TrieChar]>>) -> bool {
+    pub(crate) fn set_suffix(&mut self, index: TrieIndex, suffix: Option<Arc<[TrieChar]>>) -> bool {
         let index = (index - TAIL_START_BLOCKNO) as usize;
 
         if index >= self.tails.len() {
             return false;
         }
 
-        self.tails[index].suffix = suffix;
+        self.tails_mut()[index].suffix = suffix;
         true
     }
 
-    pub(crate) fn add_suffix(&mut self, suffix: Option<Box<[TrieChar]>>) -> TrieIndex {
-        let new_block = self.alloc_block();
+    /// Add a block with `suffix`, returning its index. Fails with
+    /// [IndexOverflow](StoreError::IndexOverflow) if the index would be past
+    /// TRIE_INDEX_MAX, or [OutOfMemory](StoreError::OutOfMemory) if the
+    /// block cannot be allocated.
+    pub(crate) fn add_suffix(
+        &mut self,
+        suffix: Option<Arc<[TrieChar]>>,
+    ) -> Result<TrieIndex, StoreError> {
+        let new_block = self.alloc_block()?;
         self.set_suffix(new_block, suffix);
-        new_block
+        Ok(new_block)
     }
 
     pub(crate) fn get_data(&self, index: TrieIndex) -> Option<&TrieData> {
@@ -51,9 +159,46 @@ impl<TrieData: Default> Tail<TrieData> {
         self.tails.get(index as usize).map(|v| &v.data)
     }
 
+    pub(crate) fn get_data_mut(&mut self, index: TrieIndex) -> Option<&mut TrieData> {
+        let index = index - TAIL_START_BLOCKNO;
+        self.tails_mut()
+            .get_mut(index as usize)
+            .map(|v| &mut v.data)
+    }
+
+    /// Same as [get_data_mut](Self::get_data_mut), but for many blocks at
+    /// once, each given with a value `K` returned alongside its data. The
+    /// entries are returned in the given order, skipping the indices that
+    /// are out of range or given more than once.
+    pub(crate) fn get_data_mut_many<K>(
+        &mut self,
+        entries: Vec<(TrieIndex, K)>,
+    ) -> Vec<(K, &mut TrieData)> {
+        let mut order: Vec<usize> = (0..entries.len()).collect();
+        order.sort_by_key(|i| entries[*i].0);
+
+        let mut entries: Vec<_> = entries.into_iter().map(Some).collect();
+        let mut found: Vec<_> = iter::repeat_with(|| None).take(entries.len()).collect();
+        let mut order = order.into_iter().peekable();
+        for (i, block) in self.tails_mut().iter_mut().enumerate() {
+            let index = i as TrieIndex + TAIL_START_BLOCKNO;
+            while order
+                .next_if(|pos| entries[*pos].as_ref().unwrap().0 < index)
+                .is_some()
+            {}
+            let Some(pos) = order.next_if(|pos| entries[*pos].as_ref().unwrap().0 == index) else {
+                continue;
+            };
+            // unwrap as an assertion since each position is only taken once
+            let (_, value) = entries[pos].take().unwrap();
+            found[pos] = Some((value, &mut block.data));
+        }
+        found.into_iter().flatten().collect()
+    }
+
     pub(crate) fn set_data(&mut self, index: TrieIndex, data: TrieData) -> Option<()> {
         let index = index - TAIL_START_BLOCKNO;
-        match self.tails.get_mut(index as usize) {
+        match self.tails_mut().get_mut(index as usize) {
             Some(block) => {
                 block.data = data;
                 Some(())
@@ -66,6 +211,110 @@ impl<TrieData: Default> Tail<TrieData> {
         self.free_block(index);
     }
 
+    /// Same as [delete](Self::delete), but for many blocks at once
+    pub(crate) fn delete_many(&mut self, mut indices: Vec<TrieIndex>) {
+        indices.sort_unstable();
+        self.free_blocks(&indices);
+    }
+
+    /// Free the memory not used by the blocks
+    pub(crate) fn shrink_to_fit(&mut self) {
+        if let Some(tails) = Arc::get_mut(&mut self.tails) {
+            tails.shrink_to_fit();
+        }
+    }
+
+    /// Remove all blocks, keeping the allocated capacity
+    pub(crate) fn clear(&mut self) {
+        match Arc::get_mut(&mut self.tails) {
+            Some(tails) => tails.clear(),
+            None => self.tails = Arc::default(),
+        }
+        self.first_free = 0;
+        self.num_used = 0;
+    }
+
+    /// Get the number of blocks in use. As each key owns a block, this is
+    /// also the number of keys in the trie.
+    pub(crate) fn num_used(&self) -> usize {
+        self.num_used
+    }
+
+    /// Get the number of blocks, including the free ones
+    pub(crate) fn num_blocks(&self) -> usize {
+        self.tails.len()
+    }
+
+    /// Iterate the blocks in use with their index and suffix, which includes
+    /// the terminator
+    pub(crate) fn used_blocks(&self) -> impl Iterator<Item = (TrieIndex, &[TrieChar])> {
+        self.tails.iter().enumerate().filter_map(|(i, block)| {
+            let suffix = block.suffix.as_deref().filter(|_| block.is_used())?;
+            Some((i as TrieIndex + TAIL_START_BLOCKNO, suffix))
+        })
+    }
+
+    /// Get the total length of the suffixes, without their terminators
+    pub(crate) fn suffix_bytes(&self) -> usize {
+        self.tails
+            .iter()
+            .filter_map(|block| block.suffix.as_ref())
+            .map(|suffix| suffix.len().saturating_sub(1))
+            .sum()
+    }
+
+    /// Same as [suffix_bytes](Self::suffix_bytes), but count the suffixes
+    /// shared by several blocks once
+    pub(crate) fn stored_suffix_bytes(&self) -> usize {
+        let mut suffixes: Vec<&Arc<[TrieChar]>> = self
+            .tails
+            .iter()
+            .filter_map(|block| block.suffix.as_ref())
+            .collect();
+        suffixes.sort_unstable_by_key(|suffix| Arc::as_ptr(suffix) as *const TrieChar);
+        suffixes.dedup_by(|a, b| Arc::ptr_eq(a, b));
+        suffixes
+            .iter()
+            .map(|suffix| suffix.len().saturating_sub(1))
+            .sum()
+    }
+
+    /// Make the blocks in use with equal suffixes share a single copy of
+    /// it. A block given a new suffix later gets its own copy, leaving the
+    /// other blocks alone. Returns the number of copies dropped.
+    pub(crate) fn share_suffixes(&mut self) -> usize {
+        let mut order: Vec<usize> = (0..self.tails.len())
+            .filter(|i| self.tails[*i].is_used() && self.tails[*i].suffix.is_some())
+            .collect();
+        if order.len() < 2 {
+            return 0;
+        }
+        // unwrap as an assertion since only blocks with a suffix are kept
+        let suffix_of = |i: usize| self.tails[i].suffix.as_ref().unwrap();
+        order.sort_by(|a, b| suffix_chars(suffix_of(*a)).cmp(suffix_chars(suffix_of(*b))));
+
+        // pair each block with the first block of its run of equal suffixes,
+        // so that the blocks are only copied from a snapshot if needed
+        let mut shared = Vec::new();
+        let mut first = order[0];
+        for i in order[1..].iter().copied() {
+            if suffix_chars(suffix_of(i)) != suffix_chars(suffix_of(first)) {
+                first = i;
+            } else if !Arc::ptr_eq(suffix_of(i), suffix_of(first)) {
+                shared.push((i, first));
+            }
+        }
+        if shared.is_empty() {
+            return 0;
+        }
+
+        let tails = self.tails_mut();
+        for (i, first) in shared.iter().copied() {
+            tails[i].suffix = tails[first].suffix.clone();
+        }
+        shared.len()
+    }
+
     /// Walk in tail with a string
     ///
     /// Walk in the tail data `t` at entry `s`, from given character position
@@ -119,42 +368,88 @@ impl<TrieData: Default> Tail<TrieData> {
         self.get_suffix(s).unwrap()[suffix_idx as usize] == c
     }
 
-    fn alloc_block(&mut self) -> TrieIndex {
+    /// Check whether `index` is a block in use with a suffix
+    pub(crate) fn is_used_block(&self, index: TrieIndex) -> bool {
+        index
+            .checked_sub(TAIL_START_BLOCKNO)
+            .and_then(|index| self.tails.get(index as usize))
+            .is_some_and(|block| block.is_used() && block.suffix.is_some())
+    }
+
+    /// Check that the free list only links free blocks in increasing order
+    pub(crate) fn validate(&self) -> Result<(), CorruptError> {
+        let mut prev = None;
+        let mut i = self.first_free;
+        while i != 0 {
+            let block = usize::try_from(i).ok().and_then(|i| self.tails.get(i));
+            if block.is_none_or(|block| block.is_used()) || prev.is_some_and(|prev| i <= prev) {
+                return Err(CorruptError::TailFreeList(i));
+            }
+            prev = Some(i);
+            i = block.unwrap().next_free;
+        }
+        Ok(())
+    }
+
+    fn alloc_block(&mut self) -> Result<TrieIndex, StoreError> {
         let block_idx;
         if self.first_free != 0 {
             block_idx = self.first_free;
             self.first_free = self.tails[block_idx as usize].next_free;
 
-            self.tails[block_idx as usize].reset();
+            self.tails_mut()[block_idx as usize].reset();
         } else {
-            block_idx = self.tails.len() as TrieIndex;
-            self.tails.push(TailBlock::default());
+            block_idx = TrieIndex::try_from(self.tails.len())
+                .ok()
+                .filter(|idx| *idx < TRIE_INDEX_MAX - TAIL_START_BLOCKNO)
+                .ok_or(StoreError::IndexOverflow)?;
+            let tails = self.tails_mut();
+            tails.try_reserve(1).map_err(StoreError::OutOfMemory)?;
+            tails.push(TailBlock::default());
         }
+        self.num_used += 1;
 
-        block_idx + TAIL_START_BLOCKNO
+        Ok(block_idx + TAIL_START_BLOCKNO)
     }
 
     fn free_block(&mut self, block: TrieIndex) {
-        let block_idx = (block - TAIL_START_BLOCKNO) as usize;
+        self.free_blocks(&[block])
+    }
 
-        // find insertion point
+    /// Free the sorted `blocks` in a single pass over the free list
+    fn free_blocks(&mut self, blocks: &[TrieIndex]) {
         let mut j = 0;
         let mut i = self.first_free as usize;
-        while i != 0 && i < block_idx {
-            j = i;
-            i = self.tails[i].next_free as usize;
-        }
+        for block in blocks.iter().copied() {
+            let block_idx = (block - TAIL_START_BLOCKNO) as usize;
 
-        let Some(block) = self.tails.get_mut(block_idx) else {
-            return;
-        };
-        block.reset();
-        block.next_free = i as TrieIndex;
+            // find insertion point, which only moves forward as blocks are sorted
+            while i != 0 && i < block_idx {
+                j = i;
+                i = self.tails[i].next_free as usize;
+            }
 
-        if j != 0 {
-            self.tails[j].next_free = block_idx as TrieIndex;
-        } else {
-            self.first_free = block_idx as TrieIndex;
+            let Some(block) = self.tails.get(block_idx) else {
+                return;
+            };
+            if block.is_used() {
+                self.num_used -= 1;
+            }
+            let block = &mut self.tails_mut()[block_idx];
+            block.reset();
+            block.next_free = i as TrieIndex;
+
+            if j != 0 {
+                self.tails_mut()[j].next_free = block_idx as TrieIndex;
+            } else {
+                self.first_free = block_idx as TrieIndex;
+            }
+            j = block_idx;
+            if block_idx == 0 {
+                // 0 also ends the free list, which is then left empty like
+                // freeing the blocks one by one would
+                i = 0;
+            }
         }
     }
 }
@@ -162,11 +457,16 @@ impl<TrieData: Default> Tail<TrieData> {
 #[cfg(feature = "std")]
 impl<TrieData: TrieSerializable> Tail<TrieData> {
     pub(crate) fn serialize<T: Write>(&self, writer: &mut T) -> io::Result<()> {
-        writer.write_u32::<BigEndian>(TAIL_SIGNATURE)?;
+        if TrieData::DATA_TAG == 0 {
+            writer.write_u32::<BigEndian>(TAIL_SIGNATURE)?;
+        } else {
+            writer.write_u32::<BigEndian>(TAGGED_TAIL_SIGNATURE)?;
+            writer.write_u32::<BigEndian>(TrieData::DATA_TAG)?;
+        }
         writer.write_i32::<BigEndian>(self.first_free)?;
         writer.write_i32::<BigEndian>(self.tails.len() as i32)?;
 
-        for block in &self.tails {
+        for block in self.tails.iter() {
             writer.write_i32::<BigEndian>(block.next_free)?;
             block.data.serialize(writer)?;
 
@@ -193,7 +493,13 @@ impl<TrieData: TrieSerializable> Tail<TrieData> {
         const SIZE_OF_BLOCK: usize = size_of::<TrieIndex>() // next_free
             + size_of::<i16>(); // length
 
+        let tag_size = match TrieData::DATA_TAG {
+            0 => 0,
+            _ => size_of::<u32>(),
+        };
+
         size_of::<i32>() // TAIL_SIGNATURE
+            + tag_size
             + size_of::<TrieIndex>() // first_free
             + size_of::<TrieIndex>() // num_tails
             + (SIZE_OF_BLOCK * self.tails.len() as usize)
@@ -206,14 +512,45 @@ impl<TrieData: TrieSerializable> Tail<TrieData> {
 #[cfg(feature = "std")]
 impl<TrieData: TrieDeserializable + Default> Tail<TrieData> {
     pub(crate) fn read<T: Read>(reader: &mut T) -> io::Result<Self> {
-        if reader.read_u32::<BigEndian>()? != TAIL_SIGNATURE {
+        let mut tail = Self::default();
+        let num_tails;
+        (tail.first_free, num_tails) = Self::read_header(reader)?;
+
+        let mut blocks = Vec::with_capacity(num_tails as usize);
+        for _ in 0..num_tails {
+            blocks.push(Self::read_block(reader)?);
+        }
+
+        tail.num_used = blocks.iter().filter(|block| block.is_used()).count();
+        tail.tails = Arc::new(blocks);
+
+        Ok(tail)
+    }
+
+    /// Read the signature, the head of the free list and the number of blocks.
+    /// The tag of the data in the tail must be that of `TrieData`, or missing
+    /// if `TrieData` reads untagged data.
+    fn read_header<T: Read>(reader: &mut T) -> io::Result<(TrieIndex, TrieIndex)> {
+        let matches = match reader.read_u32::<BigEndian>()? {
+            TAIL_SIGNATURE => TrieData::READS_UNTAGGED,
+            TAGGED_TAIL_SIGNATURE => reader.read_u32::<BigEndian>()? == TrieData::DATA_TAG,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid signature",
+                ))
+            }
+        };
+        if !matches {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
-                "invalid signature",
+                format!(
+                    "data does not match the expected tag {:#x}",
+                    TrieData::DATA_TAG
+                ),
             ));
         }
-        let mut tail = Self::default();
-        tail.first_free = reader.read_i32::<BigEndian>()?;
+        let first_free = reader.read_i32::<BigEndian>()?;
         let num_tails = reader.read_i32::<BigEndian>()?;
 
         if num_tails as isize > isize::MAX {
@@ -223,33 +560,77 @@ impl<TrieData: TrieDeserializable + Default> Tail<TrieData> {
             ));
         }
 
-        let mut blocks = Vec::with_capacity(num_tails as usize);
+        Ok((first_free, num_tails))
+    }
 
-        for _ in 0..num_tails {
-            let mut block = TailBlock {
-                next_free: reader.read_i32::<BigEndian>()?,
-                data: TrieData::deserialize(reader)?,
-                suffix: None,
-            };
+    fn read_block<T: Read>(reader: &mut T) -> io::Result<TailBlock<TrieData>> {
+        let mut block = TailBlock {
+            next_free: reader.read_i32::<BigEndian>()?,
+            data: TrieData::deserialize(reader)?,
+            suffix: None,
+        };
 
-            let length = reader.read_i16::<BigEndian>()?;
-            if length > 0 {
-                let mut suffix = vec![TRIE_CHAR_TERM; (length + 1) as usize];
-                reader.read_exact(&mut suffix[..(length as usize)])?;
-                suffix[length as usize] = TRIE_CHAR_TERM;
+        let length = reader.read_i16::<BigEndian>()?;
+        if length > 0 {
+            let mut suffix = vec![TRIE_CHAR_TERM; (length + 1) as usize];
+            reader.read_exact(&mut suffix[..(length as usize)])?;
+            suffix[length as usize] = TRIE_CHAR_TERM;
 
-                block.suffix = Some(suffix.into_boxed_slice());
-            } else {
-                // In the C version the reader always create suffix
-                block.suffix = Some(Box::new([TRIE_CHAR_TERM]));
-            }
+            block.suffix = Some(suffix.into());
+        } else {
+            // In the C version the reader always create suffix. Free
+            // blocks get one too, which is harmless as alloc_block
+            // resets it, and the free list is read from next_free as is.
+            block.suffix = Some(Arc::new([TRIE_CHAR_TERM]));
+        }
 
-            blocks.push(block);
+        Ok(block)
+    }
+}
+
+/// A tail left in a trie file, whose blocks are read on demand. Only the
+/// position of each block is kept in memory.
+#[cfg(feature = "lazy")]
+pub(crate) struct LazyTail {
+    offsets: Vec<u64>,
+}
+
+#[cfg(feature = "lazy")]
+impl LazyTail {
+    /// Read through the tail the reader is at, recording where each block
+    /// starts
+    pub(crate) fn read<T: Read + Seek, TrieData: TrieDeserializable + Default>(
+        reader: &mut T,
+    ) -> io::Result<Self> {
+        let (_, num_tails) = Tail::<TrieData>::read_header(reader)?;
+
+        let mut offsets = Vec::with_capacity(num_tails as usize);
+        for _ in 0..num_tails {
+            offsets.push(reader.stream_position()?);
+            Tail::<TrieData>::read_block(reader)?;
         }
 
-        tail.tails = blocks;
+        Ok(Self { offsets })
+    }
 
-        Ok(tail)
+    /// Read the suffix and the data of block `index`, if it is in use
+    pub(crate) fn get<T: Read + Seek, TrieData: TrieDeserializable + Default>(
+        &self,
+        reader: &mut T,
+        index: TrieIndex,
+    ) -> io::Result<Option<(Arc<[TrieChar]>, TrieData)>> {
+        let Some(offset) = index
+            .checked_sub(TAIL_START_BLOCKNO)
+            .and_then(|index| self.offsets.get(index as usize))
+        else {
+            return Ok(None);
+        };
+        reader.seek(SeekFrom::Start(*offset))?;
+        let block = Tail::<TrieData>::read_block(reader)?;
+        if !block.is_used() {
+            return Ok(None);
+        }
+        Ok(block.suffix.map(|suffix| (suffix, block.data)))
     }
 }
 
@@ -257,7 +638,16 @@ impl<TrieData: TrieDeserializable + Default> Tail<TrieData> {
 pub(crate) struct TailBlock<TrieData> {
     next_free: TrieIndex,
     data: TrieData,
-    suffix: Option<Box<[TrieChar]>>,
+    /// Suffix with its terminator. It is never modified in place, so blocks
+    /// with the same suffix can share it, see [share_suffixes](Tail::share_suffixes).
+    suffix: Option<Arc<[TrieChar]>>,
+}
+
+impl<TrieData> TailBlock<TrieData> {
+    /// Blocks in the free list always have a non-negative `next_free`
+    fn is_used(&self) -> bool {
+        self.next_free == -1
+    }
 }
 
 impl<TrieData: Default> TailBlock<TrieData> {
@@ -277,3 +667,10 @@ impl<TrieData: Default> Default for TailBlock<TrieData> {
         }
     }
 }
+
+/// Get the characters of `suffix` before its terminator. The characters after
+/// it are left over from splitting the suffix, and do not matter.
+fn suffix_chars(suffix: &[TrieChar]) -> &[TrieChar] {
+    let end = suffix.iter().position(|tc| *tc == TRIE_CHAR_TERM);
+    &suffix[..end.unwrap_or(suffix.len())]
+}
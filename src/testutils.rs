@@ -9,7 +9,7 @@ pub type TestTrie = Trie<i32>;
 
 pub fn en_alpha_map_new() -> AlphaMap {
     let mut en_map = AlphaMap::default();
-    en_map.add_range(0x0061..=0x007a);
+    en_map.add_range(0x0061..=0x007a).unwrap();
 
     en_map
 }
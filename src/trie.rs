@@ -1,28 +1,55 @@
 use alloc::borrow::Cow;
+use alloc::collections::BTreeSet;
+use alloc::collections::TryReserveError;
 use alloc::vec;
 use alloc::vec::Vec;
-use core::iter;
+use core::cmp::Ordering;
+use core::hash::{Hash, Hasher};
 use core::ops::Deref;
+use core::{fmt, iter, mem, ptr};
 #[cfg(feature = "std")]
 use std::fs::File;
 #[cfg(feature = "std")]
 use std::io;
+#[cfg(feature = "flate2")]
+use std::io::BufRead;
 #[cfg(feature = "std")]
-use std::io::{BufReader, BufWriter, Read, Write};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
 #[cfg(feature = "std")]
 use std::path::Path;
+#[cfg(feature = "mmap")]
+use std::sync::Arc;
+
+#[cfg(feature = "std")]
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "flate2")]
+use flate2::{bufread::DeflateDecoder, write::DeflateEncoder, Compression};
+#[cfg(feature = "mmap")]
+use memmap2::Mmap;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 
 use crate::alpha_map::{AlphaMap, ToAlphaChars};
+use crate::builder::TrieBuilder;
 use crate::darray::DArray;
+use crate::symbols::{Symbols, WalkableSymbols};
 use crate::tail::Tail;
 use crate::types::TRIE_CHAR_TERM;
 use crate::types::*;
 
+#[derive(Clone)]
 pub struct Trie<TrieData: Default> {
     ro: ROTrie<TrieData>,
     is_dirty: bool,
 }
 
+/// Contents of a trie saved by [Trie::snapshot], to be put back with
+/// [Trie::restore]
+#[derive(Clone)]
+pub struct TrieSnapshot<TrieData: Default> {
+    ro: ROTrie<TrieData>,
+}
+
 impl<TrieData: Default> Trie<TrieData> {
     /// Create a new empty trie object based on the given `alpha_map` alphabet
     /// set. The trie contents can then be added and deleted with trie.store() and
@@ -42,40 +69,290 @@ impl<TrieData: Default> Trie<TrieData> {
         self.ro
     }
 
+    /// Same as [into_ro](Self::into_ro), but [validate](Self::validate) the
+    /// trie first, such as to catch bugs once a trie is built.
+    pub fn finish(self) -> Result<ROTrie<TrieData>, CorruptError> {
+        self.ro.validate()?;
+        Ok(self.ro)
+    }
+
     /// Check if the trie is dirty with some pending changes and needs saving
     /// to keep the file synchronized.
     pub fn is_dirty(&self) -> bool {
         self.is_dirty
     }
 
+    /// Remove all keys from the trie, keeping its alphabet. The memory
+    /// allocated for the trie is kept to be reused by later insertions.
+    pub fn clear(&mut self) {
+        self.ro.da.clear();
+        self.ro.tail.clear();
+        self.is_dirty = true;
+    }
+
+    /// Grow the double-array by `additional_cells` free cells ahead of bulk
+    /// insertions, to avoid growing it repeatedly. The free cells are part of
+    /// the saved trie until they are used.
+    ///
+    /// Returns false if the double-array cannot grow that large.
+    pub fn reserve(&mut self, additional_cells: usize) -> bool {
+        self.try_reserve(additional_cells).is_ok()
+    }
+
+    /// Same as [reserve](Self::reserve), but tell why the double-array
+    /// cannot grow. Allocation failures are returned instead of aborting,
+    /// and growing past the maximum index is a capacity overflow.
+    pub fn try_reserve(&mut self, additional_cells: usize) -> Result<(), TryReserveError> {
+        self.ro.da.try_reserve(additional_cells)?;
+        self.is_dirty = true;
+        Ok(())
+    }
+
+    /// Build a trie from keys sorted in the order of the alphabet, such as
+    /// from [iter](Self::iter) of another trie with the same alphabet. This
+    /// is the order of the character values, unless the alphabet has a
+    /// [collation](AlphaMap::with_collation), in which case the keys must be
+    /// sorted in the collation order. As all children of a node are known at
+    /// once, this is much faster than storing the keys one by one. Keys may
+    /// be given with or without the terminating 0. Keys with characters
+    /// outside of the alphabet are skipped, and a later duplicate of a key
+    /// overwrites its data.
+    ///
+    /// Fails with [StoreError::NotSorted] if the keys are not sorted, with
+    /// [StoreError::InteriorTerminator] if a key has a 0 before its end, as
+    /// [store](Self::store) does, or if the trie cannot grow for the keys.
+    pub fn from_sorted_iter<I: IntoIterator<Item = (Vec<AlphaChar>, TrieData)>>(
+        alpha_map: AlphaMap,
+        iter: I,
+    ) -> Result<Self, StoreError> {
+        let mut trie = Trie::new(alpha_map);
+        let mut entries: Vec<(Vec<TrieChar>, Option<TrieData>)> = Vec::new();
+        for (i, (mut key, data)) in iter.into_iter().enumerate() {
+            check_terminator(&key)?;
+            if key.last() != Some(&0) {
+                key.push(0);
+            }
+            let Some(key_str) = trie.ro.alpha_map.char_to_trie_str(&key) else {
+                continue;
+            };
+            // the keys must be sorted in trie character order, which is the
+            // collation order of the alphabet
+            match entries.last_mut() {
+                Some((last, last_data)) if *last == key_str => *last_data = Some(data),
+                Some((last, _)) if *last > key_str => return Err(StoreError::NotSorted(i)),
+                _ => entries.push((key_str, Some(data))),
+            }
+        }
+
+        debug_assert!(
+            entries.windows(2).all(|pair| pair[0].0 < pair[1].0),
+            "keys should be sorted and unique"
+        );
+        let root = trie.ro.da.get_root();
+        trie.build_sorted(root, &mut entries, 0)?;
+        Ok(trie)
+    }
+
+    fn build_sorted(
+        &mut self,
+        s: TrieIndex,
+        entries: &mut [(Vec<TrieChar>, Option<TrieData>)],
+        depth: usize,
+    ) -> Result<(), StoreError> {
+        let mut symbols = Symbols::default();
+        for (key, _) in entries.iter() {
+            if symbols.iter().last() != Some(&key[depth]) {
+                symbols.add_fast(key[depth]);
+            }
+        }
+        if symbols.num() == 0 {
+            return Ok(());
+        }
+        let base = self.ro.da.insert_branches(s, &symbols)?;
+
+        let mut rest = entries;
+        for c in symbols.iter().copied() {
+            let group_len = rest.iter().take_while(|(key, _)| key[depth] == c).count();
+            let (group, next) = rest.split_at_mut(group_len);
+            rest = next;
+
+            let child = base + c as TrieIndex;
+            if let [(key, data)] = group {
+                // a lone key moves to the tail right away, see branch_in_branch
+                let suffix = if c == TRIE_CHAR_TERM {
+                    &key[depth..]
+                } else {
+                    &key[(depth + 1)..]
+                };
+                let tail = self.ro.tail.add_suffix(Some(suffix.into()))?;
+                self.ro.tail.set_data(tail, data.take().unwrap());
+                self.ro.da.set_tail_index(child, tail);
+            } else {
+                self.build_sorted(child, group, depth + 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Rebuild the trie to drop the space left unused by deleted keys.
+    ///
+    /// This reinserts every key into a new trie, so it takes about as long as
+    /// building the trie from scratch. Any outstanding [TrieState] is
+    /// invalidated by the rebuild, and saved [TrieCursor]s no longer apply to
+    /// the rebuilt trie.
+    ///
+    /// The trie is left as it was if the new trie cannot store every key.
+    pub fn shrink_to_fit(&mut self) -> Result<(), StoreError> {
+        let mut rebuilt = Trie::new(self.ro.alpha_map.clone());
+        for (key, _) in self.iter() {
+            rebuilt.store_conditionally(&key, TrieData::default(), true)?;
+        }
+        debug_assert_eq!(rebuilt.len(), self.len());
+        rebuilt.ro.da.shrink_to_fit();
+        rebuilt.ro.tail.shrink_to_fit();
+
+        self.move_data_into(&mut rebuilt);
+        self.ro = rebuilt.ro;
+        self.is_dirty = true;
+        Ok(())
+    }
+
+    /// Make the keys whose suffixes in the tail are equal share a single copy
+    /// of the suffix in memory, such as for a dictionary with many words of
+    /// the same ending. Returns the number of copies dropped, see also
+    /// [TrieStats::stored_suffix_bytes].
+    ///
+    /// Each key keeps its own data, and a key whose suffix changes later gets
+    /// its own copy again. The file format has a suffix for each key, so the
+    /// saved trie is unchanged, and a loaded trie needs to be optimized again.
+    pub fn optimize_tail(&mut self) -> usize {
+        self.ro.tail.share_suffixes()
+    }
+
+    /// Rebuild the trie so that its layout only depends on its alphabet and
+    /// its entries, not on the order they were stored or deleted in. Tries
+    /// with the same entries then serialize to the same bytes.
+    ///
+    /// This takes O(n) in the number of keys, with the trie rebuilt with
+    /// [from_sorted_iter](Self::from_sorted_iter). Unused space is dropped as
+    /// with [shrink_to_fit](Self::shrink_to_fit), and saved [TrieCursor]s no
+    /// longer apply to the rebuilt trie.
+    ///
+    /// The trie is left as it was if it cannot be rebuilt, such as when the
+    /// new trie cannot grow, or when a key read from a corrupt file has
+    /// characters outside of the alphabet.
+    pub fn canonicalize(&mut self) -> Result<(), StoreError> {
+        let keys: Vec<Vec<AlphaChar>> = self.iter().map(|(key, _)| key).collect();
+        // from_sorted_iter would skip such keys, dropping their data
+        for key in keys.iter() {
+            self.check_key(key).map_err(StoreError::CharNotInAlphaMap)?;
+        }
+        let mut rebuilt = Trie::from_sorted_iter(
+            self.ro.alpha_map.clone(),
+            keys.into_iter().map(|key| (key, TrieData::default())),
+        )?;
+        debug_assert_eq!(rebuilt.len(), self.len());
+        rebuilt.ro.da.shrink_to_fit();
+        rebuilt.ro.tail.shrink_to_fit();
+
+        self.move_data_into(&mut rebuilt);
+        self.ro = rebuilt.ro;
+        self.is_dirty = true;
+        Ok(())
+    }
+
+    /// Move the data of each key into `other`, which has the same keys
+    fn move_data_into(&mut self, other: &mut Trie<TrieData>) {
+        for ((_, data), (_, other_data)) in self.iter_mut().zip(other.iter_mut()) {
+            mem::swap(data, other_data);
+        }
+    }
+
+    /// Store `data` under `key`, overwriting any previous data.
+    ///
+    /// The empty key `[0]` is a key like any other: it can be stored,
+    /// retrieved and deleted, and is the first entry when iterating.
+    ///
+    /// As 0 terminates the key, it cannot be a character inside the key and
+    /// such keys are rejected, see [StoreError::InteriorTerminator].
     pub fn store(&mut self, key: &[AlphaChar], data: TrieData) -> bool {
         self.store_conditionally(key, data, true)
+            .is_ok_and(|stored| stored)
     }
 
     pub fn store_if_absent(&mut self, key: &[AlphaChar], data: TrieData) -> bool {
         self.store_conditionally(key, data, false)
+            .is_ok_and(|stored| stored)
+    }
+
+    /// Same as [store](Self::store), but take the key as a string. The
+    /// terminator is appended, so the key must not contain a nul character.
+    /// The alphabet must cover every character of the key.
+    pub fn store_str(&mut self, key: &str, data: TrieData) -> bool {
+        self.store(&key.as_alphachar(), data)
+    }
+
+    /// Same as [store](Self::store), but tell why the key could not be
+    /// stored. Returns true if the key is new, or false if its data was
+    /// overwritten.
+    ///
+    /// Growing the double-array or the tail for the key fails with
+    /// [StoreError::OutOfMemory] instead of aborting, leaving the trie as it
+    /// was. Smaller allocations, such as for the suffix of the key, may still
+    /// abort.
+    pub fn try_store(&mut self, key: &[AlphaChar], data: TrieData) -> Result<bool, StoreError> {
+        match self.locate(key)? {
+            Slot::Occupied(t) => {
+                self.ro.tail.set_data(t, data);
+                self.is_dirty = true;
+                Ok(false)
+            }
+            Slot::Vacant(vacant) => {
+                self.insert_at(vacant, data)?;
+                Ok(true)
+            }
+        }
     }
 
+    /// Store `data` at `key`. Returns whether the data is stored, which is
+    /// false if the key exists and `is_overwrite` is not set.
     fn store_conditionally(
         &mut self,
         key: &[AlphaChar],
         data: TrieData,
         is_overwrite: bool,
-    ) -> bool {
+    ) -> Result<bool, StoreError> {
+        match self.locate(key)? {
+            Slot::Occupied(t) => {
+                // duplicated, overwrite val if flagged
+                if !is_overwrite {
+                    return Ok(false);
+                }
+                self.ro.tail.set_data(t, data);
+                self.is_dirty = true;
+                Ok(true)
+            }
+            Slot::Vacant(vacant) => {
+                self.insert_at(vacant, data)?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Find where `key` is or should be stored
+    fn locate(&self, key: &[AlphaChar]) -> Result<Slot, StoreError> {
+        check_terminator(key)?;
+
         // walk through branches
         let mut s = self.ro.da.get_root();
         let mut p = key;
         while !self.ro.da.is_separate(s) {
-            let Some(tc) = self.ro.alpha_map.char_to_trie(p[0]) else {
-                return false;
-            };
+            let tc = self.char_to_trie(p[0])?;
             if let Some(next_s) = self.ro.da.walk(s, tc as TrieChar) {
                 s = next_s;
             } else {
-                let Some(key_str) = self.ro.alpha_map.char_to_trie_str(p) else {
-                    return false;
-                };
-                return self.branch_in_branch(s, &key_str, data).into();
+                let key_str = self.char_to_trie_str(p)?;
+                return Ok(Slot::Vacant(VacantSlot::Branch(s, key_str)));
             }
             if p[0] == 0 {
                 break;
@@ -88,29 +365,56 @@ impl<TrieData: Default> Trie<TrieData> {
         let t = self.ro.da.get_tail_index(s);
         let mut suffix_idx = 0;
         for ch in p.iter().copied() {
-            let Some(tc) = self.ro.alpha_map.char_to_trie(ch) else {
-                return false;
-            };
+            let tc = self.char_to_trie(ch)?;
             if let Some(next_idx) = self.ro.tail.walk_char(t, suffix_idx, tc as TrieChar) {
                 suffix_idx = next_idx;
             } else {
-                let Some(tail_str) = self.ro.alpha_map.char_to_trie_str(sep) else {
-                    return false;
-                };
-                return self.branch_in_tail(s, &tail_str, data).into();
+                let tail_str = self.char_to_trie_str(sep)?;
+                return Ok(Slot::Vacant(VacantSlot::Tail(s, tail_str)));
             }
             if ch == 0 {
                 break;
             }
         }
 
-        // duplicated, overwrite val if flagged
-        if !is_overwrite {
-            return false;
+        Ok(Slot::Occupied(t))
+    }
+
+    fn char_to_trie(&self, ac: AlphaChar) -> Result<TrieIndex, StoreError> {
+        self.ro
+            .alpha_map
+            .char_to_trie(ac)
+            .ok_or(StoreError::CharNotInAlphaMap(ac))
+    }
+
+    fn char_to_trie_str(&self, str: &[AlphaChar]) -> Result<Vec<TrieChar>, StoreError> {
+        self.ro
+            .check_key(str)
+            .map_err(StoreError::CharNotInAlphaMap)?;
+        // unwrap as an assertion since every character is checked above
+        Ok(self.ro.alpha_map.char_to_trie_str(str).unwrap())
+    }
+
+    /// Store `data` at a slot found by [locate](Self::locate), returning the
+    /// tail index of the new key
+    fn insert_at(&mut self, slot: VacantSlot, data: TrieData) -> Result<TrieIndex, StoreError> {
+        match slot {
+            VacantSlot::Branch(s, key_str) => self.branch_in_branch(s, &key_str, data),
+            VacantSlot::Tail(s, tail_str) => self.branch_in_tail(s, &tail_str, data),
+        }
+    }
+
+    /// Get the entry of `key` for in-place insertion or update.
+    ///
+    /// # Panics
+    /// Panics if `key` has characters outside of the alphabet, which
+    /// [store](Self::store) would reject by returning false.
+    pub fn entry(&mut self, key: &[AlphaChar]) -> Entry<'_, TrieData> {
+        match self.locate(key) {
+            Ok(Slot::Occupied(tail)) => Entry::Occupied(OccupiedEntry { trie: self, tail }),
+            Ok(Slot::Vacant(slot)) => Entry::Vacant(VacantEntry { trie: self, slot }),
+            Err(err) => panic!("{}", err),
         }
-        self.ro.tail.set_data(t, data);
-        self.is_dirty = true;
-        true
     }
 
     pub fn root(&self) -> TrieState<TrieData> {
@@ -121,44 +425,150 @@ impl<TrieData: Default> Trie<TrieData> {
         self.ro.retrieve(key)
     }
 
+    pub fn get(&self, key: &[AlphaChar]) -> Option<TrieData>
+    where
+        TrieData: Copy,
+    {
+        self.ro.get(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ro.len()
+    }
+
+    pub fn stats(&self) -> TrieStats {
+        self.ro.stats()
+    }
+
+    pub fn debug_tail_entries(&self) -> Vec<(TrieIndex, Vec<AlphaChar>)> {
+        self.ro.debug_tail_entries()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ro.is_empty()
+    }
+
+    pub fn retrieve_iter<I: IntoIterator<Item = AlphaChar>>(&self, chars: I) -> Option<&TrieData> {
+        self.ro.retrieve_iter(chars)
+    }
+
+    pub fn retrieve_ci(&self, key: &[AlphaChar]) -> Result<Option<&TrieData>, AlphaChar> {
+        self.ro.retrieve_ci(key)
+    }
+
+    pub fn retrieve_many<'a>(&'a self, keys: &[&[AlphaChar]]) -> Vec<Option<&'a TrieData>> {
+        self.ro.retrieve_many(keys)
+    }
+
+    /// Get a mutable reference to the data of `key`, to update it in place.
+    /// The trie is marked as dirty if the key is found, as the data may be
+    /// changed through the reference.
+    pub fn retrieve_mut(&mut self, key: &[AlphaChar]) -> Option<&mut TrieData> {
+        let (t, _) = self.ro.walk_key(key.iter().copied())?;
+        self.is_dirty = true;
+        self.ro.tail.get_data_mut(t)
+    }
+
+    pub fn contains_key(&self, key: &[AlphaChar]) -> bool {
+        self.ro.contains_key(key)
+    }
+
+    pub fn retrieve_str(&self, key: &str) -> Option<&TrieData> {
+        self.ro.retrieve_str(key)
+    }
+
+    pub fn contains_str(&self, key: &str) -> bool {
+        self.ro.contains_str(key)
+    }
+
+    pub fn check_key(&self, key: &[AlphaChar]) -> Result<(), AlphaChar> {
+        self.ro.check_key(key)
+    }
+
+    pub fn validate(&self) -> Result<(), CorruptError> {
+        self.ro.validate()
+    }
+
+    pub fn key_depth(&self, key: &[AlphaChar]) -> Option<usize> {
+        self.ro.key_depth(key)
+    }
+
+    pub fn walk_prefix(&self, key: &[AlphaChar]) -> (usize, bool) {
+        self.ro.walk_prefix(key)
+    }
+
+    pub fn common_prefix_search(&self, text: &[AlphaChar]) -> Vec<(Vec<AlphaChar>, &TrieData)> {
+        self.ro.common_prefix_search(text)
+    }
+
+    pub fn retrieve_longest_prefix(&self, text: &[AlphaChar]) -> Option<(usize, &TrieData)> {
+        self.ro.retrieve_longest_prefix(text)
+    }
+
+    pub fn search_fuzzy(
+        &self,
+        query: &[AlphaChar],
+        max_dist: usize,
+    ) -> Vec<(Vec<AlphaChar>, usize, &TrieData)> {
+        self.ro.search_fuzzy(query, max_dist)
+    }
+
+    pub fn search_pattern(
+        &self,
+        pattern: &[Option<AlphaChar>],
+    ) -> Vec<(Vec<AlphaChar>, &TrieData)> {
+        self.ro.search_pattern(pattern)
+    }
+
     fn branch_in_branch(
         &mut self,
         sep_node: TrieIndex,
         suffix: &[TrieChar],
         data: TrieData,
-    ) -> bool {
+    ) -> Result<TrieIndex, StoreError> {
         let mut suffix = suffix;
-        let Some(new_da) = self.ro.da.insert_branch(sep_node, suffix[0]) else {
-            return false;
-        };
+        let new_da = self.ro.da.insert_branch(sep_node, suffix[0])?;
         if suffix[0] != TRIE_CHAR_TERM {
             suffix = &suffix[1..];
         }
 
-        let new_tail = self.ro.tail.add_suffix(Some(suffix.into()));
+        let new_tail = match self.ro.tail.add_suffix(Some(suffix.into())) {
+            Ok(new_tail) => new_tail,
+            Err(err) => {
+                self.ro.da.prune(new_da);
+                return Err(err);
+            }
+        };
         self.ro.tail.set_data(new_tail, data);
         self.ro.da.set_tail_index(new_da, new_tail);
 
         self.is_dirty = true;
-        true
+        Ok(new_tail)
     }
 
-    fn branch_in_tail(&mut self, sep_node: TrieIndex, suffix: &[TrieChar], data: TrieData) -> bool {
+    fn branch_in_tail(
+        &mut self,
+        sep_node: TrieIndex,
+        suffix: &[TrieChar],
+        data: TrieData,
+    ) -> Result<TrieIndex, StoreError> {
         // adjust separate point in old path
         let old_tail = self.ro.da.get_tail_index(sep_node);
-        let Some(old_suffix) = self.ro.tail.get_suffix(old_tail) else {
-            return false;
-        };
+        // unwrap as an assertion since a separate node always has a suffix
+        let old_suffix = self.ro.tail.get_suffix(old_tail).unwrap();
 
         let mut p = old_suffix;
         let mut s = sep_node;
         let mut suffix = suffix;
         while p[0] == suffix[0] {
-            let Some(t) = self.ro.da.insert_branch(s, p[0]) else {
-                // TODO: Move to fail() code
-                self.ro.da.prune_upto(sep_node, s);
-                self.ro.da.set_tail_index(sep_node, old_tail);
-                return false;
+            let t = match self.ro.da.insert_branch(s, p[0]) {
+                Ok(t) => t,
+                Err(err) => {
+                    // TODO: Move to fail() code
+                    self.ro.da.prune_upto(sep_node, s);
+                    self.ro.da.set_tail_index(sep_node, old_tail);
+                    return Err(err);
+                }
             };
             s = t;
 
@@ -166,11 +576,14 @@ impl<TrieData: Default> Trie<TrieData> {
             suffix = &suffix[1..];
         }
 
-        let Some(old_da) = self.ro.da.insert_branch(s, p[0]) else {
-            // TODO: Move to fail() code
-            self.ro.da.prune_upto(sep_node, s);
-            self.ro.da.set_tail_index(sep_node, old_tail);
-            return false;
+        let old_da = match self.ro.da.insert_branch(s, p[0]) {
+            Ok(old_da) => old_da,
+            Err(err) => {
+                // TODO: Move to fail() code
+                self.ro.da.prune_upto(sep_node, s);
+                self.ro.da.set_tail_index(sep_node, old_tail);
+                return Err(err);
+            }
         };
 
         if p[0] != TRIE_CHAR_TERM {
@@ -184,17 +597,22 @@ impl<TrieData: Default> Trie<TrieData> {
     }
 
     pub fn delete(&mut self, key: &[AlphaChar]) -> bool {
+        self.remove(key).is_some()
+    }
+
+    /// Same as [delete](Self::delete), but take the key as a string, see
+    /// [store_str](Self::store_str).
+    pub fn delete_str(&mut self, key: &str) -> bool {
+        self.delete(&key.as_alphachar())
+    }
+
+    /// Delete `key` from the trie, returning its data
+    fn remove(&mut self, key: &[AlphaChar]) -> Option<TrieData> {
         let mut s = self.ro.da.get_root();
         let mut p = key;
         while !self.ro.da.is_separate(s) {
-            let Some(tc) = self.ro.alpha_map.char_to_trie(p[0]) else {
-                return false;
-            };
-            if let Some(new_s) = self.ro.da.walk(s, tc as TrieChar) {
-                s = new_s;
-            } else {
-                return false;
-            }
+            let tc = self.ro.alpha_map.char_to_trie(p[0])?;
+            s = self.ro.da.walk(s, tc as TrieChar)?;
             if p[0] == 0 {
                 break;
             }
@@ -205,30 +623,281 @@ impl<TrieData: Default> Trie<TrieData> {
         let mut suffix_idx = 0;
 
         for ch in p.iter().copied() {
-            let Some(tc) = self.ro.alpha_map.char_to_trie(ch) else {
-                return false;
-            };
-            if let Some(new_idx) = self.ro.tail.walk_char(t, suffix_idx, tc as TrieChar) {
-                suffix_idx = new_idx;
-            } else {
-                return false;
-            }
+            let tc = self.ro.alpha_map.char_to_trie(ch)?;
+            suffix_idx = self.ro.tail.walk_char(t, suffix_idx, tc as TrieChar)?;
             if ch == 0 {
                 break;
             }
         }
 
+        let data = mem::take(self.ro.tail.get_data_mut(t)?);
         self.ro.tail.delete(t);
         self.ro.da.set_base(s, TRIE_INDEX_ERROR);
         self.ro.da.prune(s);
 
         self.is_dirty = true;
-        true
+        Some(data)
+    }
+
+    /// Delete all keys starting with `prefix`, read up to its terminator, if
+    /// any. The sub-trie below the prefix is freed at once, instead of
+    /// deleting its keys one by one.
+    ///
+    /// Returns the number of keys deleted.
+    pub fn delete_prefix(&mut self, prefix: &[AlphaChar]) -> usize {
+        let mut s = self.ro.da.get_root();
+        let mut p = strip_terminator(prefix);
+        while !self.ro.da.is_separate(s) {
+            let Some((ch, rest)) = p.split_first() else {
+                break;
+            };
+            let Some(tc) = self.ro.alpha_map.char_to_trie(*ch) else {
+                return 0;
+            };
+            let Some(next) = self.ro.da.walk(s, tc as TrieChar) else {
+                return 0;
+            };
+            s = next;
+            p = rest;
+        }
+
+        if self.ro.da.is_separate(s) {
+            // a single key is left, which must have the rest of the prefix
+            let t = self.ro.da.get_tail_index(s);
+            let mut suffix_idx = 0;
+            for ch in p.iter().copied() {
+                let Some(tc) = self.ro.alpha_map.char_to_trie(ch) else {
+                    return 0;
+                };
+                let Some(next) = self.ro.tail.walk_char(t, suffix_idx, tc as TrieChar) else {
+                    return 0;
+                };
+                suffix_idx = next;
+            }
+            self.ro.tail.delete(t);
+            self.ro.da.set_base(s, TRIE_INDEX_ERROR);
+            self.ro.da.prune(s);
+            self.is_dirty = true;
+            return 1;
+        }
+
+        let tails = self.ro.da.free_subtree(s);
+        let count = tails.len();
+        if count == 0 {
+            return 0;
+        }
+        self.ro.tail.delete_many(tails);
+        self.ro.da.prune(s);
+        self.is_dirty = true;
+        count
+    }
+
+    /// Move all keys under the prefix `from` to be under the prefix `to`,
+    /// keeping their suffixes and data. Prefixes may be given with or without
+    /// the terminating 0. Existing keys under `to` are overwritten by the moved
+    /// keys of the same name.
+    ///
+    /// Returns the number of keys moved. The trie is left as it was if any
+    /// of the keys cannot be stored under `to`.
+    pub fn rename_prefix(
+        &mut self,
+        from: &[AlphaChar],
+        to: &[AlphaChar],
+    ) -> Result<usize, StoreError> {
+        let from = strip_terminator(from);
+        let to = strip_terminator(to);
+        self.check_key(to).map_err(StoreError::CharNotInAlphaMap)?;
+
+        let mut state = self.ro.root();
+        if state.walk_str(from) != from.len() {
+            return Ok(0);
+        }
+        let suffixes: Vec<Vec<AlphaChar>> = TrieIterator::new(&state).map(|(key, _)| key).collect();
+        let with_prefix = |prefix: &[AlphaChar]| -> Vec<Vec<AlphaChar>> {
+            suffixes
+                .iter()
+                .map(|suffix| prefix.iter().chain(suffix.iter()).copied().collect())
+                .collect()
+        };
+        let from_keys = with_prefix(from);
+        let to_keys = with_prefix(to);
+
+        // add the missing keys under `to` first, as this is the only step
+        // that can fail, and is undone by deleting them again
+        let mut added = Vec::new();
+        for to_key in to_keys.iter() {
+            match self.store_conditionally(to_key, TrieData::default(), false) {
+                Ok(true) => added.push(to_key),
+                Ok(false) => {}
+                Err(err) => {
+                    for key in added {
+                        self.remove(key);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+
+        // take out all data first, so that `to` being nested inside `from`
+        // does not move a key twice. unwrap as an assertion since the keys
+        // were just listed or added.
+        let moved: Vec<TrieData> = from_keys
+            .iter()
+            .map(|key| mem::take(self.retrieve_mut(key).unwrap()))
+            .collect();
+        for (to_key, data) in to_keys.iter().zip(moved) {
+            *self.retrieve_mut(to_key).unwrap() = data;
+        }
+        // keys moved onto by other keys are kept
+        let to_keys: BTreeSet<Vec<AlphaChar>> = to_keys.into_iter().collect();
+        for from_key in from_keys.iter() {
+            if !to_keys.contains(from_key) {
+                self.remove(from_key);
+            }
+        }
+        Ok(suffixes.len())
     }
 
     pub fn iter(&self) -> TrieIterator<TrieData> {
         self.ro.iter()
     }
+
+    /// Same as [iter](Self::iter), but with mutable references to the data,
+    /// such as to update every value in place. The trie is marked as dirty,
+    /// as the data may be changed through the references.
+    ///
+    /// The keys are collected up front, since the data cannot be borrowed
+    /// mutably while walking the trie.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Vec<AlphaChar>, &mut TrieData)> {
+        let mut entries = Vec::with_capacity(self.len());
+        let mut iter = self.ro.iter();
+        while iter.iter_next() {
+            // unwrap as an assertion since iter_next has set the state
+            let mut key = Vec::new();
+            iter.append_key(&mut key).unwrap();
+            if let Some(t) = iter.tail_index() {
+                entries.push((t, key));
+            }
+        }
+        self.is_dirty = true;
+        self.ro.tail.get_data_mut_many(entries).into_iter()
+    }
+
+    pub fn iter_prefix(&self, prefix: &[AlphaChar]) -> TrieIterator<'_, '_, TrieData> {
+        self.ro.iter_prefix(prefix)
+    }
+
+    pub fn count_prefix(&self, prefix: &[AlphaChar]) -> usize {
+        self.ro.count_prefix(prefix)
+    }
+
+    pub fn range<'a>(
+        &'a self,
+        lo: &[AlphaChar],
+        hi: &[AlphaChar],
+    ) -> impl Iterator<Item = (Vec<AlphaChar>, Option<&'a TrieData>)> + 'a {
+        self.ro.range(lo, hi)
+    }
+
+    pub fn iter_from_cursor(&self, cursor: &TrieCursor) -> Option<TrieIterator<'_, '_, TrieData>> {
+        self.ro.iter_from_cursor(cursor)
+    }
+
+    pub fn map_data<'a, U, F>(&'a self, f: F) -> impl Iterator<Item = (Vec<AlphaChar>, U)> + 'a
+    where
+        F: FnMut(&TrieData) -> U + 'a,
+    {
+        self.ro.map_data(f)
+    }
+
+    pub fn keys_with_data<'a, F>(&'a self, pred: F) -> impl Iterator<Item = Vec<AlphaChar>> + 'a
+    where
+        F: Fn(&TrieData) -> bool + 'a,
+    {
+        self.ro.keys_with_data(pred)
+    }
+
+    pub fn collect_keys(&self) -> (Vec<AlphaChar>, Vec<usize>) {
+        self.ro.collect_keys()
+    }
+
+    pub fn for_each<F: FnMut(&[AlphaChar], &TrieData) -> bool>(&self, f: F) -> bool {
+        self.ro.for_each(f)
+    }
+}
+
+impl<TrieData: Default> FromIterator<(Vec<AlphaChar>, TrieData)> for Trie<TrieData> {
+    /// Build a trie from keys and their data. The alphabet is made of exactly
+    /// the characters used in the keys, so adding keys with other characters
+    /// later fails. Keys may be given with or without the terminating 0, and
+    /// later duplicates overwrite earlier ones.
+    ///
+    /// # Panics
+    /// Panics if the keys use more than [TRIE_CHAR_MAX] distinct characters,
+    /// which an alphabet cannot hold, or if the trie cannot grow for them.
+    /// Use [TrieBuilder] to handle these errors.
+    fn from_iter<I: IntoIterator<Item = (Vec<AlphaChar>, TrieData)>>(iter: I) -> Self {
+        let mut builder = TrieBuilder::new();
+        builder.extend(iter);
+        builder.build().unwrap_or_else(|err| panic!("{}", err))
+    }
+}
+
+impl<TrieData: Default> Extend<(Vec<AlphaChar>, TrieData)> for Trie<TrieData> {
+    /// Store all keys and their data, overwriting existing keys. Keys may be
+    /// given with or without the terminating 0. Keys with characters outside
+    /// of the alphabet are skipped, as [Trie::store] would reject them.
+    fn extend<I: IntoIterator<Item = (Vec<AlphaChar>, TrieData)>>(&mut self, iter: I) {
+        for (mut key, data) in iter {
+            if !key.contains(&0) {
+                key.push(0);
+            }
+            self.store(&key, data);
+        }
+    }
+}
+
+impl<'a, TrieData: Default> Extend<(&'a [AlphaChar], TrieData)> for Trie<TrieData> {
+    /// Same as extending with owned keys, but keys with their terminating 0
+    /// are stored without being copied.
+    fn extend<I: IntoIterator<Item = (&'a [AlphaChar], TrieData)>>(&mut self, iter: I) {
+        for (key, data) in iter {
+            if key.contains(&0) {
+                self.store(key, data);
+            } else {
+                self.store(&[key, &[0]].concat(), data);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<TrieData: Default + Send + Sync> Trie<TrieData> {
+    pub fn par_iter(&self) -> impl ParallelIterator<Item = (Vec<AlphaChar>, &TrieData)> {
+        self.ro.par_iter()
+    }
+}
+
+impl<TrieData: Default + Clone> Trie<TrieData> {
+    /// Save the contents of the trie, to be put back later with
+    /// [restore](Self::restore). The snapshot shares the memory of the trie,
+    /// which is only copied when either of them is first modified, so taking
+    /// a snapshot is cheap compared to serializing the trie.
+    pub fn snapshot(&mut self) -> TrieSnapshot<TrieData> {
+        TrieSnapshot {
+            ro: ROTrie {
+                alpha_map: self.ro.alpha_map.clone(),
+                da: self.ro.da.share(),
+                tail: self.ro.tail.share(),
+            },
+        }
+    }
+
+    /// Replace the contents of the trie with the saved `snapshot`
+    pub fn restore(&mut self, snapshot: TrieSnapshot<TrieData>) {
+        self.ro = snapshot.ro;
+        self.is_dirty = true;
+    }
 }
 
 #[cfg(feature = "std")]
@@ -240,6 +909,17 @@ impl<TrieData: TrieSerializable + Default> Trie<TrieData> {
         Ok(out)
     }
 
+    /// Same as [save](Self::save), but only if the trie is
+    /// [dirty](Self::is_dirty). Returns whether the file was written, so
+    /// periodic flushes don't rewrite an unchanged file.
+    pub fn save_if_dirty<P: AsRef<Path>>(&mut self, path: P) -> io::Result<bool> {
+        if !self.is_dirty {
+            return Ok(false);
+        }
+        self.save(path)?;
+        Ok(true)
+    }
+
     pub fn serialize<T: Write>(&mut self, writer: &mut T) -> io::Result<()> {
         let out = self.ro.serialize(writer)?;
         self.is_dirty = false;
@@ -251,6 +931,19 @@ impl<TrieData: TrieSerializable + Default> Trie<TrieData> {
     pub fn serialized_size(&self) -> usize {
         self.ro.serialized_size()
     }
+
+    pub fn serialize_with_length<T: Write>(&mut self, writer: &mut T) -> io::Result<()> {
+        self.ro.serialize_with_length(writer)?;
+        self.is_dirty = false;
+        Ok(())
+    }
+
+    #[cfg(feature = "flate2")]
+    pub fn serialize_compressed<T: Write>(&mut self, writer: &mut T) -> io::Result<()> {
+        self.ro.serialize_compressed(writer)?;
+        self.is_dirty = false;
+        Ok(())
+    }
 }
 
 #[cfg(feature = "std")]
@@ -272,8 +965,83 @@ impl<TrieData: TrieDeserializable + Default> Trie<TrieData> {
             is_dirty: false,
         })
     }
+
+    /// See [ROTrie::from_bytes]
+    pub fn from_bytes(data: &[u8]) -> io::Result<Self> {
+        let ro = ROTrie::from_bytes(data)?;
+
+        Ok(Self {
+            ro,
+            is_dirty: false,
+        })
+    }
+
+    /// See [ROTrie::format_version]
+    pub fn format_version<T: Read + Seek>(reader: &mut T) -> io::Result<u32> {
+        ROTrie::<TrieData>::format_version(reader)
+    }
+
+    /// Same as [from_reader](Self::from_reader), but [validate](Self::validate)
+    /// the trie before returning it.
+    pub fn from_reader_validated<T: Read>(reader: &mut T) -> io::Result<Self> {
+        let ro = ROTrie::from_reader_validated(reader)?;
+
+        Ok(Self {
+            ro,
+            is_dirty: false,
+        })
+    }
+
+    pub fn from_reader_at<T: Read>(reader: &mut T) -> io::Result<Self> {
+        let ro = ROTrie::from_reader_at(reader)?;
+
+        Ok(Self {
+            ro,
+            is_dirty: false,
+        })
+    }
+
+    #[cfg(feature = "flate2")]
+    pub fn from_reader_compressed<T: BufRead>(reader: &mut T) -> io::Result<Self> {
+        let ro = ROTrie::from_reader_compressed(reader)?;
+
+        Ok(Self {
+            ro,
+            is_dirty: false,
+        })
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl<TrieData: TrieDeserializable + Default> ROTrie<TrieData> {
+    /// Load a trie file by memory-mapping it. The double-array, which makes
+    /// up most of the file, is used from the mapping without being copied.
+    /// The alphabet and the tail are still read into memory.
+    ///
+    /// Modifying the trie, such as through [Trie::from_ro], copies the
+    /// double-array into memory first.
+    ///
+    /// # Safety
+    /// The file must not be modified while the trie is in use, see [Mmap::map].
+    pub unsafe fn from_mmap<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let map = Arc::new(unsafe { Mmap::map(&file)? });
+
+        let mut reader = &map[..];
+        let alpha_map = AlphaMap::read(&mut reader)?;
+        let da_offset = map.len() - reader.len();
+        let (da, tail_offset) = DArray::from_mmap(map.clone(), da_offset)?;
+        let tail = Tail::read(&mut &map[tail_offset..])?;
+
+        Ok(Self {
+            alpha_map,
+            da,
+            tail,
+        })
+    }
 }
 
+#[derive(Clone)]
 pub struct ROTrie<TrieData: Default> {
     alpha_map: AlphaMap,
     da: DArray,
@@ -289,14 +1057,406 @@ impl<TrieData: Default> ROTrie<TrieData> {
         }
     }
 
+    /// Assemble a trie from parts built by other means, such as another
+    /// construction algorithm. The parts are [validated](Self::validate), so
+    /// walking the resulting trie cannot go out of bounds.
+    #[cfg(feature = "low-level")]
+    pub fn from_parts(
+        alpha_map: AlphaMap,
+        da: DArray,
+        tail: Tail<TrieData>,
+    ) -> Result<Self, CorruptError> {
+        let trie = Self {
+            alpha_map,
+            da,
+            tail,
+        };
+        trie.validate()?;
+        Ok(trie)
+    }
+
     pub fn root(&self) -> TrieState<TrieData> {
         TrieState::new(self, self.da.get_root(), 0, false)
     }
 
+    /// Get the memory breakdown of the trie, such as to tell whether
+    /// [Trie::shrink_to_fit] is worth running.
+    pub fn stats(&self) -> TrieStats {
+        TrieStats {
+            num_keys: self.len(),
+            num_cells: self.da.num_cells(),
+            num_free_cells: self.da.num_free_cells(),
+            num_tail_blocks: self.tail.num_blocks(),
+            suffix_bytes: self.tail.suffix_bytes(),
+            stored_suffix_bytes: self.tail.stored_suffix_bytes(),
+        }
+    }
+
+    /// List the tail blocks in use with their index and suffix, decoded
+    /// without the terminator. This is meant for inspecting a trie, such as
+    /// one that fails [validate](Self::validate).
+    pub fn debug_tail_entries(&self) -> Vec<(TrieIndex, Vec<AlphaChar>)> {
+        self.tail
+            .used_blocks()
+            .map(|(t, suffix)| {
+                let suffix = suffix.iter().copied().map_to_alpha_char(&self.alpha_map);
+                (t, suffix.collect())
+            })
+            .collect()
+    }
+
+    /// Get the number of keys in the trie. The count is kept up to date as
+    /// keys are added and removed, so this does not iterate the trie.
+    pub fn len(&self) -> usize {
+        self.tail.num_used()
+    }
+
+    /// Check whether the trie has no keys. This only looks at the children
+    /// of the root node, so it does not need to iterate the trie.
+    pub fn is_empty(&self) -> bool {
+        !self.da.has_children(self.da.get_root())
+    }
+
     pub fn retrieve(&self, key: &[AlphaChar]) -> Option<&TrieData> {
+        self.retrieve_iter(key.iter().copied())
+    }
+
+    /// Same as [retrieve](Self::retrieve), but return a copy of the data so
+    /// that the trie is not borrowed afterward.
+    pub fn get(&self, key: &[AlphaChar]) -> Option<TrieData>
+    where
+        TrieData: Copy,
+    {
+        self.retrieve(key).copied()
+    }
+
+    /// Check the internal structure of the trie, such as one read from an
+    /// untrusted file. A trie that passes can be walked without going out of
+    /// bounds, though its keys may still be garbage.
+    pub fn validate(&self) -> Result<(), CorruptError> {
+        self.tail.validate()?;
+        self.da.validate(|t| self.tail.is_used_block(t))
+    }
+
+    /// Check that `key` only has characters in the alphabet, returning the
+    /// first one that is not. Keys that fail this are never stored, so this
+    /// tells them apart from keys that are simply missing.
+    pub fn check_key(&self, key: &[AlphaChar]) -> Result<(), AlphaChar> {
+        self.alpha_map.validate_key(key)
+    }
+
+    /// Check whether `key` is stored in the trie, without fetching its data.
+    pub fn contains_key(&self, key: &[AlphaChar]) -> bool {
+        self.walk_key(key.iter().copied()).is_some()
+    }
+
+    /// Same as [retrieve](Self::retrieve), but take the key as a string. The
+    /// terminator is appended, so the key must not contain a nul character.
+    /// Characters outside of the alphabet are never found.
+    pub fn retrieve_str(&self, key: &str) -> Option<&TrieData> {
+        self.retrieve_iter(str_to_alpha_chars(key))
+    }
+
+    /// Same as [contains_key](Self::contains_key), but take the key as a
+    /// string, see [retrieve_str](Self::retrieve_str).
+    pub fn contains_str(&self, key: &str) -> bool {
+        self.walk_key(str_to_alpha_chars(key)).is_some()
+    }
+
+    /// Retrieve the data of a key given as an iterator of characters, so that
+    /// keys arriving in chunks need not be collected first. As with
+    /// [retrieve](Self::retrieve), the key must end with the terminating 0.
+    pub fn retrieve_iter<I: IntoIterator<Item = AlphaChar>>(&self, chars: I) -> Option<&TrieData> {
+        let (t, _) = self.walk_key(chars)?;
+
+        // found, though a corrupt trie may point to a missing tail block
+        self.tail.get_data(t)
+    }
+
+    /// Retrieve the data of `key` with its letters lowercased, for a trie
+    /// whose alphabet only has lowercase letters.
+    ///
+    /// Only simple 1:1 case mapping is supported. Returns the first character
+    /// of `key` that lowercases to more than one character, such as 'İ'.
+    /// Characters without case, including ones that are not valid Unicode,
+    /// are looked up as is.
+    pub fn retrieve_ci(&self, key: &[AlphaChar]) -> Result<Option<&TrieData>, AlphaChar> {
+        let key = &key[..key
+            .iter()
+            .position(|ac| *ac == 0)
+            .map_or(key.len(), |end| end + 1)];
+        if let Some(ac) = key.iter().copied().find(|ac| to_lowercase(*ac).is_none()) {
+            return Err(ac);
+        }
+        // unwrap as an assertion since every character is checked above
+        Ok(self.retrieve_iter(key.iter().map(|ac| to_lowercase(*ac).unwrap())))
+    }
+
+    /// Get the number of double-array cells walked through before entering
+    /// the tail when looking up `key`, or None if the key is not in the trie.
+    /// The rest of the key is stored in the tail.
+    pub fn key_depth(&self, key: &[AlphaChar]) -> Option<usize> {
+        let (_, depth) = self.walk_key(key.iter().copied())?;
+        Some(depth)
+    }
+
+    /// Walk `key` from the root as far as the trie allows. Returns the number
+    /// of characters matched, excluding the terminator, and whether the
+    /// matched prefix is itself a key. The whole key is stored if the count
+    /// is the length of `key` without the terminator and the flag is set.
+    ///
+    /// The walk can be resumed from the divergence point with
+    /// [TrieState::walk_str] and [TrieState::walkable_chars], such as to
+    /// suggest corrections.
+    pub fn walk_prefix(&self, key: &[AlphaChar]) -> (usize, bool) {
+        let mut state = self.root();
+        let matched = state.walk_str(strip_terminator(key));
+        (matched, state.is_terminal())
+    }
+
+    /// Find all keys in the trie that are prefixes of `text`, shortest first.
+    /// `text` is read up to its terminator, if any. The returned keys
+    /// include the terminator.
+    ///
+    /// Each key comes with its own data, including keys that are prefixes of
+    /// longer keys, such as to score the candidate words when segmenting text:
+    ///
+    /// ```
+    /// use datrie::{AlphaMap, AsAlphaChar, Trie};
+    ///
+    /// let mut alpha_map = AlphaMap::default();
+    /// alpha_map.add_range('a' as u32..='z' as u32).unwrap();
+    /// let mut frequencies = Trie::new(alpha_map);
+    /// frequencies.store(&"in".as_alphachar(), 50);
+    /// frequencies.store(&"inform".as_alphachar(), 5);
+    /// frequencies.store(&"information".as_alphachar(), 20);
+    ///
+    /// let found = frequencies.common_prefix_search(&"informational".as_alphachar());
+    /// let scores: Vec<(usize, i32)> = found
+    ///     .iter()
+    ///     .map(|(key, frequency)| (key.len() - 1, **frequency))
+    ///     .collect();
+    /// assert_eq!(scores, vec![(2, 50), (6, 5), (11, 20)]);
+    /// ```
+    pub fn common_prefix_search(&self, text: &[AlphaChar]) -> Vec<(Vec<AlphaChar>, &TrieData)> {
+        let text = strip_terminator(text);
+        let mut out = Vec::new();
+        self.walk_prefixes(text, |len, data| {
+            let mut key = text[..len].to_vec();
+            key.push(0);
+            out.push((key, data));
+        });
+        out
+    }
+
+    /// Find the longest key in the trie that is a prefix of `text`. Returns
+    /// the number of characters of `text` it covers, excluding the
+    /// terminator, and its data.
+    pub fn retrieve_longest_prefix(&self, text: &[AlphaChar]) -> Option<(usize, &TrieData)> {
+        let mut longest = None;
+        self.walk_prefixes(strip_terminator(text), |len, data| {
+            longest = Some((len, data));
+        });
+        longest
+    }
+
+    /// Find all keys within Levenshtein distance `max_dist` of `query`, which
+    /// is read up to its terminator, if any. Returns the keys, including the
+    /// terminator, in trie character (collation) order with their distance
+    /// and data.
+    ///
+    /// A row of the edit distance matrix is carried down each branch, and a
+    /// branch is given up as soon as every value in its row is over
+    /// `max_dist`, so only the part of the trie near `query` is walked.
+    pub fn search_fuzzy(
+        &self,
+        query: &[AlphaChar],
+        max_dist: usize,
+    ) -> Vec<(Vec<AlphaChar>, usize, &TrieData)> {
+        let query = strip_terminator(query);
+        let row: Vec<usize> = (0..=query.len()).collect();
+        let mut key = Vec::new();
+        let mut out = Vec::new();
+        self.search_fuzzy_from(&self.root(), query, max_dist, &row, &mut key, &mut out);
+        out
+    }
+
+    /// Add the keys under `state`, reached with `key` whose row of distances
+    /// to `query` is `row`, to `out`
+    fn search_fuzzy_from<'a>(
+        &'a self,
+        state: &TrieState<'a, TrieData>,
+        query: &[AlphaChar],
+        max_dist: usize,
+        row: &[usize],
+        key: &mut Vec<AlphaChar>,
+        out: &mut Vec<(Vec<AlphaChar>, usize, &'a TrieData)>,
+    ) {
+        if let Some(data) = state.get_data() {
+            let dist = row[query.len()];
+            if dist <= max_dist {
+                out.push((
+                    key.iter().copied().chain(iter::once(0)).collect(),
+                    dist,
+                    data,
+                ));
+            }
+        }
+
+        for ac in state.walkable_chars_iter().filter(|ac| *ac != 0) {
+            let mut next = state.clone();
+            next.walk(ac);
+
+            let mut next_row = Vec::with_capacity(row.len());
+            next_row.push(row[0] + 1);
+            for (i, qc) in query.iter().copied().enumerate() {
+                let replace = row[i] + usize::from(qc != ac);
+                let dist = replace.min(row[i + 1] + 1).min(next_row[i] + 1);
+                next_row.push(dist);
+            }
+            if next_row.iter().all(|dist| *dist > max_dist) {
+                continue;
+            }
+
+            key.push(ac);
+            self.search_fuzzy_from(&next, query, max_dist, &next_row, key, out);
+            key.pop();
+        }
+    }
+
+    /// Find all keys matching `pattern`, where `None` matches any one
+    /// character and `Some(c)` matches `c` only. The pattern is read up to
+    /// `Some(0)`, if any, and only keys of the same length match. Returns the
+    /// keys, including the terminator, in trie character (collation) order
+    /// with their data.
+    pub fn search_pattern(
+        &self,
+        pattern: &[Option<AlphaChar>],
+    ) -> Vec<(Vec<AlphaChar>, &TrieData)> {
+        let pattern = match pattern.iter().position(|pc| *pc == Some(0)) {
+            Some(end) => &pattern[..end],
+            None => pattern,
+        };
+        let mut key = Vec::new();
+        let mut out = Vec::new();
+        self.search_pattern_from(&self.root(), pattern, &mut key, &mut out);
+        out
+    }
+
+    /// Add the keys under `state`, reached with `key`, whose rest matches
+    /// `pattern` to `out`
+    fn search_pattern_from<'a>(
+        &'a self,
+        state: &TrieState<'a, TrieData>,
+        pattern: &[Option<AlphaChar>],
+        key: &mut Vec<AlphaChar>,
+        out: &mut Vec<(Vec<AlphaChar>, &'a TrieData)>,
+    ) {
+        let Some((pc, rest)) = pattern.split_first() else {
+            if let Some(data) = state.get_data() {
+                out.push((key.iter().copied().chain(iter::once(0)).collect(), data));
+            }
+            return;
+        };
+
+        let mut walk = |ac: AlphaChar| {
+            let mut next = state.clone();
+            if next.walk(ac) {
+                key.push(ac);
+                self.search_pattern_from(&next, rest, key, out);
+                key.pop();
+            }
+        };
+        match pc {
+            Some(ac) => walk(*ac),
+            None => state
+                .walkable_chars_iter()
+                .filter(|ac| *ac != 0)
+                .for_each(walk),
+        }
+    }
+
+    /// Walk `text` from the root in a single pass, calling `f` with the
+    /// length of each prefix of `text` that is a key in the trie.
+    fn walk_prefixes<'a, F: FnMut(usize, &'a TrieData)>(&'a self, text: &[AlphaChar], mut f: F) {
+        let mut state = self.root();
+        for (len, ch) in text.iter().copied().enumerate() {
+            if let Some(data) = state.get_data() {
+                f(len, data);
+            }
+            if !state.walk(ch) {
+                return;
+            }
+        }
+        if let Some(data) = state.get_data() {
+            f(text.len(), data);
+        }
+    }
+
+    /// Retrieve the data of each of `keys`, in the same order. The keys are
+    /// looked up in sorted order so that the branches walked for a key are
+    /// reused by the next keys sharing its prefix.
+    pub fn retrieve_many<'a>(&'a self, keys: &[&[AlphaChar]]) -> Vec<Option<&'a TrieData>> {
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_unstable_by(|a, b| strip_terminator(keys[*a]).cmp(strip_terminator(keys[*b])));
+
+        let mut results = vec![None; keys.len()];
+        // path[i] is the state after walking i characters of the previous key
+        let mut path = vec![self.da.get_root()];
+        let mut prev_key: &[AlphaChar] = &[];
+        for idx in order {
+            let key = strip_terminator(keys[idx]);
+            let common = prev_key.iter().zip(key).take_while(|(a, b)| a == b).count();
+            path.truncate(common + 1);
+            results[idx] = self
+                .walk_key_from(&mut path, key)
+                .and_then(|t| self.tail.get_data(t));
+            prev_key = key;
+        }
+        results
+    }
+
+    /// Same as [walk_key](Self::walk_key), but resume from the last state of
+    /// `path`, pushing the states walked through the branches. `key` has no
+    /// terminator.
+    fn walk_key_from(&self, path: &mut Vec<TrieIndex>, key: &[AlphaChar]) -> Option<TrieIndex> {
+        // unwrap as an assertion since the path always has the root
+        let mut s = *path.last().unwrap();
+        let mut rest = key[(path.len() - 1)..].iter().copied().chain(iter::once(0));
+
+        // walk through branches
+        let mut last_ch = ALPHA_CHAR_ERROR;
+        for ch in rest.by_ref() {
+            last_ch = ch;
+            if self.da.is_separate(s) {
+                break;
+            }
+            let tc = self.alpha_map.char_to_trie(ch)?;
+            s = self.da.walk(s, tc as TrieChar)?;
+            path.push(s);
+            if ch == 0 {
+                break;
+            }
+        }
+
+        // walk through tail
+        let t = self.da.get_tail_index(s);
+        let mut suffix_idx = 0;
+        for ch in iter::once(last_ch).chain(rest) {
+            let tc = self.alpha_map.char_to_trie(ch)?;
+            suffix_idx = self.tail.walk_char(t, suffix_idx, tc as TrieChar)?;
+        }
+
+        Some(t)
+    }
+
+    /// Walk `key` through the branches and the tail. Returns the tail index
+    /// of the key and the number of branch cells walked.
+    fn walk_key<I: IntoIterator<Item = AlphaChar>>(&self, key: I) -> Option<(TrieIndex, usize)> {
         // walk through branches
         let mut s = self.da.get_root();
-        let mut key_iter = key.iter().copied();
+        let mut depth = 0;
+        let mut key_iter = key.into_iter();
         let mut last_ch = ALPHA_CHAR_ERROR;
         while let Some(ch) = key_iter.next() {
             last_ch = ch;
@@ -305,6 +1465,7 @@ impl<TrieData: Default> ROTrie<TrieData> {
             }
             let tc = self.alpha_map.char_to_trie(ch)?;
             s = self.da.walk(s, tc as TrieChar)?;
+            depth += 1;
             if ch == 0 {
                 break;
             }
@@ -319,14 +1480,167 @@ impl<TrieData: Default> ROTrie<TrieData> {
             suffix_idx = self.tail.walk_char(s, suffix_idx, tc as TrieChar)?;
         }
 
-        // found
-        // unwrap as an assertion since this should never fail
-        Some(self.tail.get_data(s).unwrap())
+        Some((s, depth))
     }
 
+    /// Iterate all entries in the trie, in trie character (collation) order
+    /// of the keys. See [TrieIterator] for details.
     pub fn iter(&self) -> TrieIterator<TrieData> {
         TrieIterator::new_from_trie(self)
     }
+
+    /// Iterate the keys starting with `prefix` and their data, see
+    /// [TrieIterator::new_with_prefix]. Only the sub-trie below the prefix
+    /// is visited.
+    pub fn iter_prefix(&self, prefix: &[AlphaChar]) -> TrieIterator<'_, '_, TrieData> {
+        TrieIterator::new_with_prefix(self, prefix)
+    }
+
+    /// Iterate the keys `k` with `lo <= k < hi` in the order of
+    /// [iter](Self::iter), along with their data. The keys are compared by
+    /// their [AlphaChar] values. The bounds are read up to their terminator,
+    /// if any, and need not be keys in the trie or even be in the alphabet.
+    ///
+    /// With an alphabet [with a collation](AlphaMap::with_collation), the
+    /// trie is not in the order of the character values, so every key is
+    /// visited instead of only those in the range.
+    pub fn range<'a>(
+        &'a self,
+        lo: &[AlphaChar],
+        hi: &[AlphaChar],
+    ) -> impl Iterator<Item = (Vec<AlphaChar>, Option<&'a TrieData>)> + 'a {
+        let is_collated = self.alpha_map.is_collated();
+        let mut iter = TrieIterator::new_from_trie(self);
+        let lo = strip_terminator(lo).to_vec();
+        let first = if is_collated {
+            None
+        } else {
+            iter.seek(&lo).then(|| (iter.key().unwrap(), iter.data()))
+        };
+        let hi = strip_terminator(hi).to_vec();
+        let in_range = move |key: &[AlphaChar]| lo.as_slice() <= key && key < hi.as_slice();
+        first
+            .into_iter()
+            .chain(iter)
+            .map(move |(key, data)| {
+                let is_in = in_range(strip_terminator(&key));
+                (key, data, is_in)
+            })
+            // past the range, unless the keys are not in order
+            .take_while(move |(_, _, is_in)| is_collated || *is_in)
+            .filter(|(_, _, is_in)| *is_in)
+            .map(|(key, data, _)| (key, data))
+    }
+
+    /// Count the keys starting with `prefix`. This walks the same sub-trie
+    /// as [iter_prefix](Self::iter_prefix), but without building the keys.
+    pub fn count_prefix(&self, prefix: &[AlphaChar]) -> usize {
+        let mut iter = TrieIterator::new_with_prefix(self, prefix);
+        let mut count = 0;
+        while iter.iter_next() {
+            count += 1;
+        }
+        count
+    }
+
+    /// Continue iterating from a saved [TrieCursor].
+    /// Returns None if the cursor is not valid for this trie.
+    pub fn iter_from_cursor(&self, cursor: &TrieCursor) -> Option<TrieIterator<'_, '_, TrieData>> {
+        TrieIterator::from_cursor(self, cursor)
+    }
+
+    /// Iterate all entries, transforming each data with `f` as it is visited.
+    /// The mapping is lazy, so no intermediate collection is made.
+    pub fn map_data<'a, U, F>(&'a self, mut f: F) -> impl Iterator<Item = (Vec<AlphaChar>, U)> + 'a
+    where
+        F: FnMut(&TrieData) -> U + 'a,
+    {
+        self.iter()
+            .filter_map(move |(key, data)| data.map(|data| (key, f(data))))
+    }
+
+    /// Iterate keys whose data matches `pred`, e.g. `|d| d == &target` to find
+    /// all keys mapped to a value. This scans the whole trie, so it takes O(n)
+    /// time, but keys are only built for entries that match.
+    pub fn keys_with_data<'a, F>(&'a self, pred: F) -> impl Iterator<Item = Vec<AlphaChar>> + 'a
+    where
+        F: Fn(&TrieData) -> bool + 'a,
+    {
+        let mut iter = self.iter();
+        iter::from_fn(move || {
+            while iter.iter_next() {
+                if iter.data().is_some_and(&pred) {
+                    return iter.key();
+                }
+            }
+            None
+        })
+    }
+
+    /// Collect all keys into a single buffer, one after another with their
+    /// terminators, in the order of [iter](Self::iter). Returns the buffer
+    /// and the offset where each key starts in it, so that no allocation is
+    /// made for each key.
+    ///
+    /// Key `i` spans from `offsets[i]` to `offsets[i + 1]`, or to the end of
+    /// the buffer for the last key.
+    pub fn collect_keys(&self) -> (Vec<AlphaChar>, Vec<usize>) {
+        let mut iter = self.iter();
+        let mut keys = Vec::new();
+        let mut offsets = Vec::with_capacity(iter.size_hint().0);
+        while iter.iter_next() {
+            offsets.push(keys.len());
+            // unwrap as an assertion since iter_next has set the state
+            iter.append_key(&mut keys).unwrap();
+        }
+        (keys, offsets)
+    }
+
+    /// Call `f` on each entry in the trie, stopping early if `f` returns false.
+    /// Unlike [iter](Self::iter), the key buffer is reused between entries so
+    /// no allocation is made for each entry.
+    ///
+    /// Returns true if all entries were visited.
+    pub fn for_each<F: FnMut(&[AlphaChar], &TrieData) -> bool>(&self, mut f: F) -> bool {
+        let mut iter = self.iter();
+        let mut key = Vec::new();
+        while iter.iter_next() {
+            let Some(data) = iter.data() else {
+                continue;
+            };
+            if iter.key_into(&mut key).is_none() {
+                continue;
+            }
+            if !f(&key, data) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<TrieData: Default + Send + Sync> ROTrie<TrieData> {
+    /// Iterate all entries in the trie in parallel. The sub-tries under each
+    /// child of the root are walked on their own, so the entries are not in
+    /// the order of [iter](Self::iter).
+    pub fn par_iter(&self) -> impl ParallelIterator<Item = (Vec<AlphaChar>, &TrieData)> {
+        self.root()
+            .walkable_chars()
+            .into_par_iter()
+            .flat_map_iter(move |ac| {
+                // the empty key would be taken as no prefix at all
+                let (entries, empty) = match ac {
+                    0 => (None, self.retrieve(&[0]).map(|data| (vec![0], data))),
+                    ac => (Some(TrieIterator::new_with_prefix(self, &[ac])), None),
+                };
+                entries
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|(key, data)| Some((key, data?)))
+                    .chain(empty)
+            })
+    }
 }
 
 #[cfg(feature = "std")]
@@ -337,6 +1651,8 @@ impl<TrieData: TrieSerializable + Default> ROTrie<TrieData> {
         self.serialize(&mut fp)
     }
 
+    /// Write the trie in the format of libdatrie, or in a later version of
+    /// the format if the trie needs it, see [format_version](Self::format_version).
     pub fn serialize<T: Write>(&self, writer: &mut T) -> io::Result<()> {
         self.alpha_map.serialize(writer)?;
         self.da.serialize(writer)?;
@@ -349,6 +1665,37 @@ impl<TrieData: TrieSerializable + Default> ROTrie<TrieData> {
     pub fn serialized_size(&self) -> usize {
         self.alpha_map.serialized_size() + self.da.serialized_size() + self.tail.serialized_size()
     }
+
+    /// Same as [serialize](Self::serialize), but prefix the trie with its
+    /// size in bytes as a big-endian u64. Read it back with
+    /// [from_reader_at](Self::from_reader_at), which then knows where the
+    /// trie ends when it is embedded among other data.
+    ///
+    /// The trie is serialized into memory first, so that the recorded size
+    /// is the number of bytes actually written, whatever the
+    /// [serialized_size](TrieSerializable::serialized_size) of the data says.
+    pub fn serialize_with_length<T: Write>(&self, writer: &mut T) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(self.serialized_size());
+        self.serialize(&mut buf)?;
+        writer.write_u64::<BigEndian>(buf.len() as u64)?;
+        writer.write_all(&buf)
+    }
+
+    /// Same as [serialize](Self::serialize), but compress the trie with
+    /// DEFLATE after a signature telling it apart from an uncompressed trie.
+    /// Read it back with [from_reader_compressed](Self::from_reader_compressed).
+    ///
+    /// The compressed size is not known until the trie is written, so there
+    /// is no counterpart to [serialized_size](Self::serialized_size), which
+    /// is an upper bound in practice.
+    #[cfg(feature = "flate2")]
+    pub fn serialize_compressed<T: Write>(&self, writer: &mut T) -> io::Result<()> {
+        writer.write_u32::<BigEndian>(COMPRESSED_SIGNATURE)?;
+        let mut encoder = DeflateEncoder::new(writer, Compression::default());
+        self.serialize(&mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    }
 }
 
 #[cfg(feature = "std")]
@@ -373,6 +1720,99 @@ impl<TrieData: TrieDeserializable + Default> ROTrie<TrieData> {
             tail,
         })
     }
+
+    /// Same as [from_reader](Self::from_reader), but read the trie from the
+    /// start of `data`. Bytes after the trie are ignored. To find where the
+    /// trie ends, give the slice to [from_reader](Self::from_reader)
+    /// instead, which leaves it at the bytes after the trie.
+    pub fn from_bytes(mut data: &[u8]) -> io::Result<Self> {
+        Self::from_reader(&mut data)
+    }
+
+    /// Get the format version of the trie the reader is at, without consuming
+    /// it. Tries of a version newer than this crate can read are refused by
+    /// [from_reader](Self::from_reader) instead of being misparsed.
+    ///
+    /// Trie files have been written in version 1, the format of libdatrie,
+    /// which starts right away with the alphabet. Later versions start with
+    /// a header of the signature `0xd9f0d9f0` followed by the version, both
+    /// as big-endian u32. A header with version 1 is also accepted. Version
+    /// 2 is only written for an alphabet with a collation, see
+    /// [AlphaMap::with_collation].
+    ///
+    /// Other tries are still written in version 1 without the header, so
+    /// that libdatrie can read them. The trade-off is that such a file
+    /// cannot be told apart from one written by libdatrie, so any future
+    /// change to its layout must come with the header and a new version.
+    pub fn format_version<T: Read + Seek>(reader: &mut T) -> io::Result<u32> {
+        let start = reader.stream_position()?;
+        let version = AlphaMap::read_format_version(reader);
+        reader.seek(SeekFrom::Start(start))?;
+        version
+    }
+
+    /// Same as [from_reader](Self::from_reader), but [validate](Self::validate)
+    /// the trie before returning it.
+    pub fn from_reader_validated<T: Read>(reader: &mut T) -> io::Result<Self> {
+        let trie = Self::from_reader(reader)?;
+        trie.validate()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(trie)
+    }
+
+    /// Read a trie written by [serialize_with_length](Self::serialize_with_length).
+    /// Exactly the recorded number of bytes is consumed, leaving the reader
+    /// at the data following the trie.
+    pub fn from_reader_at<T: Read>(reader: &mut T) -> io::Result<Self> {
+        let len = reader.read_u64::<BigEndian>()?;
+        let mut section = reader.take(len);
+        let trie = Self::from_reader(&mut section)?;
+        if section.limit() != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "trie shorter than its recorded length",
+            ));
+        }
+        Ok(trie)
+    }
+
+    /// Read a trie written by [serialize_compressed](Self::serialize_compressed).
+    /// The reader is buffered so that only the compressed trie is consumed
+    /// from it, as with [from_reader](Self::from_reader).
+    #[cfg(feature = "flate2")]
+    pub fn from_reader_compressed<T: BufRead>(reader: &mut T) -> io::Result<Self> {
+        if reader.read_u32::<BigEndian>()? != COMPRESSED_SIGNATURE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid signature",
+            ));
+        }
+        let mut decoder = DeflateDecoder::new(reader);
+        let trie = Self::from_reader(&mut decoder)?;
+        // consume the end of the stream, which must have nothing left
+        if io::copy(&mut decoder, &mut io::sink())? != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "trailing data after trie",
+            ));
+        }
+        Ok(trie)
+    }
+}
+
+/// Kind of node a [TrieState] is at, see [TrieState::node_kind]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    /// A node in the branches of the trie, where no key ends
+    Branch,
+    /// A node in the branches of the trie, where a key ends. Its data can be
+    /// read with [TrieState::get_data], while other keys may go on.
+    TerminalWithData,
+    /// Inside the rest of a single key, which does not end here
+    TailSingle,
+    /// The end of a single key, whose data can be read with
+    /// [TrieState::get_data]
+    Leaf,
 }
 
 pub struct TrieState<'a, TrieData: Default> {
@@ -407,33 +1847,40 @@ impl<'a, TrieData: Default> TrieState<'a, TrieData> {
     }
 
     pub fn walk(&mut self, c: AlphaChar) -> bool {
-        let Some(tc) = self.trie.alpha_map.char_to_trie(c) else {
-            return false;
-        };
+        self.walk_tracked(c).is_some()
+    }
+
+    /// Same as [walk](Self::walk), but return the trie character that was
+    /// walked, such as to align the output of a transducer.
+    pub fn walk_tracked(&mut self, c: AlphaChar) -> Option<TrieChar> {
+        let tc = self.trie.alpha_map.char_to_trie(c)? as TrieChar;
         if !self.is_suffix {
-            if let Some(next_idx) = self.trie.da.walk(self.index, tc as TrieChar) {
-                self.index = next_idx;
-                if self.trie.da.is_separate(self.index) {
-                    self.index = self.trie.da.get_tail_index(self.index);
-                    self.suffix_idx = 0;
-                    self.is_suffix = true;
-                }
-                return true;
-            } else {
-                return false;
+            self.index = self.trie.da.walk(self.index, tc)?;
+            if self.trie.da.is_separate(self.index) {
+                self.index = self.trie.da.get_tail_index(self.index);
+                self.suffix_idx = 0;
+                self.is_suffix = true;
             }
         } else {
-            if let Some(next_idx) =
-                self.trie
-                    .tail
-                    .walk_char(self.index, self.suffix_idx, tc as TrieChar)
-            {
-                self.suffix_idx = next_idx;
-                return true;
-            } else {
-                return false;
+            self.suffix_idx = self.trie.tail.walk_char(self.index, self.suffix_idx, tc)?;
+        }
+        Some(tc)
+    }
+
+    /// Walk the characters of `str` in order, stopping at the first one that
+    /// cannot be walked. The state is left after the last successful walk.
+    ///
+    /// Returns the number of characters walked, which is `str.len()` if the
+    /// whole string was walked.
+    pub fn walk_str(&mut self, str: &[AlphaChar]) -> usize {
+        let mut walked = 0;
+        for ch in str.iter().copied() {
+            if !self.walk(ch) {
+                break;
             }
+            walked += 1;
         }
+        walked
     }
 
     pub fn is_walkable(&self, c: AlphaChar) -> bool {
@@ -449,28 +1896,66 @@ impl<'a, TrieData: Default> TrieState<'a, TrieData> {
         }
     }
 
+    /// Get the characters that can be walked from this state, in ascending
+    /// order. The terminator 0 is included if the state is terminal.
     pub fn walkable_chars(&self) -> Vec<AlphaChar> {
+        self.walkable_chars_iter().collect()
+    }
+
+    /// Same as [walkable_chars](Self::walkable_chars), but without collecting
+    /// the characters. A single state yields exactly one character.
+    pub fn walkable_chars_iter(&self) -> impl Iterator<Item = AlphaChar> + '_ {
+        let (children, single) = if !self.is_suffix {
+            (Some(self.trie.da.children(self.index)), None)
+        } else {
+            let suffix = self.trie.tail.get_suffix(self.index).unwrap();
+            (None, Some(suffix[self.suffix_idx as usize]))
+        };
+        children
+            .into_iter()
+            .flatten()
+            .chain(single)
+            .map(|tc| self.trie.alpha_map.trie_to_char(tc))
+    }
+
+    /// Same as [walkable_chars_iter](Self::walkable_chars_iter), but yield
+    /// the trie characters without mapping them through the alphabet, such
+    /// as to drive a custom search over the trie.
+    pub fn walkable_symbols(&self) -> WalkableSymbols {
+        let mut symbols = Symbols::default();
         if !self.is_suffix {
-            self.trie
-                .da
-                .output_symbols(self.index)
-                .iter()
-                .copied()
-                .map_to_alpha_char(&self.trie.alpha_map)
-                .collect()
+            for tc in self.trie.da.children(self.index) {
+                symbols.add_fast(tc);
+            }
         } else {
             let suffix = self.trie.tail.get_suffix(self.index).unwrap();
-            vec![self
-                .trie
-                .alpha_map
-                .trie_to_char(suffix[self.suffix_idx as usize])]
+            symbols.add_fast(suffix[self.suffix_idx as usize]);
         }
+        WalkableSymbols::new(symbols)
     }
 
     pub fn is_single(&self) -> bool {
         self.is_suffix
     }
 
+    /// Get the rest of the key from a single state, without the terminator.
+    /// Returns None if the state is not single, i.e. the key is not yet
+    /// determined.
+    pub fn suffix(&self) -> Option<Vec<AlphaChar>> {
+        if !self.is_suffix {
+            return None;
+        }
+        let suffix = self.trie.tail.get_suffix(self.index)?;
+        Some(
+            suffix[(self.suffix_idx as usize)..]
+                .iter()
+                .copied()
+                .take_while(|tc| *tc != TRIE_CHAR_TERM)
+                .map_to_alpha_char(&self.trie.alpha_map)
+                .collect(),
+        )
+    }
+
     pub fn is_terminal(&self) -> bool {
         self.is_walkable(0)
     }
@@ -479,7 +1964,18 @@ impl<'a, TrieData: Default> TrieState<'a, TrieData> {
         self.is_single() && self.is_terminal()
     }
 
-    pub fn get_data(&self) -> Option<&TrieData> {
+    /// Tell what kind of node the state is at, combining
+    /// [is_single](Self::is_single) and [is_terminal](Self::is_terminal)
+    pub fn node_kind(&self) -> NodeKind {
+        match (self.is_single(), self.is_terminal()) {
+            (false, false) => NodeKind::Branch,
+            (false, true) => NodeKind::TerminalWithData,
+            (true, false) => NodeKind::TailSingle,
+            (true, true) => NodeKind::Leaf,
+        }
+    }
+
+    pub fn get_data(&self) -> Option<&'a TrieData> {
         if !self.is_suffix {
             if let Some(index) = self.trie.da.walk(self.index, TRIE_CHAR_TERM) {
                 if self.trie.da.is_separate(index) {
@@ -514,18 +2010,77 @@ impl<'a, TrieData: Default> Clone for TrieState<'a, TrieData> {
     }
 }
 
+/// States are equal if they are at the same position of the same trie.
+/// States of different [ROTrie] instances are never equal, even if their
+/// positions are numerically the same, such as in two copies of a trie.
+impl<'a, TrieData: Default> PartialEq for TrieState<'a, TrieData> {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self.trie, other.trie)
+            && self.index == other.index
+            && self.is_suffix == other.is_suffix
+            // the suffix offset is left over after rewinding, and only
+            // matters in the suffix
+            && (!self.is_suffix || self.suffix_idx == other.suffix_idx)
+    }
+}
+
+impl<'a, TrieData: Default> Eq for TrieState<'a, TrieData> {}
+
+impl<'a, TrieData: Default> Hash for TrieState<'a, TrieData> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        ptr::hash(self.trie, state);
+        self.index.hash(state);
+        self.is_suffix.hash(state);
+        if self.is_suffix {
+            self.suffix_idx.hash(state);
+        }
+    }
+}
+
+/// Iterator over entries of a trie or a sub-trie.
+///
+/// Entries are yielded in trie character (collation) order of the keys, with
+/// a key coming before the keys it is a prefix of. [AlphaMap] assigns trie
+/// characters in ascending order of alphabet characters regardless of the
+/// order the ranges were added, so this is the lexicographic order of the
+/// keys' [AlphaChar] values, unless the alphabet has a
+/// [collation](AlphaMap::with_collation).
+///
+/// The iterator can also be walked from the back, such as with
+/// `trie.iter().rev()`, yielding the same entries in reverse order.
 pub struct TrieIterator<'trie: 'state, 'state, TrieData: Default> {
     root: Cow<'state, TrieState<'trie, TrieData>>,
     state: Option<TrieState<'trie, TrieData>>,
     key: Vec<TrieChar>,
+    /// Position of the last entry returned from the back, with its key
+    back: Option<TrieState<'trie, TrieData>>,
+    back_key: Vec<TrieChar>,
+    /// Characters walked to reach `root`, prepended to each returned key
+    prefix: Vec<AlphaChar>,
+    /// Set when the iterator has no entry left, such as an unknown prefix or
+    /// when both ends have met
+    is_empty: bool,
+    /// Upper bound of the number of entries left
+    remaining: usize,
+    /// Whether `remaining` is exact, i.e. the iterator covers the whole trie
+    is_exact: bool,
 }
 
 impl<'trie: 'state, 'state, TrieData: Default> TrieIterator<'trie, 'state, TrieData> {
+    /// Iterate the entries under `root`. The returned keys are relative to
+    /// `root`, i.e. without the characters walked to reach it. Use
+    /// [with_prefix](Self::with_prefix) to get the full keys instead.
     pub fn new(root: &'state TrieState<'trie, TrieData>) -> TrieIterator<'trie, 'state, TrieData> {
         TrieIterator {
             root: Cow::Borrowed(root),
             state: None,
             key: Vec::<TrieChar>::default(),
+            back: None,
+            back_key: Vec::new(),
+            prefix: Vec::new(),
+            is_empty: false,
+            remaining: root.trie.len(),
+            is_exact: !root.is_suffix && root.index == root.trie.da.get_root(),
         }
     }
 
@@ -534,14 +2089,253 @@ impl<'trie: 'state, 'state, TrieData: Default> TrieIterator<'trie, 'state, TrieD
             root: Cow::Owned(trie.root()),
             state: None,
             key: Vec::<TrieChar>::default(),
+            back: None,
+            back_key: Vec::new(),
+            prefix: Vec::new(),
+            is_empty: false,
+            remaining: trie.len(),
+            is_exact: true,
+        }
+    }
+
+    /// Create an iterator resuming from a position saved by [TrieIterator::cursor].
+    /// Returns None if the cursor does not point to a valid position in `trie`.
+    pub fn from_cursor(
+        trie: &'trie ROTrie<TrieData>,
+        cursor: &TrieCursor,
+    ) -> Option<TrieIterator<'trie, 'state, TrieData>> {
+        let root = cursor.root.to_state(trie)?;
+        let state = match cursor.state {
+            Some(pos) => {
+                let state = pos.to_state(trie)?;
+                if !cursor.leads_to(&root, &state) {
+                    return None;
+                }
+                Some(state)
+            }
+            None if cursor.key.is_empty() => None,
+            None => return None,
+        };
+
+        Some(TrieIterator {
+            root: Cow::Owned(root),
+            state,
+            key: cursor.key.clone(),
+            back: None,
+            back_key: Vec::new(),
+            prefix: Vec::new(),
+            is_empty: false,
+            remaining: trie.len(),
+            is_exact: false,
+        })
+    }
+
+    /// Iterate only keys starting with `prefix`, which is read up to its
+    /// terminator, if any. The returned keys include the prefix.
+    pub fn new_with_prefix(
+        trie: &'trie ROTrie<TrieData>,
+        prefix: &[AlphaChar],
+    ) -> TrieIterator<'trie, 'state, TrieData> {
+        let prefix = strip_terminator(prefix);
+        let mut root = trie.root();
+        let is_empty = root.walk_str(prefix) != prefix.len();
+        TrieIterator {
+            root: Cow::Owned(root),
+            state: None,
+            key: Vec::<TrieChar>::default(),
+            back: None,
+            back_key: Vec::new(),
+            prefix: prefix.to_vec(),
+            is_empty,
+            remaining: trie.len(),
+            is_exact: prefix.is_empty(),
+        }
+    }
+
+    /// Prepend `prefix`, read up to its terminator, if any, to each returned
+    /// key. Giving the characters walked to reach the root state of the
+    /// iterator makes the keys absolute again.
+    ///
+    /// ```
+    /// use datrie::{AlphaMap, AsAlphaChar, Trie, TrieIterator};
+    ///
+    /// let mut alpha_map = AlphaMap::default();
+    /// alpha_map.add_range('a' as u32..='z' as u32).unwrap();
+    /// let mut trie = Trie::new(alpha_map);
+    /// trie.store(&"abc".as_alphachar(), 1);
+    ///
+    /// let prefix: Vec<u32> = "ab".chars().map(|c| c as u32).collect();
+    /// let mut state = trie.root();
+    /// state.walk_str(&prefix);
+    /// let keys: Vec<_> = TrieIterator::new(&state)
+    ///     .with_prefix(&prefix)
+    ///     .map(|(key, _)| key)
+    ///     .collect();
+    /// assert_eq!(keys, vec!["abc".as_alphachar()]);
+    /// ```
+    pub fn with_prefix(mut self, prefix: &[AlphaChar]) -> Self {
+        self.prefix = strip_terminator(prefix).to_vec();
+        self
+    }
+
+    /// Move to the first entry whose key is not less than `lo`, which has
+    /// no terminator. Returns false if there is no such entry.
+    fn seek(&mut self, lo: &[AlphaChar]) -> bool {
+        let trie = self.root.trie;
+        let root = self.root.index;
+        let da = &trie.da;
+        self.key.clear();
+        self.is_exact = false;
+
+        let mut s = root;
+        let mut rest = lo;
+        let found = loop {
+            if da.is_separate(s) {
+                // compare the rest of the bound with the suffix in the tail
+                let suffix = trie.tail.get_suffix(da.get_tail_index(s)).unwrap_or(&[]);
+                let suffix = suffix.iter().copied().map_to_alpha_char(&trie.alpha_map);
+                if suffix.cmp(rest.iter().copied()) != Ordering::Less {
+                    break Some(s);
+                }
+                break da.next_separate(root, s, &mut self.key);
+            }
+            let Some((&ac, next_rest)) = rest.split_first() else {
+                // every key below is an extension of the bound
+                break da.first_separate(s, &mut self.key);
+            };
+
+            let tc = trie.alpha_map.char_to_trie(ac);
+            if let Some(next) = tc.and_then(|tc| da.walk(s, tc as TrieChar)) {
+                self.key.push(tc.unwrap() as TrieChar);
+                s = next;
+                rest = next_rest;
+                continue;
+            }
+
+            // no exact match, so go to the first child above the character
+            let ceil = match tc {
+                Some(tc) => Some(tc + 1),
+                None => trie.alpha_map.ceil_char_to_trie(ac),
+            };
+            match ceil.and_then(|c| da.first_child_from(s, c)) {
+                Some(c) => {
+                    // unwrap as an assertion since c is a child of s
+                    let child = da.walk(s, c).unwrap();
+                    self.key.push(c);
+                    break da.first_separate(child, &mut self.key);
+                }
+                None => break da.next_separate(root, s, &mut self.key),
+            }
+        };
+
+        let Some(sep) = found else {
+            self.is_empty = true;
+            return false;
+        };
+        let mut state = self.root.deref().clone();
+        state.index = sep;
+        self.state = Some(state);
+        true
+    }
+
+    /// Save the current position of the iterator. Iterating from the cursor
+    /// continues right after the last returned entry, as long as the trie has
+    /// not been modified in between. Entries returned from the back are not
+    /// recorded.
+    pub fn cursor(&self) -> TrieCursor {
+        TrieCursor {
+            root: CursorState::from(self.root.deref()),
+            state: self.state.as_ref().map(CursorState::from),
+            key: self.key.clone(),
         }
     }
 
+    /// Move to the next entry without building its key, unlike
+    /// [next](Iterator::next). The entry can then be read with
+    /// [key](Self::key), [trie_key](Self::trie_key) or [data](Self::data).
+    /// Returns false if there is no entry left.
+    pub fn advance(&mut self) -> bool {
+        self.iter_next()
+    }
+
     pub fn key(&self) -> Option<Vec<AlphaChar>> {
+        let mut out = Vec::new();
+        self.key_into(&mut out)?;
+        Some(out)
+    }
+
+    /// Get the key of the current entry as raw trie characters, without
+    /// mapping them back through the alphabet. The key is returned in two
+    /// parts, walked in the double-array and stored in the tail, neither of
+    /// them with the terminator. Like [key](Self::key), the key is relative
+    /// to the root of the iterator, and the prefix of an iterator from
+    /// [ROTrie::iter_prefix] is not included.
+    pub fn trie_key(&self) -> Option<(&[TrieChar], &[TrieChar])> {
+        let state = self.state.as_ref()?;
+        let (key, suffix) = if state.is_suffix {
+            let suffix = state.trie.tail.get_suffix(state.index)?;
+            (&[][..], &suffix[(state.suffix_idx as usize)..])
+        } else {
+            let tail_idx = state.trie.da.get_tail_index(state.index);
+            (&self.key[..], state.trie.tail.get_suffix(tail_idx)?)
+        };
+        fn until_term(chars: &[TrieChar]) -> &[TrieChar] {
+            match chars.iter().position(|tc| *tc == TRIE_CHAR_TERM) {
+                Some(end) => &chars[..end],
+                None => chars,
+            }
+        }
+        Some((until_term(key), until_term(suffix)))
+    }
+
+    /// Get a state at the end of the current entry's key, before its
+    /// terminator, as if walked from the root of the iterator. The state is
+    /// [terminal](TrieState::is_terminal), holds the entry's data, and can be
+    /// walked further to continue from the entry.
+    pub fn state(&self) -> Option<TrieState<'trie, TrieData>> {
         let state = self.state.as_ref()?;
+        let trie = state.trie;
+        let (tail_index, start) = if state.is_suffix {
+            (state.index, state.suffix_idx)
+        } else {
+            if !trie.da.is_separate(state.index) {
+                return None;
+            }
+            // the key ends in the double-array if the separate node was
+            // reached by the terminator, then its parent ends the key
+            if self.key.last() == Some(&TRIE_CHAR_TERM) {
+                let parent = trie.da.get_check(state.index)?;
+                return Some(TrieState::new(trie, parent, 0, false));
+            }
+            (trie.da.get_tail_index(state.index), 0)
+        };
+        let suffix = trie.tail.get_suffix(tail_index)?;
+        let len = suffix[(start as usize)..]
+            .iter()
+            .position(|tc| *tc == TRIE_CHAR_TERM)?;
+        Some(TrieState::new(trie, tail_index, start + len as i16, true))
+    }
+
+    /// Same as [key](Self::key), but write the key into `out` to reuse its buffer
+    fn key_into(&self, out: &mut Vec<AlphaChar>) -> Option<()> {
+        out.clear();
+        self.append_key(out)
+    }
+
+    /// Same as [key](Self::key), but append the key to `out`
+    fn append_key(&self, out: &mut Vec<AlphaChar>) -> Option<()> {
+        self.key_of(self.state.as_ref()?, &self.key, out)
+    }
 
+    /// Append the key of the entry at `state`, walked with `key` from the root
+    fn key_of(
+        &self,
+        state: &TrieState<'trie, TrieData>,
+        key: &[TrieChar],
+        out: &mut Vec<AlphaChar>,
+    ) -> Option<()> {
         let mut tail_str;
-        let mut out = Vec::new();
+        out.extend_from_slice(&self.prefix);
 
         // if state in tail, root == state
         if state.is_suffix {
@@ -552,12 +2346,7 @@ impl<'trie: 'state, 'state, TrieData: Default> TrieIterator<'trie, 'state, TrieD
             tail_str = state.trie.tail.get_suffix(tail_idx)?;
 
             // Add current key to the output
-            out.extend(
-                self.key
-                    .iter()
-                    .copied()
-                    .map_to_alpha_char(&state.trie.alpha_map),
-            )
+            out.extend(key.iter().copied().map_to_alpha_char(&state.trie.alpha_map))
         }
 
         out.extend(
@@ -568,60 +2357,122 @@ impl<'trie: 'state, 'state, TrieData: Default> TrieIterator<'trie, 'state, TrieD
         );
         out.push(0);
 
-        Some(out)
+        Some(())
     }
 
     pub fn data(&self) -> Option<&'state TrieData> {
-        let state = self.state.as_ref()?;
+        Self::data_of(self.state.as_ref()?)
+    }
 
-        let tail_index;
+    fn data_of(state: &TrieState<'trie, TrieData>) -> Option<&'trie TrieData> {
+        state.trie.tail.get_data(Self::tail_index_of(state)?)
+    }
+
+    /// Get the index of the tail block holding the data of the current entry
+    fn tail_index(&self) -> Option<TrieIndex> {
+        Self::tail_index_of(self.state.as_ref()?)
+    }
 
+    fn tail_index_of(state: &TrieState<'trie, TrieData>) -> Option<TrieIndex> {
         if !state.is_suffix {
             if !state.trie.da.is_separate(state.index) {
                 return None;
             }
-            tail_index = state.trie.da.get_tail_index(state.index);
+            Some(state.trie.da.get_tail_index(state.index))
         } else {
-            tail_index = state.index;
+            Some(state.index)
         }
-
-        state.trie.tail.get_data(tail_index)
     }
 
     fn iter_next(&mut self) -> bool {
-        return match &mut self.state {
+        if self.is_empty {
+            return false;
+        }
+        let is_moved = match &mut self.state {
             Some(state) => {
                 // no next entry for tail state
-                if state.is_suffix {
-                    return false;
-                }
-
-                let Some(sep) =
-                    state
-                        .trie
-                        .da
-                        .next_separate(self.root.index, state.index, &mut self.key)
-                else {
-                    return false;
-                };
-                state.index = sep;
-                true
+                !state.is_suffix
+                    && match state.trie.da.next_separate(
+                        self.root.index,
+                        state.index,
+                        &mut self.key,
+                    ) {
+                        Some(sep) => {
+                            state.index = sep;
+                            true
+                        }
+                        None => false,
+                    }
             }
             None => {
                 let state = self.state.insert(self.root.deref().clone());
 
                 // for tail state, we are already at the only entry
-                if state.is_suffix {
-                    return true;
-                }
+                state.is_suffix
+                    || match state.trie.da.first_separate(state.index, &mut self.key) {
+                        Some(sep) => {
+                            state.index = sep;
+                            true
+                        }
+                        None => false,
+                    }
+            }
+        };
+        self.check_ends_met(is_moved)
+    }
 
-                let Some(sep) = state.trie.da.first_separate(state.index, &mut self.key) else {
-                    return false;
-                };
-                state.index = sep;
-                true
+    fn iter_next_back(&mut self) -> bool {
+        if self.is_empty {
+            return false;
+        }
+        let is_moved = match &mut self.back {
+            Some(state) => {
+                // no previous entry for tail state
+                !state.is_suffix
+                    && match state.trie.da.prev_separate(
+                        self.root.index,
+                        state.index,
+                        &mut self.back_key,
+                    ) {
+                        Some(sep) => {
+                            state.index = sep;
+                            true
+                        }
+                        None => false,
+                    }
             }
+            None => {
+                let state = self.back.insert(self.root.deref().clone());
+
+                // for tail state, we are already at the only entry
+                state.is_suffix
+                    || match state.trie.da.last_separate(state.index, &mut self.back_key) {
+                        Some(sep) => {
+                            state.index = sep;
+                            true
+                        }
+                        None => false,
+                    }
+            }
+        };
+        self.check_ends_met(is_moved)
+    }
+
+    /// Mark the iterator as exhausted if it could not move, or if one end
+    /// has moved onto an entry already returned from the other end
+    fn check_ends_met(&mut self, is_moved: bool) -> bool {
+        let is_met = match (&self.state, &self.back) {
+            (Some(front), Some(back)) => {
+                front.index == back.index && front.is_suffix == back.is_suffix
+            }
+            _ => false,
         };
+        if !is_moved || is_met {
+            self.is_empty = true;
+            return false;
+        }
+        self.remaining = self.remaining.saturating_sub(1);
+        true
     }
 }
 
@@ -634,6 +2485,469 @@ impl<'trie: 'state, 'state, TrieData: Default> Iterator for TrieIterator<'trie,
             false => None,
         }
     }
+
+    /// The hint is exact when iterating the whole trie. Otherwise, the
+    /// number of keys in the trie is used as an upper bound.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match (self.is_empty, self.is_exact) {
+            (true, _) => (0, Some(0)),
+            (false, true) => (self.remaining, Some(self.remaining)),
+            (false, false) => (0, Some(self.remaining)),
+        }
+    }
+}
+
+impl<'trie: 'state, 'state, TrieData: Default> DoubleEndedIterator
+    for TrieIterator<'trie, 'state, TrieData>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if !self.iter_next_back() {
+            return None;
+        }
+        // unwrap as an assertion since iter_next_back has set the state
+        let back = self.back.as_ref().unwrap();
+        let mut key = Vec::new();
+        self.key_of(back, &self.back_key, &mut key).unwrap();
+        Some((key, Self::data_of(back)))
+    }
+}
+
+/// Number of keys shown by the [Debug](fmt::Debug) output of a trie
+const DEBUG_KEYS: usize = 8;
+
+/// The first [DEBUG_KEYS] keys of a trie, for its [Debug](fmt::Debug) output
+struct DebugKeys<'a, TrieData: Default>(&'a ROTrie<TrieData>);
+
+impl<TrieData: Default> fmt::Debug for DebugKeys<'_, TrieData> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut list = f.debug_list();
+        for (key, _) in self.0.iter().take(DEBUG_KEYS) {
+            match key.as_slice().ac_to_string() {
+                Some(key) => list.entry(&key),
+                None => list.entry(&key),
+            };
+        }
+        if self.0.len() > DEBUG_KEYS {
+            list.finish_non_exhaustive()
+        } else {
+            list.finish()
+        }
+    }
+}
+
+impl<TrieData: Default> fmt::Debug for ROTrie<TrieData> {
+    /// Print the number of keys, the alphabet and the first few keys, so that
+    /// printing a large trie stays cheap
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ROTrie")
+            .field("len", &self.len())
+            .field("alpha_map", &self.alpha_map)
+            .field("keys", &DebugKeys(self))
+            .finish()
+    }
+}
+
+impl<TrieData: Default> fmt::Debug for Trie<TrieData> {
+    /// Same as the output of [ROTrie], with whether the trie is dirty
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Trie")
+            .field("len", &self.len())
+            .field("alpha_map", &self.ro.alpha_map)
+            .field("keys", &DebugKeys(&self.ro))
+            .field("is_dirty", &self.is_dirty)
+            .finish()
+    }
+}
+
+/// Reason a key could not be stored, see [Trie::try_store] and
+/// [Trie::from_sorted_iter]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StoreError {
+    /// The key has a character outside of the alphabet
+    CharNotInAlphaMap(AlphaChar),
+    /// The tail has no block index left for the key
+    IndexOverflow,
+    /// The double-array has no free cell left for the key, as it cannot
+    /// grow past TRIE_INDEX_MAX
+    PoolExhausted,
+    /// The key has a terminating 0 at the given position before its end
+    InteriorTerminator(usize),
+    /// The keys given to [Trie::from_sorted_iter] are out of order at the
+    /// given position
+    NotSorted(usize),
+    /// The double-array or the tail could not be grown for the key
+    OutOfMemory(TryReserveError),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::CharNotInAlphaMap(ac) => {
+                write!(f, "character {:#x} is outside of the alphabet", ac)
+            }
+            StoreError::IndexOverflow => write!(f, "tail index overflow"),
+            StoreError::PoolExhausted => write!(f, "double-array pool exhausted"),
+            StoreError::InteriorTerminator(pos) => write!(f, "key has a 0 at position {}", pos),
+            StoreError::NotSorted(pos) => write!(f, "keys are not sorted at position {}", pos),
+            StoreError::OutOfMemory(err) => write!(f, "out of memory: {}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StoreError {}
+
+/// Size of the parts of a trie, see [ROTrie::stats]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TrieStats {
+    pub num_keys: usize,
+    /// Number of double-array cells, including the free ones
+    pub num_cells: usize,
+    pub num_free_cells: usize,
+    /// Number of tail blocks, including the free ones
+    pub num_tail_blocks: usize,
+    /// Total length of the suffixes in the tail
+    pub suffix_bytes: usize,
+    /// Total length of the suffixes kept in memory, which is less than
+    /// `suffix_bytes` once the suffixes are shared by [Trie::optimize_tail]
+    pub stored_suffix_bytes: usize,
+}
+
+impl fmt::Display for TrieStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Keys: {}", self.num_keys)?;
+        writeln!(
+            f,
+            "Double-array cells: {} ({} free)",
+            self.num_cells, self.num_free_cells
+        )?;
+        writeln!(
+            f,
+            "Tail blocks: {} ({} free)",
+            self.num_tail_blocks,
+            self.num_tail_blocks.saturating_sub(self.num_keys)
+        )?;
+        write!(
+            f,
+            "Suffix bytes: {} ({} stored)",
+            self.suffix_bytes, self.stored_suffix_bytes
+        )
+    }
+}
+
+/// Inconsistency found in a trie, see [ROTrie::validate]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CorruptError {
+    /// The double-array header does not match its number of cells
+    CellCount,
+    /// The free list of the double-array is broken at the given cell
+    FreeList(TrieIndex),
+    /// The given cell does not belong to a valid parent
+    Check(TrieIndex),
+    /// The given cell points to a tail block that does not exist
+    TailIndex(TrieIndex),
+    /// The free list of the tail is broken at the given block
+    TailFreeList(TrieIndex),
+}
+
+impl fmt::Display for CorruptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CorruptError::CellCount => write!(f, "invalid double-array cell count"),
+            CorruptError::FreeList(s) => {
+                write!(f, "broken double-array free list at cell {}", s)
+            }
+            CorruptError::Check(s) => write!(f, "invalid parent of cell {}", s),
+            CorruptError::TailIndex(s) => write!(f, "invalid tail index in cell {}", s),
+            CorruptError::TailFreeList(t) => {
+                write!(f, "broken tail free list at block {}", t)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CorruptError {}
+
+/// Where a key is located, see [Trie::locate]
+enum Slot {
+    /// The key exists, with the given tail index
+    Occupied(TrieIndex),
+    Vacant(VacantSlot),
+}
+
+/// Where a missing key should be inserted, along with the rest of the key
+/// after the separate node
+enum VacantSlot {
+    Branch(TrieIndex, Vec<TrieChar>),
+    Tail(TrieIndex, Vec<TrieChar>),
+}
+
+/// A view into a single key of a [Trie], see [Trie::entry]
+pub enum Entry<'a, TrieData: Default> {
+    Occupied(OccupiedEntry<'a, TrieData>),
+    Vacant(VacantEntry<'a, TrieData>),
+}
+
+impl<'a, TrieData: Default> Entry<'a, TrieData> {
+    /// Insert `default` if the key is missing, and get a mutable reference
+    /// to the key's data
+    ///
+    /// # Panics
+    /// Panics if the trie has no room left for the key, see
+    /// [VacantEntry::try_insert] to handle it.
+    pub fn or_insert(self, default: TrieData) -> &'a mut TrieData {
+        self.or_insert_with(|| default)
+    }
+
+    /// Insert the result of `default` if the key is missing, and get a
+    /// mutable reference to the key's data
+    ///
+    /// # Panics
+    /// Panics if the trie has no room left for the key, see
+    /// [VacantEntry::try_insert] to handle it.
+    pub fn or_insert_with<F: FnOnce() -> TrieData>(self, default: F) -> &'a mut TrieData {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Call `f` on the key's data if the key exists
+    pub fn and_modify<F: FnOnce(&mut TrieData)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+/// An existing key in a [Trie], see [Entry]
+pub struct OccupiedEntry<'a, TrieData: Default> {
+    trie: &'a mut Trie<TrieData>,
+    tail: TrieIndex,
+}
+
+impl<'a, TrieData: Default> OccupiedEntry<'a, TrieData> {
+    pub fn get(&self) -> &TrieData {
+        // unwrap as an assertion since the tail came from a successful walk
+        self.trie.ro.tail.get_data(self.tail).unwrap()
+    }
+
+    /// Get a mutable reference to the data. The trie is marked as dirty.
+    pub fn get_mut(&mut self) -> &mut TrieData {
+        self.trie.is_dirty = true;
+        self.trie.ro.tail.get_data_mut(self.tail).unwrap()
+    }
+
+    /// Convert into a mutable reference to the data that lives as long as
+    /// the trie borrow. The trie is marked as dirty.
+    pub fn into_mut(self) -> &'a mut TrieData {
+        self.trie.is_dirty = true;
+        self.trie.ro.tail.get_data_mut(self.tail).unwrap()
+    }
+}
+
+/// A missing key in a [Trie], see [Entry]
+pub struct VacantEntry<'a, TrieData: Default> {
+    trie: &'a mut Trie<TrieData>,
+    slot: VacantSlot,
+}
+
+impl<'a, TrieData: Default> VacantEntry<'a, TrieData> {
+    /// Insert the key with `data`, without walking the key again.
+    ///
+    /// # Panics
+    /// Panics if the trie has no room left for the key, see
+    /// [try_insert](Self::try_insert).
+    pub fn insert(self, data: TrieData) -> &'a mut TrieData {
+        self.try_insert(data)
+            .unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as [insert](Self::insert), but return the error if the trie has
+    /// no room left for the key. The key is not inserted on error.
+    pub fn try_insert(self, data: TrieData) -> Result<&'a mut TrieData, StoreError> {
+        let tail = self.trie.insert_at(self.slot, data)?;
+        // unwrap as an assertion since the tail was just allocated
+        Ok(self.trie.ro.tail.get_data_mut(tail).unwrap())
+    }
+}
+
+/// Saved position of a [TrieIterator], see [TrieIterator::cursor]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrieCursor {
+    root: CursorState,
+    state: Option<CursorState>,
+    key: Vec<TrieChar>,
+}
+
+#[cfg(feature = "std")]
+const TRIE_CURSOR_VERSION: u8 = 1;
+
+impl TrieCursor {
+    /// Check that the saved key walks from `root` to `state`
+    fn leads_to<TrieData: Default>(
+        &self,
+        root: &TrieState<TrieData>,
+        state: &TrieState<TrieData>,
+    ) -> bool {
+        if root.is_suffix {
+            // tail states have only one entry, which is the root itself
+            return self.key.is_empty() && CursorState::from(root) == CursorState::from(state);
+        }
+        if state.is_suffix {
+            return false;
+        }
+
+        let da = &root.trie.da;
+        let mut s = root.index;
+        for tc in self.key.iter().copied() {
+            let Some(next) = da.walk(s, tc) else {
+                return false;
+            };
+            s = next;
+        }
+        s == state.index && da.is_separate(s)
+    }
+}
+
+#[cfg(feature = "std")]
+impl TrieCursor {
+    pub fn serialize<T: Write>(&self, writer: &mut T) -> io::Result<()> {
+        writer.write_u8(TRIE_CURSOR_VERSION)?;
+        self.root.serialize(writer)?;
+        match &self.state {
+            Some(state) => {
+                writer.write_u8(1)?;
+                state.serialize(writer)?;
+            }
+            None => writer.write_u8(0)?,
+        }
+        writer.write_u32::<BigEndian>(self.key.len() as u32)?;
+        writer.write_all(&self.key)
+    }
+
+    pub fn from_reader<T: Read>(reader: &mut T) -> io::Result<Self> {
+        if reader.read_u8()? != TRIE_CURSOR_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unsupported cursor version",
+            ));
+        }
+        let root = CursorState::read(reader)?;
+        let state = match reader.read_u8()? {
+            0 => None,
+            1 => Some(CursorState::read(reader)?),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid state byte",
+                ))
+            }
+        };
+
+        let key_len = reader.read_u32::<BigEndian>()?;
+        let mut key = Vec::new();
+        reader.take(key_len as u64).read_to_end(&mut key)?;
+        if key.len() != key_len as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "cursor key is truncated",
+            ));
+        }
+
+        Ok(Self { root, state, key })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct CursorState {
+    index: TrieIndex,
+    suffix_idx: i16,
+    is_suffix: bool,
+}
+
+impl CursorState {
+    fn to_state<TrieData: Default>(
+        self,
+        trie: &ROTrie<TrieData>,
+    ) -> Option<TrieState<'_, TrieData>> {
+        let valid = if self.is_suffix {
+            self.suffix_idx >= 0
+                && trie
+                    .tail
+                    .get_suffix(self.index)
+                    .is_some_and(|suffix| (self.suffix_idx as usize) < suffix.len())
+        } else {
+            trie.da.get_base(self.index).is_some()
+        };
+        valid.then(|| TrieState::new(trie, self.index, self.suffix_idx, self.is_suffix))
+    }
+
+    #[cfg(feature = "std")]
+    fn serialize<T: Write>(&self, writer: &mut T) -> io::Result<()> {
+        writer.write_i32::<BigEndian>(self.index)?;
+        writer.write_i16::<BigEndian>(self.suffix_idx)?;
+        writer.write_u8(self.is_suffix as u8)
+    }
+
+    #[cfg(feature = "std")]
+    fn read<T: Read>(reader: &mut T) -> io::Result<Self> {
+        Ok(Self {
+            index: reader.read_i32::<BigEndian>()?,
+            suffix_idx: reader.read_i16::<BigEndian>()?,
+            is_suffix: reader.read_u8()? != 0,
+        })
+    }
+}
+
+impl<TrieData: Default> From<&TrieState<'_, TrieData>> for CursorState {
+    fn from(state: &TrieState<TrieData>) -> Self {
+        Self {
+            index: state.index,
+            suffix_idx: state.suffix_idx,
+            is_suffix: state.is_suffix,
+        }
+    }
+}
+
+/// Lowercase `ac`, or return None if it lowercases to more than one character
+fn to_lowercase(ac: AlphaChar) -> Option<AlphaChar> {
+    let Some(c) = char::from_u32(ac) else {
+        return Some(ac);
+    };
+    let mut lower = c.to_lowercase();
+    match lower.len() {
+        1 => lower.next().map(|c| c as AlphaChar),
+        _ => None,
+    }
+}
+
+/// Map the characters of `key` to [AlphaChar], followed by the terminator
+fn str_to_alpha_chars(key: &str) -> impl Iterator<Item = AlphaChar> + '_ {
+    key.chars().map(|c| c as AlphaChar).chain(iter::once(0))
+}
+
+/// Signature of a trie written by [ROTrie::serialize_compressed]
+#[cfg(feature = "flate2")]
+const COMPRESSED_SIGNATURE: u32 = 0xdaf1daf1;
+
+/// Check that `key` has no 0 before its end. The key ends at its first
+/// terminator, so anything after it would be silently dropped.
+fn check_terminator(key: &[AlphaChar]) -> Result<(), StoreError> {
+    match key.iter().position(|ch| *ch == 0) {
+        Some(pos) if pos + 1 != key.len() => Err(StoreError::InteriorTerminator(pos)),
+        _ => Ok(()),
+    }
+}
+
+/// Cut `key` at its terminator, if any
+pub(crate) fn strip_terminator(key: &[AlphaChar]) -> &[AlphaChar] {
+    match key.iter().position(|ch| *ch == 0) {
+        Some(end) => &key[..end],
+        None => key,
+    }
 }
 
 #[cfg(feature = "cffi")]
@@ -742,7 +3056,7 @@ mod cffi {
         let trie = unsafe { &*trie };
         let key_slice = alpha_char_as_slice(key);
 
-        match trie.retrieve(key_slice).copied() {
+        match trie.get(key_slice) {
             Some(v) => {
                 if !o_data.is_null() {
                     unsafe {
@@ -765,7 +3079,7 @@ mod cffi {
         let trie = unsafe { trie.as_mut() };
         let key_slice = alpha_char_as_slice(key);
 
-        trie.store_conditionally(key_slice, Some(data), true).into()
+        trie.store(key_slice, Some(data)).into()
     }
 
     #[deprecated(note = "Use trie.store_if_absent()")]
@@ -778,8 +3092,7 @@ mod cffi {
         let trie = unsafe { trie.as_mut() };
         let key_slice = alpha_char_as_slice(key);
 
-        trie.store_conditionally(key_slice, Some(data), false)
-            .into()
+        trie.store_if_absent(key_slice, Some(data)).into()
     }
 
     #[no_mangle]
@@ -799,19 +3112,10 @@ mod cffi {
     ) -> Bool {
         let trie = unsafe { &*trie };
 
-        let mut cont = true;
-        for (key, data) in trie.iter() {
-            cont = unsafe {
-                enum_func(
-                    key.as_ptr(),
-                    data.copied().flatten().unwrap_or(TRIE_DATA_ERROR),
-                    user_data,
-                )
-                .into()
-            };
-        }
-
-        cont.into()
+        trie.for_each(|key, data| unsafe {
+            enum_func(key.as_ptr(), data.unwrap_or(TRIE_DATA_ERROR), user_data).into()
+        })
+        .into()
     }
 
     #[deprecated(note = "Use trie.root()")]
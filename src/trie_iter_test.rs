@@ -1,5 +1,9 @@
-use crate::testutils::{assert_dict_complete, en_trie_new, DICT};
-use crate::types::{AlphaChar, AsAlphaChar};
+use std::io::Cursor;
+
+use crate::alpha_map::{AlphaMap, ToAlphaChars};
+use crate::testutils::{assert_dict_complete, en_alpha_map_new, en_trie_new, DICT};
+use crate::trie::{Trie, TrieCursor, TrieIterator};
+use crate::types::{AlphaChar, AlphaCharToString, AsAlphaChar, TrieChar};
 
 // Ported from test_iterator.c
 #[test]
@@ -313,3 +317,686 @@ fn test_walk() {
     println!("Try getting data from (28)");
     assert_eq!(s.get_data(), Some(&1), "Mismatched data from (28)");
 }
+
+#[test]
+fn test_walkable_chars_iter() {
+    let mut trie = en_trie_new();
+    for word in ["ab", "abc", "abd", "b"] {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+
+    let mut s = trie.root();
+    let chars: Vec<_> = s.walkable_chars_iter().collect();
+    assert_eq!(chars, vec!['a' as AlphaChar, 'b' as AlphaChar]);
+
+    println!("Terminal state with children includes the terminator");
+    assert_eq!(s.walk_str(&['a' as AlphaChar, 'b' as AlphaChar]), 2);
+    let chars: Vec<_> = s.walkable_chars_iter().collect();
+    assert_eq!(chars, vec![0, 'c' as AlphaChar, 'd' as AlphaChar]);
+    assert_eq!(chars, s.walkable_chars());
+
+    println!("Single state yields one character");
+    let mut s = trie.root();
+    assert!(s.walk('b' as AlphaChar));
+    assert!(s.is_single());
+    assert_eq!(s.walkable_chars_iter().collect::<Vec<_>>(), vec![0]);
+}
+
+#[test]
+fn test_walkable_symbols() {
+    let mut trie = en_trie_new();
+    for word in ["ab", "abc", "abd", "b"] {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+
+    println!("Symbols map back to the walkable characters");
+    let mut s = trie.root();
+    assert_eq!(s.walk_str(&['a' as AlphaChar, 'b' as AlphaChar]), 2);
+    let symbols: Vec<TrieChar> = s.walkable_symbols().collect();
+    assert_eq!(symbols, vec![0, 3, 4]);
+    assert_eq!(s.walkable_symbols().len(), 3);
+    let mapped: Vec<AlphaChar> = symbols[1..]
+        .iter()
+        .copied()
+        .map_to_alpha_char(&en_alpha_map_new())
+        .collect();
+    assert_eq!(mapped, vec!['c' as AlphaChar, 'd' as AlphaChar]);
+
+    println!("Single state yields one symbol");
+    let mut s = trie.root();
+    assert!(s.walk('b' as AlphaChar));
+    assert_eq!(s.walkable_symbols().collect::<Vec<_>>(), vec![0]);
+}
+
+#[test]
+fn test_map_data() {
+    let mut trie = en_trie_new();
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+
+    let mapped: Vec<(Vec<AlphaChar>, i64)> = trie.map_data(|v| *v as i64 * 2).collect();
+    assert_eq!(mapped.len(), DICT.len());
+    for (key, value) in mapped {
+        assert_eq!(Some(value), trie.retrieve(&key).map(|v| *v as i64 * 2));
+    }
+}
+
+#[test]
+fn test_iterator_cursor() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let all_keys: Vec<Vec<AlphaChar>> = trie.iter().map(|(key, _)| key).collect();
+
+    for split in [0, 1, 10, DICT.len()] {
+        let mut iter = trie.iter();
+        for _ in 0..split {
+            iter.next().unwrap();
+        }
+
+        let mut buf = Vec::new();
+        iter.cursor().serialize(&mut buf).unwrap();
+        let cursor = TrieCursor::from_reader(&mut Cursor::new(&buf)).unwrap();
+        assert_eq!(cursor, iter.cursor());
+
+        let resumed: Vec<Vec<AlphaChar>> = trie
+            .iter_from_cursor(&cursor)
+            .expect("Cursor should be valid")
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(resumed, all_keys[split..]);
+    }
+
+    println!("Rejecting a cursor from another trie");
+    let mut iter = trie.iter();
+    iter.nth(20).unwrap();
+    let cursor = iter.cursor();
+    let mut other = en_trie_new();
+    assert!(other.store(&"a".as_alphachar(), 1));
+    assert!(other.iter_from_cursor(&cursor).is_none());
+}
+
+#[test]
+fn test_keys_with_data() {
+    let mut trie = en_trie_new();
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), (i % 3) as i32));
+    }
+
+    let mut keys: Vec<Vec<AlphaChar>> = trie.keys_with_data(|d| d == &1).collect();
+    keys.sort();
+    let mut expected: Vec<Vec<AlphaChar>> = DICT
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| i % 3 == 1)
+        .map(|(_, word)| word.as_alphachar())
+        .collect();
+    expected.sort();
+    assert!(expected.len() > 1);
+    assert_eq!(keys, expected);
+
+    assert_eq!(trie.keys_with_data(|d| d == &3).count(), 0);
+}
+
+#[test]
+fn test_for_each() {
+    let mut trie = en_trie_new();
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+
+    println!("Visiting all entries");
+    let mut visited = Vec::new();
+    assert!(trie.for_each(|key, data| {
+        visited.push((key.to_vec(), *data));
+        true
+    }));
+    let expected: Vec<(Vec<AlphaChar>, i32)> = trie
+        .iter()
+        .map(|(key, data)| (key, *data.unwrap()))
+        .collect();
+    assert_eq!(visited, expected);
+
+    println!("Stopping early");
+    let mut count = 0;
+    assert!(!trie.for_each(|_, _| {
+        count += 1;
+        count < 3
+    }));
+    assert_eq!(count, 3);
+}
+
+#[test]
+fn test_iter_mut() {
+    let mut trie = en_trie_new();
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+    assert!(trie.store(&"".as_alphachar(), -1));
+    trie.serialize(&mut Vec::new()).unwrap();
+    assert!(!trie.is_dirty());
+
+    println!("Updating all data in place");
+    let keys: Vec<Vec<AlphaChar>> = trie.iter().map(|(key, _)| key).collect();
+    let mut visited = Vec::new();
+    for (key, data) in trie.iter_mut() {
+        *data *= 10;
+        visited.push(key);
+    }
+    assert_eq!(visited, keys);
+    assert!(trie.is_dirty());
+
+    println!("Checking the updated data");
+    for (i, word) in DICT.iter().enumerate() {
+        assert_eq!(
+            trie.retrieve(&word.as_alphachar()),
+            Some(&(i as i32 * 10)),
+            "{}",
+            word
+        );
+    }
+    assert_eq!(trie.retrieve(&"".as_alphachar()), Some(&-10));
+    assert_eq!(trie.validate(), Ok(()));
+
+    println!("Iterating an empty trie");
+    let mut trie = en_trie_new();
+    assert_eq!(trie.iter_mut().count(), 0);
+}
+
+#[test]
+fn test_collect_keys() {
+    let mut trie = en_trie_new();
+    println!("Collecting keys of an empty trie");
+    assert_eq!(trie.collect_keys(), (vec![], vec![]));
+
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+
+    println!("Collecting keys of a full trie");
+    let (keys, offsets) = trie.collect_keys();
+    let expected: Vec<Vec<AlphaChar>> = trie.iter().map(|(key, _)| key).collect();
+    assert_eq!(offsets.len(), expected.len());
+    assert_eq!(
+        keys.len(),
+        expected.iter().map(|key| key.len()).sum::<usize>()
+    );
+    let ends = offsets.iter().skip(1).copied().chain([keys.len()]);
+    for ((start, end), key) in offsets.iter().zip(ends).zip(&expected) {
+        assert_eq!(&keys[*start..end], &key[..]);
+    }
+}
+
+#[test]
+fn test_iter_prefix() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let expected_with = |prefix: &str| -> Vec<Vec<AlphaChar>> {
+        let mut keys: Vec<Vec<AlphaChar>> = DICT
+            .iter()
+            .filter(|word| word.starts_with(prefix))
+            .map(|word| word.as_alphachar())
+            .collect();
+        keys.sort();
+        keys
+    };
+
+    for prefix in ["", "a", "ab", "ne", "netw", "q", "zeb", "zebra"] {
+        println!("Iterating prefix {}", prefix);
+        let keys: Vec<Vec<AlphaChar>> = trie
+            .iter_prefix(&prefix.as_alphachar())
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, expected_with(prefix), "Mismatch on {}", prefix);
+    }
+
+    println!("Iterating missing prefixes");
+    for prefix in ["abc", "zebras", "x"] {
+        assert_eq!(
+            trie.iter_prefix(&prefix.as_alphachar()).count(),
+            0,
+            "Mismatch on {}",
+            prefix
+        );
+    }
+}
+
+#[test]
+fn test_trie_key() {
+    let mut trie = en_trie_new();
+    for word in DICT.iter().chain(["", "ab"].iter()) {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    // the alphabet maps 'a'..='z' to trie characters 1..=26
+    let to_alpha = |(key, suffix): (&[TrieChar], &[TrieChar])| -> Vec<AlphaChar> {
+        key.iter()
+            .chain(suffix)
+            .map(|tc| *tc as AlphaChar + 0x60)
+            .chain([0])
+            .collect()
+    };
+
+    let mut iter = trie.iter();
+    assert_eq!(iter.trie_key(), None);
+    let mut count = 0;
+    while iter.advance() {
+        assert_eq!(to_alpha(iter.trie_key().unwrap()), iter.key().unwrap());
+        count += 1;
+    }
+    assert_eq!(count, DICT.len() + 2);
+
+    println!("Getting raw keys relative to a single state");
+    let mut state = trie.root();
+    assert_eq!(state.walk_str(&"zeb".as_alphachar()[..3]), 3);
+    let mut iter = TrieIterator::new(&state);
+    assert!(iter.advance());
+    assert_eq!(to_alpha(iter.trie_key().unwrap()), "ra".as_alphachar());
+    assert!(!iter.advance());
+}
+
+#[test]
+fn test_iter_state() {
+    let mut trie = en_trie_new();
+    for word in DICT.iter().chain(["", "ab"].iter()) {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+
+    let mut iter = trie.iter();
+    assert!(iter.state().is_none());
+    while iter.advance() {
+        let key = iter.key().unwrap();
+        let state = iter.state().unwrap();
+        assert!(state.is_terminal(), "State of {:?} is not terminal", key);
+        assert_eq!(state.get_data(), iter.data());
+
+        // same as walking the key from the root
+        let mut walked = trie.root();
+        assert_eq!(walked.walk_str(&key[..key.len() - 1]), key.len() - 1);
+        assert_eq!(state.is_single(), walked.is_single(), "Key {:?}", key);
+        assert_eq!(state.walkable_chars(), walked.walkable_chars());
+    }
+
+    println!("Continuing to walk from the state of an entry");
+    let mut iter = trie.iter();
+    assert!(iter.advance());
+    assert_eq!(iter.key().unwrap(), [0]);
+    let mut state = iter.state().unwrap();
+    assert_eq!(state.walk_str(&"ab".as_alphachar()[..2]), 2);
+    assert!(state.is_terminal());
+    assert_eq!(state.get_data(), Some(&1));
+
+    println!("Getting the state relative to a single state");
+    let mut root = trie.root();
+    assert_eq!(root.walk_str(&"zeb".as_alphachar()[..3]), 3);
+    let mut iter = TrieIterator::new(&root);
+    assert!(iter.advance());
+    let state = iter.state().unwrap();
+    assert!(state.is_leaf());
+    assert_eq!(state.get_data(), Some(&1));
+}
+
+#[test]
+fn test_iter_with_prefix() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+
+    // "zeb" ends in a single state, inside the tail
+    for prefix in ["a", "ab", "ne", "netw", "zeb"] {
+        println!("Iterating from the state walked with {}", prefix);
+        let chars: Vec<AlphaChar> = prefix.chars().map(|c| c as AlphaChar).collect();
+        let mut state = trie.root();
+        assert_eq!(state.walk_str(&chars), chars.len());
+
+        let expected: Vec<_> = trie.iter_prefix(&chars).map(|(key, _)| key).collect();
+        let keys: Vec<_> = TrieIterator::new(&state)
+            .with_prefix(&chars)
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(keys, expected, "Mismatch on {}", prefix);
+
+        println!("Keys without the prefix are relative to the state");
+        let relative: Vec<_> = TrieIterator::new(&state).map(|(key, _)| key).collect();
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|key| key[chars.len()..].to_vec())
+            .collect();
+        assert_eq!(relative, expected, "Mismatch on {}", prefix);
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_par_iter() {
+    use rayon::prelude::*;
+
+    let mut trie = en_trie_new();
+    assert_eq!(trie.par_iter().count(), 0);
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+
+    let check = |trie: &Trie<i32>| {
+        let mut entries: Vec<(Vec<AlphaChar>, i32)> =
+            trie.par_iter().map(|(key, data)| (key, *data)).collect();
+        entries.sort();
+        let expected: Vec<(Vec<AlphaChar>, i32)> = trie
+            .iter()
+            .map(|(key, data)| (key, *data.unwrap()))
+            .collect();
+        assert_eq!(entries, expected);
+    };
+    check(&trie);
+
+    println!("Iterating with the empty key");
+    assert!(trie.store(&"".as_alphachar(), -1));
+    check(&trie);
+}
+
+#[test]
+fn test_iter_rev() {
+    let mut trie = en_trie_new();
+    println!("Iterating empty trie from the back");
+    assert_eq!(trie.iter().next_back(), None);
+
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+
+    for prefix in ["", "a", "ab", "ne", "netw", "zeb", "zebra", "x"] {
+        println!("Iterating prefix {} from the back", prefix);
+        let forward: Vec<_> = trie.iter_prefix(&prefix.as_alphachar()).collect();
+        let mut backward: Vec<_> = trie.iter_prefix(&prefix.as_alphachar()).rev().collect();
+        backward.reverse();
+        assert_eq!(forward, backward, "Mismatch on {}", prefix);
+    }
+
+    println!("Iterating from both ends");
+    let forward: Vec<_> = trie.iter().map(|(key, _)| key).collect();
+    for split in 0..=forward.len() {
+        let mut iter = trie.iter();
+        let mut keys: Vec<_> = iter.by_ref().take(split).map(|(key, _)| key).collect();
+        let mut tail: Vec<_> = iter.by_ref().rev().map(|(key, _)| key).collect();
+        tail.reverse();
+        keys.extend(tail);
+        assert_eq!(keys, forward, "Mismatch splitting at {}", split);
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+    }
+}
+
+#[test]
+fn test_iter_size_hint() {
+    let mut trie = en_trie_new();
+    assert_eq!(trie.iter().size_hint(), (0, Some(0)));
+
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+
+    println!("Checking exact hints while iterating from both ends");
+    let mut iter = trie.iter();
+    for left in (0..=DICT.len()).rev() {
+        assert_eq!(
+            iter.size_hint(),
+            (left, Some(left)),
+            "{} entries left",
+            left
+        );
+        if left % 2 == 0 {
+            iter.next();
+        } else {
+            iter.next_back();
+        }
+    }
+    assert_eq!(iter.next(), None);
+    assert_eq!(iter.size_hint(), (0, Some(0)));
+
+    for prefix in ["a", "ne", "zebra", "x"] {
+        println!("Checking bounds of prefix {}", prefix);
+        let iter = trie.iter_prefix(&prefix.as_alphachar());
+        let (lower, upper) = iter.size_hint();
+        let count = iter.count();
+        assert!(lower <= count, "Lower bound {} above {}", lower, count);
+        assert!(
+            upper.unwrap() >= count,
+            "Upper bound {:?} below {}",
+            upper,
+            count
+        );
+    }
+}
+
+#[test]
+fn test_range() {
+    let mut trie = en_trie_new();
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+    let mut sorted: Vec<&str> = DICT.to_vec();
+    sorted.sort();
+
+    let bounds = [
+        "",
+        "a",
+        "ab",
+        "abacus",
+        "abandon",
+        "abandoned",
+        "abc",
+        "a6",
+        "b",
+        "bet",
+        "be{",
+        "n",
+        "netw",
+        "q",
+        "z",
+        "zebra",
+        "zz",
+        "{",
+    ];
+    for lo in bounds {
+        for hi in bounds {
+            let keys: Vec<Vec<AlphaChar>> = trie
+                .range(&lo.as_alphachar(), &hi.as_alphachar())
+                .map(|(key, _)| key)
+                .collect();
+            let expected: Vec<Vec<AlphaChar>> = sorted
+                .iter()
+                .filter(|word| lo <= **word && **word < hi)
+                .map(|word| word.as_alphachar())
+                .collect();
+            assert_eq!(keys, expected, "Mismatch on {}..{}", lo, hi);
+        }
+    }
+
+    println!("Checking ranges with a collation");
+    let order: Vec<AlphaChar> = ('a'..='z').rev().map(AlphaChar::from).collect();
+    let mut collated = Trie::new(AlphaMap::with_collation(&order).unwrap());
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(collated.store(&word.as_alphachar(), i as i32));
+    }
+    for lo in bounds {
+        for hi in bounds {
+            let keys: Vec<Vec<AlphaChar>> = collated
+                .range(&lo.as_alphachar(), &hi.as_alphachar())
+                .map(|(key, _)| key)
+                .collect();
+            // in the order of the trie, but compared by the character values
+            let expected: Vec<Vec<AlphaChar>> = collated
+                .iter()
+                .map(|(key, _)| key)
+                .filter(|key| {
+                    let key = key.as_slice().ac_to_string().unwrap();
+                    lo <= key.as_str() && key.as_str() < hi
+                })
+                .collect();
+            assert_eq!(keys, expected, "Mismatch on collated {}..{}", lo, hi);
+        }
+    }
+    let mut collated = Trie::new(AlphaMap::with_collation(&"zyx".as_alphachar()).unwrap());
+    for word in ["xy", "yz", "zx"] {
+        assert!(collated.store(&word.as_alphachar(), 1));
+    }
+    let keys: Vec<String> = collated
+        .range(&"y".as_alphachar(), &"zz".as_alphachar())
+        .map(|(key, _)| key.as_slice().ac_to_string().unwrap())
+        .collect();
+    assert_eq!(keys, ["zx", "yz"]);
+
+    println!("Checking data in range");
+    let (key, data) = trie
+        .range(&"bet".as_alphachar(), &"bf".as_alphachar())
+        .next()
+        .unwrap();
+    assert_eq!(key, "bet".as_alphachar());
+    let idx = DICT.iter().position(|word| *word == "bet").unwrap();
+    assert_eq!(data, Some(&(idx as i32)));
+}
+
+#[test]
+fn test_count_prefix() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+
+    for prefix in ["", "a", "ab", "ne", "netw", "q", "zeb", "zebra"] {
+        println!("Counting prefix {}", prefix);
+        let expected = DICT.iter().filter(|word| word.starts_with(prefix)).count();
+        assert_eq!(
+            trie.count_prefix(&prefix.as_alphachar()),
+            expected,
+            "Mismatch on {}",
+            prefix
+        );
+    }
+    assert_eq!(trie.count_prefix(&[]), DICT.len());
+
+    println!("Counting missing prefixes");
+    for prefix in ["abc", "zebras", "x"] {
+        assert_eq!(
+            trie.count_prefix(&prefix.as_alphachar()),
+            0,
+            "Mismatch on {}",
+            prefix
+        );
+    }
+}
+
+#[test]
+fn test_iter_sorted() {
+    println!("Preparing alpha map with ranges out of order");
+    let mut alpha_map = AlphaMap::default();
+    alpha_map.add_range(0x0e01..=0x0e2e).unwrap();
+    alpha_map.add_range(0x0061..=0x007a).unwrap();
+    alpha_map.add_range(0x0030..=0x0039).unwrap();
+    let mut trie: Trie<i32> = Trie::new(alpha_map);
+
+    let words = [
+        "zebra",
+        "\u{0e01}\u{0e02}",
+        "abc",
+        "ab",
+        "\u{0e01}",
+        "a1",
+        "9lives",
+        "b",
+    ];
+    for word in words {
+        assert!(
+            trie.store(&word.as_alphachar(), 1),
+            "Failed to store {}",
+            word
+        );
+    }
+
+    let keys: Vec<Vec<AlphaChar>> = trie.iter().map(|(key, _)| key).collect();
+    let mut expected: Vec<Vec<AlphaChar>> = words.iter().map(|word| word.as_alphachar()).collect();
+    expected.sort();
+    assert_eq!(keys, expected);
+}
+
+#[test]
+fn test_walk_str() {
+    let mut trie = en_trie_new();
+    for word in ["pool", "prize", "preview", "prepare"] {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let chars = |word: &str| -> Vec<AlphaChar> { word.chars().map(|c| c as AlphaChar).collect() };
+
+    println!("Walking a whole key");
+    let mut s = trie.root();
+    assert_eq!(s.walk_str(&chars("prize")), 5);
+    assert!(s.is_terminal());
+
+    println!("Walking into a dead end");
+    let mut s = trie.root();
+    assert_eq!(s.walk_str(&chars("prepay")), 5);
+    assert!(s.is_walkable('r' as AlphaChar));
+    assert!(!s.is_terminal());
+
+    println!("Walking past the end of a key in the tail");
+    let mut s = trie.root();
+    assert_eq!(s.walk_str(&chars("pools")), 4);
+    assert!(s.is_leaf());
+
+    let mut s = trie.root();
+    assert_eq!(s.walk_str(&[]), 0);
+    assert_eq!(s.walk_str(&chars("x")), 0);
+}
+
+#[test]
+fn test_walk_tracked() {
+    let mut trie = en_trie_new();
+    for word in ["pool", "prize"] {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+
+    println!("Walking through branches and into the tail");
+    let mut s = trie.root();
+    let mut walked = trie.root();
+    for c in "pool".chars() {
+        // en alphabet maps 'a' to 1
+        let tc = c as TrieChar - b'a' + 1;
+        assert_eq!(s.walk_tracked(c as AlphaChar), Some(tc));
+        assert!(walked.walk(c as AlphaChar));
+        assert!(s == walked);
+    }
+    assert_eq!(s.walk_tracked(0), Some(0));
+    assert!(s.is_terminal());
+
+    println!("Failing walks leave the state as is");
+    let mut s = trie.root();
+    assert_eq!(s.walk_tracked('p' as AlphaChar), Some(16));
+    let before = s.clone();
+    assert_eq!(s.walk_tracked('x' as AlphaChar), None);
+    assert_eq!(s.walk_tracked('A' as AlphaChar), None);
+    assert!(s == before);
+}
+
+#[test]
+fn test_state_suffix() {
+    let mut trie = en_trie_new();
+    for word in ["pool", "prize", "preview"] {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let chars = |word: &str| -> Vec<AlphaChar> { word.chars().map(|c| c as AlphaChar).collect() };
+
+    let mut s = trie.root();
+    assert_eq!(s.walk_str(&chars("pr")), 2);
+    assert_eq!(s.suffix(), None, "Branch state should have no suffix");
+
+    assert!(s.walk('i' as AlphaChar));
+    assert!(s.is_single());
+    assert_eq!(s.suffix(), Some(chars("ze")));
+
+    assert_eq!(s.walk_str(&chars("ze")), 2);
+    assert_eq!(s.suffix(), Some(vec![]));
+}
@@ -1,10 +1,15 @@
-use std::collections::{BTreeSet, HashMap};
-use std::io::Cursor;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io;
+use std::io::{Cursor, Read, Write};
+use std::iter;
 
-use crate::alpha_map::AlphaMap;
+use crate::alpha_map::{AlphaMap, AlphaMapError, FORMAT_VERSION};
+use crate::builder::{BuildError, TrieBuilder};
 use crate::testutils::*;
-use crate::trie::Trie;
-use crate::types::{AlphaChar, AsAlphaChar};
+use crate::trie::{CorruptError, Entry, StoreError, Trie};
+use crate::types::{
+    AlphaChar, AlphaCharToString, AsAlphaChar, TrieDeserializable, TrieSerializable, TRIE_INDEX_MAX,
+};
 use crate::types_c::CTrieData;
 
 // Ported from test_null_trie.c
@@ -20,7 +25,8 @@ fn test_null_trie() {
 fn test_byte_alpha() {
     println!("Preparing alpha map");
     let mut alpha_map = AlphaMap::default();
-    alpha_map.add_range(0x00..=0xff);
+    // 0x00..=0xff in libdatrie, where 0xff aliases the terminator
+    alpha_map.add_range(0x01..=0xff).unwrap();
 
     println!("Preparing trie");
     let mut trie = Trie::new(alpha_map);
@@ -34,6 +40,34 @@ fn test_byte_alpha() {
         .retrieve(&key)
         .expect("Failed to retrieve key from test trie");
     assert_eq!(*data, 1, "Incorrect TrieData received");
+
+    println!("Checking that 0xff does not alias the terminator");
+    assert!(trie.store(&[0xff, 0], 7));
+    assert_eq!(trie.retrieve(&[0xff, 0]), Some(&7));
+    assert_eq!(trie.retrieve(&[0]), None);
+    let keys: Vec<Vec<AlphaChar>> = trie.iter().map(|(key, _)| key).collect();
+    assert_eq!(keys, [vec![0xff, 0], vec![0xff, 0xff, 0]]);
+}
+
+#[test]
+fn test_byte_alpha_file() {
+    // written by the version before the alphabet limit, with an alphabet of
+    // 0x00..=0x7f and 0x81..=0xfe, where 0 takes a trie character of its own
+    // as in libdatrie
+    let file = include_bytes!("testdata/byte_alpha.tri");
+    let trie = Trie::<i32>::from_reader(&mut Cursor::new(file)).unwrap();
+    let keys: [&[AlphaChar]; 4] = [
+        &[0x01, 0x02, 0],
+        &[0x61, 0x62, 0],
+        &[0x61, 0xfe, 0x20, 0],
+        &[0xfe, 0],
+    ];
+    for (i, key) in keys.iter().enumerate() {
+        assert_eq!(trie.retrieve(key), Some(&(i as i32 + 1)), "{:?}", key);
+    }
+    assert_eq!(trie.retrieve(&[0x02, 0x03, 0]), None);
+    let stored: Vec<Vec<AlphaChar>> = trie.iter().map(|(key, _)| key).collect();
+    assert_eq!(stored, keys);
 }
 
 // Ported from test_byte_list.c
@@ -41,7 +75,8 @@ fn test_byte_alpha() {
 fn test_byte_list() {
     println!("Preparing alpha map");
     let mut alpha_map = AlphaMap::default();
-    alpha_map.add_range(0x00..=0xff);
+    // 0x00..=0xff in libdatrie, see test_byte_alpha
+    alpha_map.add_range(0x01..=0xff).unwrap();
 
     println!("Preparing trie");
     let mut trie = Trie::new(alpha_map);
@@ -101,6 +136,403 @@ fn test_serialize() {
     );
 }
 
+#[test]
+fn test_serialize_i64() {
+    println!("Preparing trie with 64-bit data");
+    let mut trie: Trie<i64> = Trie::new(en_alpha_map_new());
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), (i as i64) << 40 | -1 << 63));
+    }
+
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+    assert_eq!(buf.len(), trie.serialized_size());
+
+    println!("Reloading trie from serialized data");
+    let trie: Trie<i64> = Trie::from_reader(&mut Cursor::new(&buf))
+        .expect("Failed to reload trie from serialized state");
+    for (i, word) in DICT.iter().enumerate() {
+        assert_eq!(
+            trie.retrieve(&word.as_alphachar()),
+            Some(&((i as i64) << 40 | -1 << 63)),
+            "Wrong data for {}",
+            word
+        );
+    }
+
+    println!("Refusing to load the data as another type");
+    let err = Trie::<i32>::from_reader(&mut Cursor::new(&buf))
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let err = Trie::<u64>::from_reader(&mut Cursor::new(&buf))
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let mut buf = Vec::new();
+    en_trie_new().serialize(&mut buf).unwrap();
+    let err = Trie::<i64>::from_reader(&mut Cursor::new(&buf))
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_untagged_data_files() {
+    // written before the data tag was recorded, with keys "ab", "acd" and "z"
+    let keys = ["ab", "acd", "z"].map(|key| key.as_alphachar());
+
+    println!("Loading untagged Vec<u8> data");
+    let file = include_bytes!("testdata/vec_u8.tri");
+    let mut trie = Trie::<Vec<u8>>::from_reader(&mut Cursor::new(file)).unwrap();
+    let data: Vec<Option<&Vec<u8>>> = keys.iter().map(|key| trie.retrieve(key)).collect();
+    assert_eq!(
+        data,
+        [Some(&vec![1, 2, 3]), Some(&vec![]), Some(&vec![0xff; 5])]
+    );
+    println!("Tagging the data once saved again");
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).unwrap();
+    assert_ne!(&buf[..], &file[..]);
+    let reloaded = Trie::<Vec<u8>>::from_reader(&mut Cursor::new(&buf)).unwrap();
+    assert!(reloaded.iter().eq(trie.iter()));
+    assert!(Trie::<i32>::from_reader(&mut Cursor::new(&buf)).is_err());
+
+    println!("Loading untagged Option<i32> data");
+    let file = include_bytes!("testdata/option_i32.tri");
+    let trie = Trie::<Option<i32>>::from_reader(&mut Cursor::new(file)).unwrap();
+    let data: Vec<Option<&Option<i32>>> = keys.iter().map(|key| trie.retrieve(key)).collect();
+    assert_eq!(data, [Some(&Some(-5)), Some(&None), Some(&Some(7))]);
+
+    println!("Refusing untagged data for types added with the tags");
+    let err = Trie::<Option<i64>>::from_reader(&mut Cursor::new(file))
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    let err = Trie::<u32>::from_reader(&mut Cursor::new(file))
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[derive(Default, Debug, PartialEq)]
+struct Record {
+    id: u16,
+    tags: Vec<u8>,
+}
+
+impl TrieSerializable for Record {
+    const DATA_TAG: u32 = 0x1000;
+
+    fn serialize<T: Write>(&self, writer: &mut T) -> io::Result<()> {
+        self.id.serialize(writer)?;
+        self.tags.serialize(writer)
+    }
+}
+
+impl TrieDeserializable for Record {
+    const DATA_TAG: u32 = 0x1000;
+
+    fn deserialize<T: Read>(reader: &mut T) -> io::Result<Self> {
+        Ok(Record {
+            id: u16::deserialize(reader)?,
+            tags: Vec::deserialize(reader)?,
+        })
+    }
+}
+
+#[test]
+fn test_serialize_struct() {
+    println!("Preparing trie with struct data");
+    let mut trie: Trie<Option<Record>> = Trie::new(en_alpha_map_new());
+    for (i, word) in DICT.iter().enumerate() {
+        let entry = Record {
+            id: i as u16,
+            tags: word.bytes().take(i).collect(),
+        };
+        assert!(trie.store(&word.as_alphachar(), Some(entry)));
+    }
+
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+    assert_eq!(buf.len(), trie.serialized_size());
+
+    println!("Reloading trie from serialized data");
+    let trie: Trie<Option<Record>> = Trie::from_reader(&mut Cursor::new(&buf))
+        .expect("Failed to reload trie from serialized state");
+    assert_eq!(trie.len(), DICT.len());
+    for (i, word) in DICT.iter().enumerate() {
+        let entry = trie.retrieve(&word.as_alphachar()).unwrap().as_ref();
+        assert_eq!(entry.map(|e| e.id), Some(i as u16), "Wrong id for {}", word);
+        assert_eq!(
+            entry.map(|e| e.tags.as_slice()),
+            Some(&word.as_bytes()[..i.min(word.len())])
+        );
+    }
+    let err = Trie::<Record>::from_reader(&mut Cursor::new(&buf))
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_serialized_size() {
+    println!("Checking size with variable-width data");
+    let mut trie: Trie<Vec<u8>> = Trie::new(en_alpha_map_new());
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), vec![0xab; i * 100]));
+    }
+    assert!(trie.delete(&"abacus".as_alphachar()));
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+    assert_eq!(buf.len(), trie.serialized_size());
+
+    println!("Checking size with C data");
+    let mut trie: Trie<Option<CTrieData>> = Trie::new(en_alpha_map_new());
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), Some(CTrieData(i as i32))));
+    }
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+    assert_eq!(buf.len(), trie.serialized_size());
+
+    println!("Checking size computed by the default method");
+    let record = Record {
+        id: 1,
+        tags: vec![1, 2, 3],
+    };
+    let mut buf = Vec::new();
+    record.serialize(&mut buf).unwrap();
+    assert_eq!(buf.len(), record.serialized_size());
+}
+
+#[test]
+fn test_validate() {
+    println!("Preparing trie");
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    for word in &DICT[..DICT.len() / 2] {
+        assert!(trie.delete(&word.as_alphachar()));
+    }
+    assert_eq!(trie.validate(), Ok(()));
+    assert_eq!(trie.clone().finish().unwrap().len(), trie.len());
+
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+    Trie::<i32>::from_reader_validated(&mut Cursor::new(&buf)).expect("Valid trie is rejected");
+
+    // cells of the double-array come right after the alphabet
+    let da_begin = en_alpha_map_new().serialized_size();
+    let num_cells = i32::from_be_bytes(buf[da_begin + 4..da_begin + 8].try_into().unwrap());
+    let cell_at = |buf: &[u8], s: usize| {
+        let cell = &buf[da_begin + s * 8..da_begin + s * 8 + 8];
+        (
+            i32::from_be_bytes(cell[..4].try_into().unwrap()),
+            i32::from_be_bytes(cell[4..].try_into().unwrap()),
+        )
+    };
+    let corrupt = |s: usize, base: Option<i32>, check: Option<i32>| {
+        let mut buf = buf.clone();
+        let offset = da_begin + s * 8;
+        if let Some(base) = base {
+            buf[offset..offset + 4].copy_from_slice(&base.to_be_bytes());
+        }
+        if let Some(check) = check {
+            buf[offset + 4..offset + 8].copy_from_slice(&check.to_be_bytes());
+        }
+        let trie = Trie::<i32>::from_reader(&mut Cursor::new(&buf))
+            .expect("Corrupted trie should still be read");
+        assert!(Trie::<i32>::from_reader_validated(&mut Cursor::new(&buf)).is_err());
+        assert_eq!(trie.clone().finish().err(), trie.validate().err());
+        trie.validate()
+    };
+    let cells: Vec<(usize, (i32, i32))> = (3..num_cells as usize)
+        .map(|s| (s, cell_at(&buf, s)))
+        .collect();
+
+    println!("Checking out of range parent");
+    let (used, _) = cells.iter().find(|(_, (_, check))| *check > 0).unwrap();
+    assert_eq!(
+        corrupt(*used, None, Some(num_cells + 10)),
+        Err(CorruptError::Check(*used as i32))
+    );
+
+    println!("Checking broken free list");
+    let (free, _) = cells.iter().find(|(_, (_, check))| *check < 0).unwrap();
+    assert!(matches!(
+        corrupt(*free, None, Some(-num_cells - 10)),
+        Err(CorruptError::FreeList(_))
+    ));
+
+    println!("Checking missing tail block");
+    let (sep, _) = cells
+        .iter()
+        .find(|(_, (base, check))| *base < 0 && *check > 0)
+        .unwrap();
+    assert_eq!(
+        corrupt(*sep, Some(-100_000), None),
+        Err(CorruptError::TailIndex(*sep as i32))
+    );
+}
+
+#[test]
+fn test_format_version() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let mut buf = b"before".to_vec();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+
+    println!("Probing a trie without version header");
+    let mut reader = Cursor::new(&buf);
+    reader.set_position(6);
+    assert_eq!(Trie::<i32>::format_version(&mut reader).unwrap(), 1);
+    assert_eq!(reader.position(), 6, "Probing should not consume the trie");
+    assert_dict_complete(&Trie::<i32>::from_reader(&mut reader).unwrap());
+
+    let with_header = |version: u32| {
+        let mut out = Vec::new();
+        out.extend_from_slice(&0xd9f0d9f0u32.to_be_bytes());
+        out.extend_from_slice(&version.to_be_bytes());
+        out.extend_from_slice(&buf[6..]);
+        out
+    };
+
+    println!("Reading a trie with version header");
+    let versioned = with_header(1);
+    assert_eq!(
+        Trie::<i32>::format_version(&mut Cursor::new(&versioned)).unwrap(),
+        1
+    );
+    assert_dict_complete(&Trie::<i32>::from_reader(&mut Cursor::new(&versioned)).unwrap());
+
+    println!("Refusing a trie of a newer version");
+    let newer = with_header(FORMAT_VERSION + 1);
+    assert_eq!(
+        Trie::<i32>::format_version(&mut Cursor::new(&newer)).unwrap(),
+        FORMAT_VERSION + 1
+    );
+    let err = Trie::<i32>::from_reader(&mut Cursor::new(&newer))
+        .expect_err("Newer format should be refused");
+    assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+
+    println!("Probing something else");
+    let err = Trie::<i32>::format_version(&mut Cursor::new(&buf)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_collation() {
+    // reverse of the code point order
+    let order: Vec<AlphaChar> = "แเคขก".chars().map(|c| c as AlphaChar).collect();
+    let alpha_map = AlphaMap::with_collation(&order).unwrap();
+    let mut trie = Trie::new(alpha_map.clone());
+    for word in ["เก", "กข", "แค", "ขเ", "ก"] {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let expected = ["แค", "เก", "ขเ", "ก", "กข"];
+    let keys = |trie: &Trie<i32>| -> Vec<String> {
+        trie.iter()
+            .map(|(key, _)| key.as_slice().ac_to_string().unwrap())
+            .collect()
+    };
+    assert_eq!(keys(&trie), expected);
+
+    println!("Saving the collation with the trie");
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+    assert_eq!(buf.len(), trie.serialized_size());
+    assert_eq!(
+        Trie::<i32>::format_version(&mut Cursor::new(&buf)).unwrap(),
+        2
+    );
+    let loaded = Trie::<i32>::from_reader(&mut Cursor::new(&buf)).unwrap();
+    assert_eq!(keys(&loaded), expected);
+    assert_eq!(AlphaMap::read(&mut Cursor::new(&buf)).unwrap(), alpha_map);
+    assert_ne!(alpha_map, AlphaMap::try_from("กขคเแ").unwrap());
+
+    // the last character of the collation ends the alphabet
+    let last = alpha_map.serialized_size() - 4;
+
+    println!("Adding characters after the collation");
+    let mut alpha_map = alpha_map;
+    alpha_map.add_range(0x61..=0x62).unwrap();
+    let mut trie = Trie::new(alpha_map);
+    for word in ["a", "b", "แ", "ก"] {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    assert_eq!(keys(&trie), ["แ", "ก", "a", "b"]);
+
+    println!("Refusing a collated trie with a character outside the alphabet");
+    buf[last..last + 4].copy_from_slice(&0x61u32.to_be_bytes());
+    let err = Trie::<i32>::from_reader(&mut Cursor::new(&buf))
+        .err()
+        .unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_corrupt_root_base() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+
+    // base of the root cell, which is the third cell of the double-array
+    let offset = en_alpha_map_new().serialized_size() + 2 * 8;
+    for base in [i32::MAX, i32::MAX - 1, 1 << 20] {
+        println!("Reading trie with root base {}", base);
+        let mut buf = buf.clone();
+        buf[offset..offset + 4].copy_from_slice(&base.to_be_bytes());
+        let trie = Trie::<i32>::from_reader(&mut Cursor::new(&buf))
+            .expect("Corrupted trie should still be read");
+        for word in DICT {
+            assert_eq!(trie.retrieve(&word.as_alphachar()), None);
+        }
+        assert_eq!(trie.iter().count(), 0);
+    }
+}
+
+#[test]
+fn test_corrupt_tail_index() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+
+    // point every separate node past the tail blocks, or before the first one
+    let da_begin = en_alpha_map_new().serialized_size();
+    let num_cells = i32::from_be_bytes(buf[da_begin + 4..da_begin + 8].try_into().unwrap());
+    for bad_index in [DICT.len() as i32 + 5, i32::MAX, 0] {
+        println!("Reading trie with tail index {}", bad_index);
+        let mut buf = buf.clone();
+        for s in 3..num_cells as usize {
+            let offset = da_begin + s * 8;
+            let base = i32::from_be_bytes(buf[offset..offset + 4].try_into().unwrap());
+            let check = i32::from_be_bytes(buf[offset + 4..offset + 8].try_into().unwrap());
+            if base < 0 && check > 0 {
+                buf[offset..offset + 4].copy_from_slice(&(-bad_index).to_be_bytes());
+            }
+        }
+        let trie = Trie::<i32>::from_reader(&mut Cursor::new(&buf))
+            .expect("Corrupted trie should still be read");
+        let keys: Vec<Vec<AlphaChar>> = DICT.iter().map(|word| word.as_alphachar()).collect();
+        for key in &keys {
+            assert_eq!(trie.retrieve(key), None);
+            assert!(!trie.contains_key(key));
+        }
+        let keys: Vec<&[AlphaChar]> = keys.iter().map(|key| key.as_slice()).collect();
+        assert!(trie.retrieve_many(&keys).iter().all(Option::is_none));
+    }
+}
+
 // Ported from test_nonalpha.c
 #[test]
 fn test_nonalpha() {
@@ -134,3 +566,1885 @@ fn test_nonalpha() {
 
     assert_dict_complete(&trie);
 }
+
+#[test]
+fn test_trie_char_boundary() {
+    println!("Preparing alpha map");
+    // 255 characters occupy every trie char from 1 to TRIE_CHAR_MAX,
+    // with 0xff left as a gap right before the character mapped to TRIE_CHAR_MAX
+    let mut alpha_map = AlphaMap::default();
+    alpha_map.add_range(0x01..=0xfe).unwrap();
+    alpha_map.add_range(0x100..=0x100).unwrap();
+    assert!(alpha_map.roundtrip_ok(&[0x100, 0]));
+    assert!(!alpha_map.roundtrip_ok(&[0xff, 0]));
+
+    println!("Preparing trie");
+    let mut trie = Trie::new(alpha_map);
+
+    println!("Storing keys to test trie");
+    // the node after 0x01 gets all 256 possible children including the terminator,
+    // inserted in descending order so that the last insertions force relocations
+    let chars: Vec<AlphaChar> = (0x01..=0xfe).chain(iter::once(0x100)).rev().collect();
+    let mut keys: Vec<Vec<AlphaChar>> = vec![vec![0x01, 0]];
+    keys.extend(chars.iter().map(|ac| vec![0x01, *ac, 0]));
+    keys.extend(
+        chars
+            .iter()
+            .filter(|ac| **ac != 0x01)
+            .map(|ac| vec![*ac, 0]),
+    );
+    for (i, key) in keys.iter().enumerate() {
+        assert!(trie.store(key, i as i32), "Failed to store {:?}", key);
+    }
+
+    println!("Rejecting characters in the alphabet gap");
+    assert!(!trie.store(&[0xff, 0], -1));
+    assert_eq!(trie.retrieve(&[0xff, 0]), None);
+    assert_eq!(trie.retrieve(&[0x01, 0xff, 0]), None);
+
+    println!("Serializing trie");
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+    let trie: Trie<i32> = Trie::from_reader(&mut Cursor::new(&buf))
+        .expect("Failed to reload trie from serialized state");
+
+    println!("Checking trie contents");
+    for (i, key) in keys.iter().enumerate() {
+        assert_eq!(
+            trie.retrieve(key),
+            Some(&(i as i32)),
+            "Mismatch on {:?}",
+            key
+        );
+    }
+    keys.sort();
+    let iterated: Vec<Vec<AlphaChar>> = trie.iter().map(|(key, _)| key).collect();
+    assert_eq!(iterated, keys);
+}
+
+#[test]
+fn test_delete_prefix() {
+    let new_trie = || {
+        let mut trie = en_trie_new();
+        for word in DICT.iter().chain(["", "ab"].iter()) {
+            assert!(trie.store(&word.as_alphachar(), 1));
+        }
+        trie
+    };
+
+    for prefix in [
+        "", "a", "ab", "aba", "abacus", "abacuss", "acc", "h", "ho", "ne", "net", "z", "zeb",
+        "zebra", "zebu", "q",
+    ] {
+        println!("Deleting prefix {:?}", prefix);
+        let prefix = &prefix.as_alphachar();
+
+        // baseline collecting the keys, then deleting them one by one
+        let mut expected = new_trie();
+        let keys: Vec<Vec<AlphaChar>> = expected.iter_prefix(prefix).map(|(key, _)| key).collect();
+        for key in &keys {
+            assert!(expected.delete(key));
+        }
+
+        let mut trie = new_trie();
+        assert_eq!(trie.delete_prefix(prefix), keys.len());
+        trie.validate()
+            .expect("Trie is corrupt after deleting a prefix");
+        assert_eq!(trie.iter_prefix(prefix).count(), 0);
+        assert!(trie
+            .iter()
+            .map(|(key, _)| key)
+            .eq(expected.iter().map(|(key, _)| key)));
+        assert_eq!(trie.stats(), expected.stats());
+
+        println!("Storing the deleted keys again");
+        for key in &keys {
+            assert!(trie.store(key, 1));
+        }
+        trie.validate()
+            .expect("Trie is corrupt after storing again");
+        assert_eq!(trie.len(), DICT.len() + 2);
+        assert!(trie
+            .iter()
+            .map(|(key, _)| key)
+            .eq(new_trie().iter().map(|(key, _)| key)));
+    }
+}
+
+#[test]
+fn test_rename_prefix() {
+    println!("Preparing trie");
+    let mut trie = en_trie_new();
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+    let data_of = |word: &str| DICT.iter().position(|v| *v == word).unwrap() as i32;
+
+    println!("Renaming a plain prefix");
+    assert_eq!(
+        trie.rename_prefix(&"ho".as_alphachar(), &"za".as_alphachar()),
+        Ok(2)
+    );
+    assert_eq!(trie.retrieve(&"home".as_alphachar()), None);
+    assert_eq!(
+        trie.retrieve(&"zame".as_alphachar()),
+        Some(&data_of("home"))
+    );
+    assert_eq!(
+        trie.retrieve(&"zause".as_alphachar()),
+        Some(&data_of("house"))
+    );
+    assert_eq!(trie.retrieve(&"hut".as_alphachar()), Some(&data_of("hut")));
+
+    println!("Renaming into a nested prefix");
+    assert_eq!(
+        trie.rename_prefix(
+            &['n', 'e', 't'].map(|v| v as AlphaChar),
+            &"netw".as_alphachar()
+        ),
+        Ok(2)
+    );
+    assert_eq!(trie.retrieve(&"net".as_alphachar()), None);
+    assert_eq!(trie.retrieve(&"netw".as_alphachar()), Some(&data_of("net")));
+    assert_eq!(
+        trie.retrieve(&"netwwork".as_alphachar()),
+        Some(&data_of("network"))
+    );
+    assert_eq!(trie.retrieve(&"network".as_alphachar()), None);
+
+    println!("Renaming onto existing keys");
+    assert_eq!(
+        trie.rename_prefix(&"ba".as_alphachar(), &"be".as_alphachar()),
+        Ok(1)
+    );
+    assert_eq!(trie.retrieve(&"bat".as_alphachar()), None);
+    assert_eq!(trie.retrieve(&"bet".as_alphachar()), Some(&data_of("bat")));
+    assert_eq!(
+        trie.retrieve(&"best".as_alphachar()),
+        Some(&data_of("best"))
+    );
+
+    println!("Renaming missing or invalid prefixes");
+    assert_eq!(
+        trie.rename_prefix(&"xyz".as_alphachar(), &"a".as_alphachar()),
+        Ok(0)
+    );
+    assert_eq!(
+        trie.rename_prefix(&"a".as_alphachar(), &"A".as_alphachar()),
+        Err(StoreError::CharNotInAlphaMap('A' as AlphaChar))
+    );
+    assert_eq!(
+        trie.retrieve(&"abacus".as_alphachar()),
+        Some(&data_of("abacus"))
+    );
+    assert_eq!(trie.iter().count(), DICT.len() - 1);
+
+    println!("Renaming onto keys being moved");
+    let mut trie = en_trie_new();
+    for (i, word) in ["a", "aa", "aab"].iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+    assert_eq!(
+        trie.rename_prefix(&"a".as_alphachar(), &"aa".as_alphachar()),
+        Ok(3)
+    );
+    let entries: Vec<(Vec<AlphaChar>, i32)> = trie
+        .iter()
+        .map(|(key, data)| (key, *data.unwrap()))
+        .collect();
+    assert_eq!(
+        entries,
+        [
+            ("aa".as_alphachar(), 0),
+            ("aaa".as_alphachar(), 1),
+            ("aaab".as_alphachar(), 2)
+        ]
+    );
+}
+
+#[test]
+fn test_empty_key() {
+    let words: Vec<&str> = DICT.iter().copied().chain(["ab"]).collect();
+    // the empty key stored first, last and in between, into a trie where the
+    // root is still empty or already branching
+    for at in [0, 1, 2, words.len()] {
+        println!("Storing the empty key after {} words", at);
+        let mut trie = en_trie_new();
+        for word in &words[..at] {
+            assert!(trie.store(&word.as_alphachar(), 1));
+        }
+        assert_eq!(trie.retrieve(&[0]), None);
+        assert!(trie.store(&[0], 2));
+        for word in &words[at..] {
+            assert!(trie.store(&word.as_alphachar(), 1));
+        }
+        trie.validate().expect("Trie with the empty key is corrupt");
+        assert_eq!(trie.retrieve(&[0]), Some(&2));
+        assert!(trie.contains_key(&[0]));
+        assert_eq!(trie.retrieve_str(""), Some(&2));
+
+        let mut keys = trie.iter().map(|(key, _)| key);
+        assert_eq!(keys.next(), Some(vec![0]), "Empty key should come first");
+        assert_eq!(keys.count(), words.len());
+        assert_eq!(trie.len(), words.len() + 1);
+
+        println!("Overwriting and deleting the empty key");
+        assert!(trie.store(&[0], 3));
+        assert_eq!(trie.retrieve(&[0]), Some(&3));
+        assert!(trie.delete(&[0]));
+        assert!(!trie.delete(&[0]));
+        assert_eq!(trie.retrieve(&[0]), None);
+        trie.validate()
+            .expect("Trie is corrupt after deleting the empty key");
+        assert_eq!(trie.iter().count(), words.len());
+        for word in &words {
+            assert_eq!(trie.retrieve(&word.as_alphachar()), Some(&1));
+        }
+    }
+}
+
+#[test]
+fn test_is_empty() {
+    let mut trie = en_trie_new();
+    assert!(trie.is_empty(), "New trie should be empty");
+
+    assert!(trie.store(&"a".as_alphachar(), 1));
+    assert!(!trie.is_empty(), "Trie with a key should not be empty");
+
+    assert!(trie.delete(&"a".as_alphachar()));
+    assert!(
+        trie.is_empty(),
+        "Trie should be empty after deleting its only key"
+    );
+
+    println!("Checking read-only and built tries");
+    assert!(crate::trie::ROTrie::<i32>::new(en_alpha_map_new()).is_empty());
+    assert!(TrieBuilder::<i32>::new().build().unwrap().is_empty());
+    let mut builder = TrieBuilder::new();
+    builder.insert(&"a".as_alphachar(), 1);
+    assert!(!builder.build().unwrap().into_ro().is_empty());
+
+    println!("Storing the empty key");
+    assert!(trie.store(&[0], 1));
+    assert!(
+        !trie.is_empty(),
+        "Trie with the empty key should not be empty"
+    );
+}
+
+#[test]
+fn test_key_depth() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+
+    // the only key starting with 'z' moves to the tail right away
+    assert_eq!(trie.key_depth(&"zebra".as_alphachar()), Some(1));
+    // 'a' and its terminator are both branches
+    assert_eq!(trie.key_depth(&"a".as_alphachar()), Some(2));
+    // "ki" is shared with "kite"
+    assert_eq!(trie.key_depth(&"king".as_alphachar()), Some(3));
+    assert_eq!(trie.key_depth(&"kin".as_alphachar()), None);
+    assert_eq!(trie.key_depth(&"zebras".as_alphachar()), None);
+}
+
+#[test]
+fn test_alpha_map_from_trie_file() {
+    let mut trie = en_trie_new();
+    assert!(trie.store(&"abc".as_alphachar(), 1));
+
+    let path = std::env::temp_dir().join("datrie_test_alpha_map_from_trie_file.tri");
+    trie.save(&path).expect("Failed to save trie");
+    let alpha_map = AlphaMap::from_trie_file(&path);
+    std::fs::remove_file(&path).unwrap();
+    let alpha_map = alpha_map.expect("Failed to read alpha map");
+
+    // keys from the companion trie should be usable in the saved one
+    let mut companion = Trie::new(alpha_map);
+    assert!(companion.store(&"abc".as_alphachar(), 2));
+    for (key, _) in companion.iter() {
+        assert_eq!(trie.retrieve(&key), Some(&1));
+    }
+}
+
+#[test]
+fn test_retrieve_iter() {
+    let mut trie = en_trie_new();
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+
+    println!("Retrieving keys given in chunks");
+    for word in DICT.iter().chain(&["ab", "abacuses", "zeb", "a6acus", ""]) {
+        let key = word.as_alphachar();
+        let (head, tail) = key.split_at(key.len() / 2);
+        assert_eq!(
+            trie.retrieve_iter(head.iter().chain(tail).copied()),
+            trie.retrieve(&key),
+            "Mismatch on {}",
+            word
+        );
+    }
+}
+
+/// Store `count` numeric keys and check that they can all be retrieved and iterated
+fn check_many_keys(count: u32) {
+    println!("Preparing alpha map");
+    let mut alpha_map = AlphaMap::default();
+    alpha_map
+        .add_range('0' as AlphaChar..='9' as AlphaChar)
+        .unwrap();
+
+    println!("Storing {} keys", count);
+    let mut trie = Trie::new(alpha_map);
+    // reverse the digits so keys spread out over the first branches
+    let key_of = |i: u32| -> Vec<AlphaChar> {
+        let mut key: Vec<AlphaChar> = i
+            .to_string()
+            .chars()
+            .rev()
+            .map(|c| c as AlphaChar)
+            .collect();
+        key.push(0);
+        key
+    };
+    for i in 0..count {
+        assert!(trie.store(&key_of(i), i as i32), "Failed to store {}", i);
+    }
+
+    println!("Checking trie contents");
+    for i in 0..count {
+        assert_eq!(trie.retrieve(&key_of(i)), Some(&(i as i32)));
+    }
+    let mut iterated = 0;
+    for (key, data) in trie.iter() {
+        assert_eq!(key, key_of(*data.unwrap() as u32));
+        iterated += 1;
+    }
+    assert_eq!(iterated, count);
+    assert_eq!(trie.validate(), Ok(()));
+}
+
+#[test]
+fn test_many_keys() {
+    check_many_keys(20_000);
+}
+
+#[test]
+#[ignore = "slow, builds a trie with millions of cells; run with --release --ignored"]
+fn test_many_keys_stress() {
+    check_many_keys(1_000_000);
+}
+
+#[test]
+fn test_common_prefix_search() {
+    let mut trie = en_trie_new();
+    for (i, word) in ["a", "ab", "abacus", "abandon", "b"].iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+
+    println!("Searching text with prefixes in branches and tail");
+    let found = trie.common_prefix_search(&"abacuses".as_alphachar());
+    let expected = vec![
+        ("a".as_alphachar(), &0),
+        ("ab".as_alphachar(), &1),
+        ("abacus".as_alphachar(), &2),
+    ];
+    assert_eq!(found, expected);
+
+    println!("Searching text without terminator");
+    let text: Vec<AlphaChar> = "abandoned".chars().map(|c| c as AlphaChar).collect();
+    let found = trie.common_prefix_search(&text);
+    assert_eq!(found.last(), Some(&("abandon".as_alphachar(), &3)));
+    assert_eq!(found.len(), 3);
+
+    println!("Searching text without matches");
+    assert!(trie.common_prefix_search(&"cab".as_alphachar()).is_empty());
+    assert!(trie.common_prefix_search(&"".as_alphachar()).is_empty());
+
+    println!("Matching the empty key");
+    assert!(trie.store(&"".as_alphachar(), 10));
+    let found = trie.common_prefix_search(&"bc".as_alphachar());
+    assert_eq!(
+        found,
+        vec![("".as_alphachar(), &10), ("b".as_alphachar(), &4)]
+    );
+}
+
+#[test]
+fn test_retrieve_longest_prefix() {
+    let mut trie = en_trie_new();
+    for (i, word) in ["a", "ab", "abacus", "b"].iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+
+    println!("Matching inside the tail");
+    assert_eq!(
+        trie.retrieve_longest_prefix(&"abacuses".as_alphachar()),
+        Some((6, &2))
+    );
+    assert_eq!(
+        trie.retrieve_longest_prefix(&"abac".as_alphachar()),
+        Some((2, &1))
+    );
+    assert_eq!(
+        trie.retrieve_longest_prefix(&"ba".as_alphachar()),
+        Some((1, &3))
+    );
+    assert_eq!(trie.retrieve_longest_prefix(&"cab".as_alphachar()), None);
+
+    println!("Matching the empty key");
+    assert!(trie.store(&"".as_alphachar(), 10));
+    assert_eq!(
+        trie.retrieve_longest_prefix(&"cab".as_alphachar()),
+        Some((0, &10))
+    );
+    assert_eq!(
+        trie.retrieve_longest_prefix(&"".as_alphachar()),
+        Some((0, &10))
+    );
+}
+
+#[test]
+fn test_search_fuzzy() {
+    let mut trie = en_trie_new();
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+    assert!(trie.store(&"".as_alphachar(), -1));
+
+    let levenshtein = |a: &[AlphaChar], b: &[AlphaChar]| {
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+        for (i, ac) in a.iter().enumerate() {
+            let mut next = vec![i + 1];
+            for (j, bc) in b.iter().enumerate() {
+                let dist = (row[j] + usize::from(ac != bc))
+                    .min(row[j + 1] + 1)
+                    .min(next[j] + 1);
+                next.push(dist);
+            }
+            row = next;
+        }
+        row[b.len()]
+    };
+
+    for query in ["abacus", "abakus", "netwrk", "zebras", "ab", "", "qqqq"] {
+        let query = query.as_alphachar();
+        let query = &query[..query.len() - 1];
+        for max_dist in 0..=3 {
+            println!("Searching {:?} within {}", query, max_dist);
+            let expected: Vec<(Vec<AlphaChar>, usize, &i32)> = trie
+                .iter()
+                .filter_map(|(key, data)| {
+                    let dist = levenshtein(&key[..key.len() - 1], query);
+                    (dist <= max_dist).then(|| (key, dist, data.unwrap()))
+                })
+                .collect();
+            assert_eq!(trie.search_fuzzy(query, max_dist), expected);
+        }
+    }
+    assert_eq!(
+        trie.search_fuzzy(&"abakus".as_alphachar(), 1),
+        vec![("abacus".as_alphachar(), 1, &1)]
+    );
+}
+
+#[test]
+fn test_search_pattern() {
+    let mut trie = en_trie_new();
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+    // '?' matches any character
+    let pattern_of = |pattern: &str| -> Vec<Option<AlphaChar>> {
+        pattern
+            .chars()
+            .map(|c| (c != '?').then_some(c as AlphaChar))
+            .collect()
+    };
+
+    for pattern in [
+        "ab?cus",
+        "?????",
+        "a?",
+        "",
+        "z?b??",
+        "??????????????????????",
+        "abacus",
+    ] {
+        println!("Searching {}", pattern);
+        let expected: Vec<(Vec<AlphaChar>, &i32)> = trie
+            .iter()
+            .filter(|(key, _)| {
+                key.len() == pattern.chars().count() + 1
+                    && pattern
+                        .chars()
+                        .zip(key.iter())
+                        .all(|(pc, ac)| pc == '?' || pc as AlphaChar == *ac)
+            })
+            .map(|(key, data)| (key, data.unwrap()))
+            .collect();
+        assert_eq!(trie.search_pattern(&pattern_of(pattern)), expected);
+    }
+    assert_eq!(
+        trie.search_pattern(&pattern_of("ab?cus")),
+        vec![("abacus".as_alphachar(), &1)]
+    );
+
+    println!("Checking the terminator in the pattern");
+    let mut pattern = pattern_of("ab?cus");
+    pattern.push(Some(0));
+    assert_eq!(trie.search_pattern(&pattern).len(), 1);
+    pattern.push(None);
+    assert_eq!(trie.search_pattern(&pattern).len(), 1);
+    assert!(trie.search_pattern(&pattern_of("abacu")).is_empty());
+    assert!(trie.search_pattern(&pattern_of("abacus?")).is_empty());
+}
+
+#[test]
+fn test_state_eq() {
+    use crate::trie::TrieState;
+
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    fn walk<'a>(trie: &'a Trie<i32>, key: &str) -> TrieState<'a, i32> {
+        let mut state = trie.root();
+        assert_eq!(state.walk_str(&key.as_alphachar()[..key.len()]), key.len());
+        state
+    }
+
+    println!("Comparing states of the same trie");
+    assert!(walk(&trie, "ab") == walk(&trie, "ab"));
+    assert!(walk(&trie, "zeb") == walk(&trie, "zeb"));
+    assert!(walk(&trie, "ab") != walk(&trie, "ac"));
+    assert!(walk(&trie, "ze") != walk(&trie, "zeb"));
+    let mut rewound = walk(&trie, "zeb");
+    rewound.rewind();
+    assert!(rewound == trie.root());
+
+    println!("Hashing states");
+    let states: HashSet<_> = ["ab", "ab", "ac", "zeb", "", "zeb"]
+        .iter()
+        .map(|key| walk(&trie, key))
+        .collect();
+    assert_eq!(states.len(), 4);
+    assert!(states.contains(&rewound));
+
+    println!("Comparing states of different tries");
+    let copy = trie.clone();
+    assert!(walk(&trie, "ab") != walk(&copy, "ab"));
+    assert!(trie.root() != copy.root());
+}
+
+#[test]
+fn test_node_kind() {
+    use crate::trie::NodeKind;
+
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    for (key, kind) in [
+        ("", NodeKind::Branch),
+        ("ab", NodeKind::Branch),
+        ("a", NodeKind::TerminalWithData),
+        ("ze", NodeKind::TailSingle),
+        ("zebra", NodeKind::Leaf),
+    ] {
+        let mut state = trie.root();
+        assert_eq!(state.walk_str(&key.as_alphachar()[..key.len()]), key.len());
+        assert_eq!(state.node_kind(), kind, "Key {:?}", key);
+        let has_data = matches!(kind, NodeKind::TerminalWithData | NodeKind::Leaf);
+        assert_eq!(state.get_data().is_some(), has_data, "Key {:?}", key);
+    }
+}
+
+#[test]
+fn test_walk_prefix() {
+    let mut trie = en_trie_new();
+    for (i, word) in ["ab", "abacus", "abandon", "b"].iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+
+    println!("Walking stored keys");
+    assert_eq!(trie.walk_prefix(&"ab".as_alphachar()), (2, true));
+    assert_eq!(trie.walk_prefix(&"abacus".as_alphachar()), (6, true));
+
+    println!("Walking keys diverging in branches and tail");
+    assert_eq!(trie.walk_prefix(&"abx".as_alphachar()), (2, true));
+    assert_eq!(trie.walk_prefix(&"abacks".as_alphachar()), (4, false));
+    assert_eq!(trie.walk_prefix(&"abacuses".as_alphachar()), (6, true));
+    assert_eq!(trie.walk_prefix(&"a".as_alphachar()), (1, false));
+    assert_eq!(trie.walk_prefix(&"cab".as_alphachar()), (0, false));
+    assert_eq!(trie.walk_prefix(&"a6".as_alphachar()), (1, false));
+
+    println!("Walking without terminator");
+    let text: Vec<AlphaChar> = "aband".chars().map(|c| c as AlphaChar).collect();
+    assert_eq!(trie.walk_prefix(&text), (5, false));
+}
+
+#[test]
+fn test_str_keys() {
+    let mut trie = en_trie_new();
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store_str(word, i as i32), "Failed to store {}", word);
+    }
+    for (i, word) in DICT.iter().enumerate() {
+        assert_eq!(
+            trie.retrieve_str(word),
+            Some(&(i as i32)),
+            "Mismatch on {}",
+            word
+        );
+        assert!(trie.contains_str(word), "Missing {}", word);
+    }
+    assert_eq!(trie.retrieve_str("abacu"), None);
+    assert!(!trie.contains_str("a6acus"));
+    assert!(!trie.store_str("a6acus", 1));
+
+    println!("Deleting by string");
+    assert!(trie.delete_str("abacus"));
+    assert!(!trie.delete_str("abacus"));
+    assert!(!trie.contains_str("abacus"));
+    assert_eq!(trie.retrieve(&"abacus".as_alphachar()), None);
+}
+
+#[test]
+fn test_reload_allocation() {
+    let serialize = |trie: &mut TestTrie| {
+        let mut buf = Vec::new();
+        trie.serialize(&mut buf).unwrap();
+        buf
+    };
+
+    println!("Leaving free cells and tail blocks behind");
+    let mut trie = en_trie_new();
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+    for word in DICT.iter().step_by(3) {
+        assert!(trie.delete(&word.as_alphachar()));
+    }
+    let buf = serialize(&mut trie);
+    let mut loaded = Trie::<i32>::from_reader(&mut Cursor::new(&buf)).unwrap();
+
+    println!("Mutating both tries the same way");
+    let words: Vec<String> = DICT
+        .iter()
+        .flat_map(|word| [format!("{}s", word), format!("re{}", word)])
+        .collect();
+    for (i, word) in words.iter().enumerate() {
+        assert!(trie.store(&word.as_str().as_alphachar(), i as i32));
+        assert!(loaded.store(&word.as_str().as_alphachar(), i as i32));
+        if i % 4 == 0 {
+            let key = DICT[i / 4 % DICT.len()].as_alphachar();
+            assert_eq!(trie.delete(&key), loaded.delete(&key));
+        }
+        assert_eq!(
+            serialize(&mut trie),
+            serialize(&mut loaded),
+            "Diverged at {}",
+            word
+        );
+    }
+}
+
+#[test]
+fn test_canonicalize() {
+    use crate::types::ALPHA_CHAR_ERROR;
+
+    let serialize = |trie: &mut TestTrie| {
+        let mut buf = Vec::new();
+        trie.serialize(&mut buf).unwrap();
+        buf
+    };
+
+    let mut forward = en_trie_new();
+    for word in DICT {
+        assert!(forward.store(&word.as_alphachar(), 1));
+    }
+    println!("Storing in reverse, with extra keys deleted afterward");
+    let mut backward = en_trie_new();
+    for word in DICT.iter().rev().chain(["abc", "zebu", "networked"].iter()) {
+        assert!(backward.store(&word.as_alphachar(), 1));
+    }
+    for word in ["abc", "zebu", "networked"] {
+        assert!(backward.delete(&word.as_alphachar()));
+    }
+
+    forward.canonicalize().unwrap();
+    backward.canonicalize().unwrap();
+    assert_dict_complete(&forward);
+    assert!(forward.validate().is_ok());
+    assert_eq!(serialize(&mut forward), serialize(&mut backward));
+
+    println!("Canonicalizing again keeps the same bytes");
+    let before = serialize(&mut forward);
+    forward.canonicalize().unwrap();
+    assert!(forward.is_dirty());
+    assert_eq!(serialize(&mut forward), before);
+
+    println!("Keeping a trie that cannot be rebuilt");
+    let mut trie = en_trie_new();
+    for (i, word) in ["abc", "abz", "zoo"].iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+    let mut buf = serialize(&mut trie);
+    // drop 'z' from the end of the a..z range of the alphabet
+    assert_eq!(buf[12..16], [0, 0, 0, 0x7a]);
+    buf[15] = 0x79;
+    let mut corrupt = TestTrie::from_reader(&mut Cursor::new(&buf)).unwrap();
+    let before: Vec<_> = corrupt
+        .iter()
+        .map(|(key, data)| (key, *data.unwrap()))
+        .collect();
+    assert_eq!(before.len(), 3);
+    assert_eq!(
+        corrupt.canonicalize(),
+        Err(StoreError::CharNotInAlphaMap(ALPHA_CHAR_ERROR))
+    );
+    let after: Vec<_> = corrupt
+        .iter()
+        .map(|(key, data)| (key, *data.unwrap()))
+        .collect();
+    assert_eq!(after, before);
+    assert_eq!(corrupt.retrieve(&"abc".as_alphachar()), Some(&0));
+}
+
+#[test]
+fn test_clone() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).unwrap();
+    assert!(!trie.is_dirty());
+
+    println!("Storing into the clone");
+    let mut clone = trie.clone();
+    assert!(!clone.is_dirty());
+    assert!(clone.store(&"zebu".as_alphachar(), -1));
+    assert!(clone.delete(&DICT[0].as_alphachar()));
+    assert!(clone.is_dirty());
+    assert_eq!(clone.retrieve(&"zebu".as_alphachar()), Some(&-1));
+
+    println!("Checking the original is unchanged");
+    assert!(!trie.is_dirty());
+    assert_eq!(trie.retrieve(&"zebu".as_alphachar()), None);
+    assert_dict_complete(&trie);
+    let mut buf2 = Vec::new();
+    trie.serialize(&mut buf2).unwrap();
+    assert_eq!(buf, buf2);
+
+    println!("Cloning a read-only trie");
+    let ro = Trie::<i32>::from_reader(&mut Cursor::new(&buf))
+        .unwrap()
+        .into_ro();
+    let ro_clone = ro.clone();
+    drop(ro);
+    assert_eq!(ro_clone.len(), DICT.len());
+}
+
+#[test]
+fn test_get() {
+    let mut trie = en_trie_new();
+    assert!(trie.store(&"abacus".as_alphachar(), 1));
+
+    let key = "abacus".as_alphachar();
+    // the copy does not borrow the trie, so it can be stored back
+    let data = trie.get(&key).unwrap();
+    assert!(trie.store(&key, data + 1));
+    assert_eq!(trie.get(&key), Some(2));
+    assert_eq!(trie.get(&"abac".as_alphachar()), None);
+}
+
+#[test]
+fn test_retrieve_ci() {
+    let mut trie = en_trie_new();
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+
+    for (i, word) in DICT.iter().enumerate() {
+        let upper = word.to_uppercase();
+        assert_eq!(
+            trie.retrieve_ci(&upper.as_str().as_alphachar()),
+            Ok(Some(&(i as i32))),
+            "Mismatch on {}",
+            upper
+        );
+    }
+    assert_eq!(trie.retrieve_ci(&"AbAcUs".as_alphachar()), Ok(Some(&1)));
+    assert_eq!(trie.retrieve_ci(&"ABACU".as_alphachar()), Ok(None));
+    assert_eq!(trie.retrieve_ci(&"ABAC6".as_alphachar()), Ok(None));
+
+    println!("Checking characters without 1:1 lowercase");
+    assert_eq!(trie.retrieve_ci(&"\u{130}a".as_alphachar()), Err(0x130));
+    // characters after the terminator are ignored
+    assert_eq!(trie.retrieve_ci(&[0x41, 0, 0x130]), Ok(Some(&0)));
+}
+
+#[test]
+fn test_retrieve_many() {
+    let mut trie = en_trie_new();
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+    assert!(trie.store(&"ab".as_alphachar(), -1));
+
+    let words: Vec<Vec<AlphaChar>> = DICT
+        .iter()
+        .rev()
+        .chain(["", "a", "ab", "abacuses", "zeb", "a6acus", "abacus"].iter())
+        .map(|word| word.as_alphachar())
+        .collect();
+    let keys: Vec<&[AlphaChar]> = words.iter().map(|word| word.as_slice()).collect();
+
+    let results = trie.retrieve_many(&keys);
+    assert_eq!(results.len(), keys.len());
+    for (key, result) in keys.iter().zip(results) {
+        assert_eq!(result, trie.retrieve(key), "Mismatch on {:?}", key);
+    }
+    assert!(trie.retrieve_many(&[]).is_empty());
+}
+
+#[test]
+fn test_contains_key() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+
+    for word in DICT {
+        assert!(trie.contains_key(&word.as_alphachar()), "Missing {}", word);
+    }
+    for word in ["ab", "abacuses", "zeb", "a6acus", ""] {
+        assert!(
+            !trie.contains_key(&word.as_alphachar()),
+            "Trie falsely contains {}",
+            word
+        );
+    }
+}
+
+#[test]
+fn test_len() {
+    let mut trie = en_trie_new();
+    assert_eq!(trie.len(), 0);
+
+    println!("Counting stored keys");
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), 1));
+        assert_eq!(trie.len(), i + 1);
+    }
+    assert!(trie.store(&"abacus".as_alphachar(), 2));
+    assert!(!trie.store_if_absent(&"abacus".as_alphachar(), 3));
+    assert_eq!(trie.len(), DICT.len());
+
+    println!("Counting after deletion");
+    assert!(trie.delete(&"abacus".as_alphachar()));
+    assert!(!trie.delete(&"abacus".as_alphachar()));
+    assert_eq!(trie.len(), DICT.len() - 1);
+    assert!(trie.store_if_absent(&"abacus".as_alphachar(), 1));
+    assert!(trie.delete(&"zebra".as_alphachar()));
+    assert_eq!(trie.len(), DICT.len() - 1);
+
+    println!("Counting after reload");
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+    let trie: Trie<i32> = Trie::from_reader(&mut Cursor::new(&buf))
+        .expect("Failed to reload trie from serialized state");
+    assert_eq!(trie.len(), DICT.len() - 1);
+    assert_eq!(trie.len(), trie.iter().count());
+}
+
+#[test]
+fn test_stats() {
+    let mut trie = en_trie_new();
+    let empty = trie.stats();
+    assert_eq!(empty.num_keys, 0);
+    assert_eq!(empty.num_tail_blocks, 0);
+    assert_eq!(empty.suffix_bytes, 0);
+
+    println!("Storing keys");
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let stats = trie.stats();
+    assert_eq!(stats.num_keys, DICT.len());
+    assert_eq!(stats.num_tail_blocks, DICT.len());
+    assert!(stats.num_cells > empty.num_cells);
+    assert!(stats.num_free_cells < stats.num_cells);
+    // each key keeps at least one character in the double-array
+    let total_len: usize = DICT.iter().map(|word| word.len()).sum();
+    assert!(stats.suffix_bytes < total_len);
+
+    println!("Deleting keys");
+    assert!(trie.delete(&"abacus".as_alphachar()));
+    let deleted = trie.stats();
+    assert_eq!(deleted.num_keys, DICT.len() - 1);
+    assert_eq!(deleted.num_tail_blocks, DICT.len());
+    assert!(deleted.suffix_bytes < stats.suffix_bytes);
+    assert!(deleted
+        .to_string()
+        .contains(&format!("Tail blocks: {} (1 free)", DICT.len())));
+}
+
+#[test]
+fn test_optimize_tail() {
+    println!("Storing keys with the same ending");
+    let mut trie = en_trie_new();
+    let mut words = Vec::new();
+    for a in 'a'..='z' {
+        for b in 'a'..='z' {
+            words.push(format!("{}{}ingly", a, b));
+        }
+    }
+    for (i, word) in words.iter().enumerate() {
+        assert!(trie.store(&word.as_str().as_alphachar(), i as i32));
+    }
+    let stats = trie.stats();
+    assert_eq!(stats.stored_suffix_bytes, stats.suffix_bytes);
+    let snapshot = trie.snapshot();
+    trie.serialize(&mut Vec::new()).unwrap();
+
+    println!("Sharing the suffixes");
+    assert_eq!(trie.optimize_tail(), words.len() - 1);
+    assert_eq!(trie.optimize_tail(), 0);
+    let optimized = trie.stats();
+    assert_eq!(optimized.suffix_bytes, stats.suffix_bytes);
+    // all keys end with the same suffix
+    assert_eq!(
+        optimized.stored_suffix_bytes,
+        stats.suffix_bytes / words.len()
+    );
+    assert!(!trie.is_dirty());
+    println!("{}", optimized);
+
+    println!("Changing keys with a shared suffix");
+    assert!(trie.store(&"abingo".as_alphachar(), -1));
+    assert!(trie.delete(&"zzingly".as_alphachar()));
+    assert_eq!(trie.validate(), Ok(()));
+    for (i, word) in words.iter().enumerate() {
+        let expected = match word.as_str() {
+            "zzingly" => None,
+            _ => Some(&(i as i32)),
+        };
+        assert_eq!(trie.retrieve(&word.as_str().as_alphachar()), expected);
+    }
+    assert_eq!(trie.retrieve(&"abingo".as_alphachar()), Some(&-1));
+
+    println!("Saving the shared suffixes");
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+    let loaded = Trie::<i32>::from_reader(&mut Cursor::new(&buf)).unwrap();
+    assert_eq!(loaded.stats().suffix_bytes, trie.stats().suffix_bytes);
+    assert!(loaded.iter().eq(trie.iter()));
+
+    trie.restore(snapshot);
+    assert_eq!(trie.stats(), stats);
+}
+
+#[test]
+fn test_display_key() {
+    use crate::types::{AlphaCharToStringLossy, DisplayKey, ALPHA_CHAR_ERROR};
+
+    println!("Displaying valid keys");
+    let mut trie = en_trie_new();
+    for word in ["", "abc", "xyz"] {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let keys: Vec<String> = trie
+        .iter()
+        .map(|(key, _)| DisplayKey(&key).to_string())
+        .collect();
+    assert_eq!(keys, ["", "abc", "xyz"]);
+    for (key, _) in trie.iter() {
+        let key = key.as_slice();
+        assert_eq!(Some(key.ac_to_string_lossy()), key.ac_to_string());
+    }
+
+    println!("Displaying keys with invalid characters");
+    let key = [0x61, 0xd800, ALPHA_CHAR_ERROR, 0x62, 0, 0x63];
+    assert_eq!(key.as_slice().ac_to_string(), None);
+    assert_eq!(key.as_slice().ac_to_string_lossy(), "a\u{fffd}\u{fffd}b");
+    assert_eq!(DisplayKey(&key).to_string(), "a\\u{d800}\\u{ffffffff}b");
+    assert_eq!(
+        format!("{:?}", DisplayKey(&key)),
+        r#""a\u{d800}\u{ffffffff}b""#
+    );
+    assert_eq!(
+        format!("{:?}", DisplayKey(&"a\"\n".as_alphachar())),
+        r#""a\"\n""#
+    );
+}
+
+#[test]
+fn test_debug() {
+    let mut trie = en_trie_new();
+    assert_eq!(
+        format!("{:?}", en_alpha_map_new()),
+        "AlphaMap { ranges: {97..=122} }"
+    );
+    for word in ["b", "a", "c"] {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    assert_eq!(
+        format!("{:?}", trie),
+        "Trie { len: 3, alpha_map: AlphaMap { ranges: {97..=122} }, \
+         keys: [\"a\", \"b\", \"c\"], is_dirty: true }"
+    );
+
+    println!("Printing only the first keys of a large trie");
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let debug = format!("{:?}", trie.into_ro());
+    assert!(debug.starts_with("ROTrie { len: "), "Unexpected {}", debug);
+    assert_eq!(debug.matches('"').count(), 8 * 2);
+    assert!(debug.ends_with(", ..] }"), "Unexpected {}", debug);
+}
+
+#[test]
+fn test_debug_tail_entries() {
+    let mut trie = en_trie_new();
+    assert!(trie.debug_tail_entries().is_empty());
+    for word in ["abacus", "abandon", "b"] {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+
+    let suffixes = |trie: &TestTrie| {
+        let mut suffixes: Vec<String> = trie
+            .debug_tail_entries()
+            .iter()
+            .map(|(_, suffix)| suffix.as_slice().ac_to_string().unwrap())
+            .collect();
+        suffixes.sort();
+        suffixes
+    };
+    // the characters telling the keys apart stay in the double-array
+    assert_eq!(suffixes(&trie), ["", "don", "us"]);
+
+    println!("Deleted keys are not listed");
+    let entries = trie.debug_tail_entries();
+    assert!(trie.delete(&"abandon".as_alphachar()));
+    assert_eq!(suffixes(&trie), ["", "us"]);
+    let remaining = trie.debug_tail_entries();
+    assert!(remaining.iter().all(|entry| entries.contains(entry)));
+}
+
+#[test]
+fn test_clear() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+
+    println!("Clearing trie");
+    trie.clear();
+    assert!(trie.is_empty());
+    assert_eq!(trie.len(), 0);
+    assert_eq!(trie.iter().count(), 0);
+    assert_eq!(trie.retrieve(&"abacus".as_alphachar()), None);
+
+    println!("Refilling trie");
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    assert_dict_complete(&trie);
+}
+
+#[test]
+fn test_retrieve_mut() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+    assert!(!trie.is_dirty());
+
+    println!("Looking up missing key");
+    assert_eq!(trie.retrieve_mut(&"zebras".as_alphachar()), None);
+    assert!(!trie.is_dirty(), "Missing key should not dirty the trie");
+
+    println!("Updating data in place");
+    for _ in 0..3 {
+        *trie.retrieve_mut(&"zebra".as_alphachar()).unwrap() += 1;
+    }
+    assert!(trie.is_dirty());
+    assert_eq!(trie.retrieve(&"zebra".as_alphachar()), Some(&4));
+    assert_eq!(trie.retrieve(&"abacus".as_alphachar()), Some(&1));
+}
+
+#[test]
+fn test_entry() {
+    let mut trie = en_trie_new();
+
+    println!("Counting words with entries");
+    let text = ["to", "be", "or", "not", "to", "be", "that", "is", "to"];
+    for word in text {
+        *trie.entry(&word.as_alphachar()).or_insert(0) += 1;
+    }
+    assert_eq!(trie.len(), 6);
+    assert_eq!(trie.retrieve(&"to".as_alphachar()), Some(&3));
+    assert_eq!(trie.retrieve(&"be".as_alphachar()), Some(&2));
+    assert_eq!(trie.retrieve(&"that".as_alphachar()), Some(&1));
+
+    println!("Modifying existing entries only");
+    trie.entry(&"to".as_alphachar())
+        .and_modify(|v| *v *= 10)
+        .or_insert_with(|| unreachable!());
+    assert_eq!(trie.retrieve(&"to".as_alphachar()), Some(&30));
+    assert_eq!(
+        *trie
+            .entry(&"tot".as_alphachar())
+            .and_modify(|v| *v *= 10)
+            .or_insert(7),
+        7
+    );
+    assert_eq!(trie.retrieve(&"to".as_alphachar()), Some(&30));
+    assert_eq!(trie.len(), 7);
+
+    println!("Inserting vacant entries fallibly");
+    let Entry::Vacant(entry) = trie.entry(&"tote".as_alphachar()) else {
+        panic!("tote should be vacant");
+    };
+    let data = entry.try_insert(4).unwrap();
+    *data += 1;
+    assert_eq!(trie.retrieve(&"tote".as_alphachar()), Some(&5));
+    assert_eq!(trie.retrieve(&"tot".as_alphachar()), Some(&7));
+    assert_eq!(trie.len(), 8);
+}
+
+#[test]
+#[should_panic(expected = "outside of the alphabet")]
+fn test_entry_nonalpha() {
+    let mut trie = en_trie_new();
+    trie.entry(&"a6acus".as_alphachar()).or_insert(1);
+}
+
+#[test]
+fn test_try_store() {
+    let mut trie = en_trie_new();
+    println!("Inserting new key");
+    assert_eq!(trie.try_store(&"abacus".as_alphachar(), 1), Ok(true));
+    println!("Overwriting existing key");
+    assert_eq!(trie.try_store(&"abacus".as_alphachar(), 2), Ok(false));
+    assert_eq!(trie.retrieve(&"abacus".as_alphachar()), Some(&2));
+
+    println!("Storing key with character outside of the alphabet");
+    assert_eq!(
+        trie.try_store(&"a6acus".as_alphachar(), 3),
+        Err(StoreError::CharNotInAlphaMap('6' as AlphaChar))
+    );
+    assert_eq!(
+        trie.try_store(&"ab6".as_alphachar(), 3),
+        Err(StoreError::CharNotInAlphaMap('6' as AlphaChar))
+    );
+    assert!(
+        !trie.store(&"a6acus".as_alphachar(), 3),
+        "store() should still return false on error"
+    );
+    assert_eq!(
+        trie.check_key(&"a6acus".as_alphachar()),
+        Err('6' as AlphaChar)
+    );
+    assert_eq!(trie.check_key(&"abacus".as_alphachar()), Ok(()));
+    assert_eq!(trie.len(), 1);
+
+    println!("Storing key with interior terminator");
+    let key = [0x61, 0, 0x62, 0];
+    assert_eq!(
+        trie.try_store(&key, 3),
+        Err(StoreError::InteriorTerminator(1))
+    );
+    assert_eq!(
+        trie.try_store(&[0x61, 0, 0], 3),
+        Err(StoreError::InteriorTerminator(1))
+    );
+    assert!(!trie.store(&key, 3));
+    assert_eq!(trie.retrieve(&"a".as_alphachar()), None);
+    assert_eq!(trie.len(), 1);
+}
+
+#[test]
+fn test_from_iter() {
+    println!("Collecting trie");
+    let trie: Trie<i32> = DICT
+        .iter()
+        .enumerate()
+        .map(|(i, word)| (word.as_alphachar(), i as i32))
+        .chain(iter::once((
+            "xy".chars().map(|c| c as AlphaChar).collect(),
+            -1,
+        )))
+        .collect();
+
+    assert_eq!(trie.len(), DICT.len() + 1);
+    for (i, word) in DICT.iter().enumerate() {
+        assert_eq!(trie.retrieve(&word.as_alphachar()), Some(&(i as i32)));
+    }
+    assert_eq!(trie.retrieve(&"xy".as_alphachar()), Some(&-1));
+
+    println!("Checking inferred alphabet");
+    let mut trie = trie;
+    assert!(trie.store(&"yak".as_alphachar(), 1));
+    assert!(!trie.store(&"A".as_alphachar(), 1));
+}
+
+#[test]
+fn test_builder() {
+    println!("Building trie with unknown alphabet");
+    let mut builder = TrieBuilder::new();
+    for (i, word) in DICT.iter().enumerate() {
+        builder.insert(&word.as_alphachar(), i as i32);
+    }
+    builder.insert(&[0x0e01, 0x0e03], -1);
+    builder.insert(&"zebra".as_alphachar(), -2);
+    assert_eq!(builder.len(), DICT.len() + 1);
+
+    let alpha_map = builder.alpha_map().unwrap();
+    // scattered characters are merged into ranges
+    assert!(alpha_map.ranges().count() < alpha_map.total_chars());
+    assert_eq!(alpha_map.char_to_trie(0x0e02), None);
+
+    let trie = builder.build().unwrap();
+    assert_eq!(trie.len(), DICT.len() + 1);
+    for (i, word) in DICT.iter().enumerate() {
+        let expected = if *word == "zebra" { -2 } else { i as i32 };
+        assert_eq!(trie.retrieve(&word.as_alphachar()), Some(&expected));
+    }
+    assert_eq!(trie.retrieve(&[0x0e01, 0x0e03, 0]), Some(&-1));
+
+    println!("Building trie with too many characters");
+    let mut builder = TrieBuilder::new();
+    for ac in 0x100..0x200 {
+        builder.insert(&[ac, 0], 1);
+    }
+    assert_eq!(
+        builder.build().err(),
+        Some(BuildError::AlphaMap(AlphaMapError::TooManyChars(0x100)))
+    );
+}
+
+#[test]
+fn test_interned_trie() {
+    use crate::interned::InternedTrie;
+
+    let mut trie = InternedTrie::new(en_alpha_map_new());
+    assert!(trie.is_empty());
+
+    println!("Storing values in the arena");
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), word.to_uppercase()));
+    }
+    assert!(!trie.store(&"A".as_alphachar(), String::new()));
+    assert_eq!(trie.len(), DICT.len());
+    assert_eq!(trie.values().len(), DICT.len());
+    for word in DICT {
+        assert_eq!(
+            trie.retrieve(&word.as_alphachar()),
+            Some(&word.to_uppercase())
+        );
+    }
+    assert_eq!(trie.retrieve(&"zoo".as_alphachar()), None);
+
+    println!("Replacing a value in place");
+    assert!(trie.store(&"zebra".as_alphachar(), String::from("stripes")));
+    trie.retrieve_mut(&"abacus".as_alphachar())
+        .unwrap()
+        .push('!');
+    assert_eq!(trie.values().len(), DICT.len());
+    assert_eq!(
+        trie.retrieve(&"zebra".as_alphachar()).map(String::as_str),
+        Some("stripes")
+    );
+    assert_eq!(
+        trie.retrieve(&"abacus".as_alphachar()).map(String::as_str),
+        Some("ABACUS!")
+    );
+
+    let (trie, values) = trie.into_parts();
+    let index = trie.get(&"abandon".as_alphachar()).unwrap();
+    assert_eq!(values[index as usize], "ABANDON");
+}
+
+#[test]
+fn test_extend() {
+    let mut trie = en_trie_new();
+
+    println!("Extending with owned keys");
+    trie.extend(DICT.iter().map(|word| (word.as_alphachar(), 1)));
+    assert_dict_complete(&trie);
+
+    println!("Extending with borrowed keys");
+    let keys: Vec<Vec<AlphaChar>> = ["abacus", "zoo"]
+        .iter()
+        .map(|word| word.chars().map(|c| c as AlphaChar).collect())
+        .collect();
+    trie.extend(keys.iter().map(|key| (key.as_slice(), 2)));
+    trie.extend([(&[0x41, 0][..], 3)]);
+    assert_eq!(trie.retrieve(&"abacus".as_alphachar()), Some(&2));
+    assert_eq!(trie.retrieve(&"zoo".as_alphachar()), Some(&2));
+    assert_eq!(trie.retrieve(&[0x41, 0]), None);
+    assert_eq!(trie.len(), DICT.len() + 1);
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_from_mmap() {
+    use crate::trie::ROTrie;
+
+    println!("Preparing trie file");
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let path = std::env::temp_dir().join("datrie_test_from_mmap.tri");
+    trie.save(&path).expect("Failed to save trie");
+
+    println!("Mapping trie file");
+    let ro: ROTrie<i32> = unsafe { ROTrie::from_mmap(&path) }.expect("Failed to map trie");
+    assert_eq!(ro.serialized_size(), trie.serialized_size());
+    let mut trie = Trie::from_ro(ro);
+    assert_dict_complete(&trie);
+
+    println!("Modifying mapped trie");
+    assert!(trie.store(&"mapped".as_alphachar(), 1));
+    assert!(trie.delete(&"zebra".as_alphachar()));
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(trie.retrieve(&"mapped".as_alphachar()), Some(&1));
+    assert_eq!(trie.retrieve(&"zebra".as_alphachar()), None);
+    assert_eq!(trie.len(), DICT.len());
+}
+
+#[cfg(feature = "lazy")]
+#[test]
+fn test_lazy_ro_trie() {
+    use crate::lazy::LazyROTrie;
+
+    println!("Preparing trie file with free blocks");
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let mut pairs = Vec::new();
+    for a in 'a'..='z' {
+        for b in 'a'..='z' {
+            pairs.push(format!("q{}{}x", a, b));
+        }
+    }
+    for (i, pair) in pairs.iter().enumerate() {
+        assert!(trie.store(&pair.as_str().as_alphachar(), i as i32));
+    }
+    assert!(trie.delete(&"zebra".as_alphachar()));
+    assert!(trie.delete(&"qaax".as_alphachar()));
+    let mut buf = b"before".to_vec();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+
+    println!("Opening trie lazily with a single page of cells");
+    let mut reader = Cursor::new(buf);
+    reader.set_position(6);
+    let mut lazy = LazyROTrie::<_, i32>::with_cache_pages(reader, 1).unwrap();
+    assert_eq!(lazy.alpha_map(), &en_alpha_map_new());
+
+    println!("Comparing lookups with the loaded trie");
+    let missing = ["zebra", "qaax", "ab", "abacuss", "qzz", "", "ABC"];
+    for key in DICT
+        .iter()
+        .chain(&missing)
+        .copied()
+        .chain(pairs.iter().map(|p| p.as_str()))
+    {
+        let key = key.as_alphachar();
+        assert_eq!(
+            lazy.retrieve(&key).unwrap(),
+            trie.retrieve(&key).copied(),
+            "Wrong data for {:?}",
+            key
+        );
+        assert_eq!(lazy.contains_key(&key).unwrap(), trie.contains_key(&key));
+        assert_eq!(lazy.walk_prefix(&key).unwrap(), trie.walk_prefix(&key));
+    }
+    assert_eq!(lazy.retrieve(&[0x61]).unwrap(), None);
+
+    println!("Opening a truncated trie");
+    let mut buf = lazy.into_inner().into_inner();
+    buf.truncate(buf.len() - 1);
+    let mut reader = Cursor::new(buf);
+    reader.set_position(6);
+    assert!(LazyROTrie::<_, i32>::new(reader).is_err());
+}
+
+#[test]
+fn test_from_bytes() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+    buf.extend_from_slice(b"trailing");
+
+    println!("Reading a trie followed by other data");
+    let loaded = Trie::<i32>::from_bytes(&buf).unwrap();
+    assert!(!loaded.is_dirty());
+    assert_dict_complete(&loaded);
+    assert_dict_complete(&Trie::from_ro(
+        crate::trie::ROTrie::<i32>::from_bytes(&buf).unwrap(),
+    ));
+
+    let mut rest = &buf[..];
+    Trie::<i32>::from_reader(&mut rest).unwrap();
+    assert_eq!(rest, b"trailing");
+
+    println!("Reading a truncated trie");
+    let err = Trie::<i32>::from_bytes(&buf[..trie.serialized_size() - 1]).err();
+    assert_eq!(err.unwrap().kind(), io::ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn test_serialize_with_length() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let mut small = en_trie_new();
+    assert!(small.store(&"small".as_alphachar(), 2));
+
+    println!("Writing two tries between other data");
+    let mut buf = b"head".to_vec();
+    trie.serialize_with_length(&mut buf).unwrap();
+    small.serialize_with_length(&mut buf).unwrap();
+    buf.extend_from_slice(b"tail");
+    assert_eq!(
+        buf.len(),
+        4 + 8 + trie.serialized_size() + 8 + small.serialized_size() + 4
+    );
+
+    let mut reader = Cursor::new(&buf);
+    reader.set_position(4);
+    let loaded = Trie::<i32>::from_reader_at(&mut reader).unwrap();
+    assert_dict_complete(&loaded);
+    assert_eq!(reader.position() as usize, 4 + 8 + trie.serialized_size());
+    let loaded = Trie::<i32>::from_reader_at(&mut reader).unwrap();
+    assert_eq!(loaded.retrieve(&"small".as_alphachar()), Some(&2));
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"tail");
+
+    println!("Reading a section with the wrong length");
+    let mut buf = Vec::new();
+    small.serialize_with_length(&mut buf).unwrap();
+    let len = small.serialized_size() as u64;
+    buf[..8].copy_from_slice(&(len - 1).to_be_bytes());
+    assert!(Trie::<i32>::from_reader_at(&mut Cursor::new(&buf)).is_err());
+    buf[..8].copy_from_slice(&(len + 1).to_be_bytes());
+    buf.push(0);
+    assert!(Trie::<i32>::from_reader_at(&mut Cursor::new(&buf)).is_err());
+
+    println!("Recording the written length despite a wrong size");
+    #[derive(Default)]
+    struct Miscounted(u16);
+    impl TrieSerializable for Miscounted {
+        fn serialize<T: Write>(&self, writer: &mut T) -> io::Result<()> {
+            self.0.serialize(writer)
+        }
+
+        fn serialized_size(&self) -> usize {
+            0
+        }
+    }
+    impl TrieDeserializable for Miscounted {
+        fn deserialize<T: Read>(reader: &mut T) -> io::Result<Self> {
+            Ok(Miscounted(u16::deserialize(reader)?))
+        }
+    }
+    let mut trie: Trie<Miscounted> = Trie::new(en_alpha_map_new());
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), Miscounted(i as u16)));
+    }
+    let mut buf = Vec::new();
+    trie.serialize_with_length(&mut buf).unwrap();
+    buf.extend_from_slice(b"tail");
+    let mut reader = Cursor::new(&buf);
+    let loaded = Trie::<Miscounted>::from_reader_at(&mut reader).unwrap();
+    assert_eq!(loaded.len(), DICT.len());
+    assert_eq!(reader.position() as usize, buf.len() - 4);
+}
+
+#[cfg(feature = "flate2")]
+#[test]
+fn test_serialize_compressed() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+
+    println!("Writing compressed trie with trailing data");
+    let mut buf = Vec::new();
+    trie.serialize_compressed(&mut buf).unwrap();
+    assert!(!trie.is_dirty());
+    buf.extend_from_slice(b"after");
+
+    let mut reader = Cursor::new(&buf);
+    let loaded = Trie::<i32>::from_reader_compressed(&mut reader).unwrap();
+    assert_dict_complete(&loaded);
+    let mut rest = Vec::new();
+    reader.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, b"after");
+
+    println!("Telling compressed and raw tries apart");
+    let mut raw = Vec::new();
+    trie.serialize(&mut raw).unwrap();
+    let err = Trie::<i32>::from_reader_compressed(&mut Cursor::new(&raw)).err();
+    assert_eq!(err.map(|err| err.kind()), Some(io::ErrorKind::InvalidData));
+    assert!(Trie::<i32>::from_reader(&mut Cursor::new(&buf)).is_err());
+}
+
+#[cfg(feature = "flate2")]
+#[test]
+#[ignore = "slow, builds a large trie to compare sizes; run with --release --ignored --nocapture"]
+fn bench_serialize_compressed() {
+    let mut alpha_map = AlphaMap::default();
+    alpha_map
+        .add_range('0' as AlphaChar..='9' as AlphaChar)
+        .unwrap();
+    let count = 200_000;
+    let mut trie = Trie::from_sorted_iter(
+        alpha_map,
+        (0..count).map(|i: u32| (format!("{:07}", i).as_str().as_alphachar(), i as i32)),
+    )
+    .unwrap();
+
+    let mut raw = Vec::new();
+    trie.serialize(&mut raw).unwrap();
+    let mut compressed = Vec::new();
+    trie.serialize_compressed(&mut compressed).unwrap();
+    println!(
+        "{} keys: {} bytes raw, {} bytes compressed ({:.1}%)",
+        count,
+        raw.len(),
+        compressed.len(),
+        compressed.len() as f64 * 100.0 / raw.len() as f64
+    );
+    assert!(compressed.len() < raw.len());
+}
+
+#[test]
+fn test_reserve() {
+    let mut trie = en_trie_new();
+    let empty_size = trie.serialized_size();
+
+    println!("Reserving cells");
+    assert!(trie.reserve(0));
+    assert!(trie.reserve(1000));
+    assert_eq!(trie.serialized_size(), empty_size + 1000 * 8);
+    assert!(!trie.reserve(usize::MAX));
+    let reserved_size = trie.serialized_size();
+
+    println!("Reserving cells fallibly");
+    assert_eq!(trie.try_reserve(0), Ok(()));
+    assert!(trie.try_reserve(usize::MAX).is_err());
+    // the double-array cannot index this many cells, though a vector could
+    assert!(trie.try_reserve(TRIE_INDEX_MAX as usize).is_err());
+    assert_eq!(trie.serialized_size(), reserved_size);
+    assert_eq!(trie.try_reserve(24), Ok(()));
+    assert_eq!(trie.serialized_size(), reserved_size + 24 * 8);
+
+    println!("Storing into reserved cells");
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    assert_dict_complete(&trie);
+
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).expect("Failed to serialize trie");
+    let trie: Trie<i32> = Trie::from_reader(&mut Cursor::new(&buf))
+        .expect("Failed to reload trie from serialized state");
+    assert_dict_complete(&trie);
+}
+
+#[test]
+fn test_shrink_to_fit() {
+    use crate::types::ALPHA_CHAR_ERROR;
+
+    let mut trie = en_trie_new();
+    for (i, word) in DICT.iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+    let mut kept = en_trie_new();
+    for (i, word) in DICT.iter().enumerate() {
+        if i % 4 == 0 {
+            assert!(kept.store(&word.as_alphachar(), i as i32));
+        } else {
+            assert!(trie.delete(&word.as_alphachar()));
+        }
+    }
+    let size_before = trie.serialized_size();
+
+    println!("Shrinking trie");
+    trie.shrink_to_fit().unwrap();
+    assert!(trie.serialized_size() < size_before);
+    assert!(trie.serialized_size() <= kept.serialized_size());
+    assert_eq!(
+        trie.iter()
+            .map(|(k, d)| (k, *d.unwrap()))
+            .collect::<Vec<_>>(),
+        kept.iter()
+            .map(|(k, d)| (k, *d.unwrap()))
+            .collect::<Vec<_>>()
+    );
+
+    println!("Storing after shrinking");
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    assert_dict_complete(&trie);
+
+    println!("Keeping a trie that cannot be rebuilt");
+    let mut trie = en_trie_new();
+    for (i, word) in ["abc", "abz", "zoo"].iter().enumerate() {
+        assert!(trie.store(&word.as_alphachar(), i as i32));
+    }
+    let mut buf = Vec::new();
+    trie.serialize(&mut buf).unwrap();
+    // drop 'z' from the end of the a..z range of the alphabet
+    assert_eq!(buf[12..16], [0, 0, 0, 0x7a]);
+    buf[15] = 0x79;
+    let mut corrupt = TestTrie::from_reader(&mut Cursor::new(&buf)).unwrap();
+    let before: Vec<_> = corrupt
+        .iter()
+        .map(|(key, data)| (key, *data.unwrap()))
+        .collect();
+    assert_eq!(
+        corrupt.shrink_to_fit(),
+        Err(StoreError::CharNotInAlphaMap(ALPHA_CHAR_ERROR))
+    );
+    let after: Vec<_> = corrupt
+        .iter()
+        .map(|(key, data)| (key, *data.unwrap()))
+        .collect();
+    assert_eq!(after, before);
+}
+
+#[test]
+fn test_from_sorted_iter() {
+    let mut words: Vec<&str> = DICT.to_vec();
+    words.sort();
+
+    println!("Building trie from sorted keys");
+    let mut trie = Trie::from_sorted_iter(
+        en_alpha_map_new(),
+        words.iter().map(|word| (word.as_alphachar(), 1)),
+    )
+    .unwrap();
+    assert_dict_complete(&trie);
+    assert_eq!(trie.len(), DICT.len());
+
+    println!("Building trie from keys of another trie");
+    let copy = Trie::from_sorted_iter(
+        en_alpha_map_new(),
+        trie.iter().map(|(key, data)| (key, *data.unwrap())),
+    )
+    .unwrap();
+    assert_dict_complete(&copy);
+
+    println!("Modifying built trie");
+    assert!(trie.store(&"abacuses".as_alphachar(), 2));
+    assert!(trie.delete(&"abacus".as_alphachar()));
+    assert_eq!(trie.retrieve(&"abacuses".as_alphachar()), Some(&2));
+    assert_eq!(trie.retrieve(&"abacus".as_alphachar()), None);
+
+    println!("Building trie with the empty key");
+    let trie = Trie::from_sorted_iter(
+        en_alpha_map_new(),
+        ["", "a", "ab"].iter().map(|word| (word.as_alphachar(), 1)),
+    )
+    .unwrap();
+    assert_eq!(trie.len(), 3);
+    assert_eq!(trie.retrieve(&"".as_alphachar()), Some(&1));
+    assert_eq!(trie.retrieve(&"ab".as_alphachar()), Some(&1));
+}
+
+#[test]
+fn test_from_sorted_iter_unsorted() {
+    let trie = Trie::from_sorted_iter(
+        en_alpha_map_new(),
+        ["b", "a"].iter().map(|word| (word.as_alphachar(), 1)),
+    );
+    assert_eq!(trie.err(), Some(StoreError::NotSorted(1)));
+    // skipped keys still count in the position
+    let trie = Trie::from_sorted_iter(
+        en_alpha_map_new(),
+        ["b", "B", "c", "a"]
+            .iter()
+            .map(|word| (word.as_alphachar(), 1)),
+    );
+    assert_eq!(trie.err(), Some(StoreError::NotSorted(3)));
+
+    println!("Building trie with duplicate keys");
+    let trie = Trie::from_sorted_iter(
+        en_alpha_map_new(),
+        [("ab", 1), ("ab", 2), ("abc", 3), ("abc", 4)]
+            .iter()
+            .map(|(word, data)| (word.as_alphachar(), *data)),
+    )
+    .unwrap();
+    assert_eq!(trie.len(), 2);
+    assert_eq!(trie.retrieve(&"ab".as_alphachar()), Some(&2));
+    assert_eq!(trie.retrieve(&"abc".as_alphachar()), Some(&4));
+    assert_eq!(trie.validate(), Ok(()));
+    // with and without the terminator are the same key
+    let trie = Trie::from_sorted_iter(
+        en_alpha_map_new(),
+        [(vec![0x61], 1), (vec![0x61, 0], 2), (vec![0x61, 0x62], 3)],
+    )
+    .unwrap();
+    let entries: Vec<(Vec<AlphaChar>, i32)> = trie
+        .iter()
+        .map(|(key, data)| (key, *data.unwrap()))
+        .collect();
+    assert_eq!(entries, [("a".as_alphachar(), 2), ("ab".as_alphachar(), 3)]);
+
+    println!("Building trie with an interior terminator");
+    let trie = Trie::from_sorted_iter(
+        en_alpha_map_new(),
+        [(vec![0x61, 0], 1), (vec![0x61, 0, 0x62], 2)],
+    );
+    assert_eq!(trie.err(), Some(StoreError::InteriorTerminator(1)));
+}
+
+#[cfg(feature = "low-level")]
+#[test]
+fn test_from_parts() {
+    use crate::darray::DArray;
+    use crate::tail::Tail;
+    use crate::trie::ROTrie;
+
+    // "ab" and "ac", with 'a' at cell 3 and the separate nodes at 4 and 5
+    let cells = vec![
+        (0, 0),   // header, filled in by from_cells
+        (-1, -1), // empty free list
+        (2, 0),   // root
+        (2, 2),   // 'a'
+        (-1, 3),  // "ab", tail block 1
+        (-2, 3),  // "ac", tail block 2
+    ];
+    let tail = || Tail::from_blocks(vec![Some((vec![], 1)), Some((vec![], 2)), None]);
+
+    println!("Assembling trie from parts");
+    let trie = ROTrie::<i32>::from_parts(
+        en_alpha_map_new(),
+        DArray::from_cells(cells.clone()),
+        tail(),
+    )
+    .expect("Valid parts are rejected");
+    assert_eq!(trie.retrieve(&"ab".as_alphachar()), Some(&1));
+    assert_eq!(trie.retrieve(&"ac".as_alphachar()), Some(&2));
+    assert_eq!(trie.retrieve(&"a".as_alphachar()), None);
+    assert_eq!(trie.len(), 2);
+
+    println!("Checking that the parts are validated");
+    let mut corrupt = cells.clone();
+    corrupt[5] = (-2, 10);
+    assert_eq!(
+        ROTrie::<i32>::from_parts(en_alpha_map_new(), DArray::from_cells(corrupt), tail()).err(),
+        Some(CorruptError::Check(5))
+    );
+    let mut corrupt = cells.clone();
+    corrupt[5] = (-3, 3);
+    assert_eq!(
+        ROTrie::<i32>::from_parts(en_alpha_map_new(), DArray::from_cells(corrupt), tail()).err(),
+        Some(CorruptError::TailIndex(5))
+    );
+    assert_eq!(
+        ROTrie::<i32>::from_parts(
+            en_alpha_map_new(),
+            DArray::from_cells(cells[..2].to_vec()),
+            tail()
+        )
+        .err(),
+        Some(CorruptError::CellCount)
+    );
+
+    println!("Storing into the assembled trie");
+    let mut trie = Trie::from_ro(trie);
+    assert!(trie.store(&"abc".as_alphachar(), 3));
+    assert!(trie.store(&"b".as_alphachar(), 4));
+    assert_eq!(trie.retrieve(&"ab".as_alphachar()), Some(&1));
+    assert_eq!(trie.retrieve(&"abc".as_alphachar()), Some(&3));
+    assert_eq!(trie.len(), 4);
+}
+
+#[test]
+fn test_save_if_dirty() {
+    let path = std::env::temp_dir().join("datrie_test_save_if_dirty.tri");
+    let _ = std::fs::remove_file(&path);
+
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    println!("Saving dirty trie");
+    assert!(trie.save_if_dirty(&path).expect("Failed to save trie"));
+    assert!(!trie.is_dirty());
+    assert_dict_complete(&Trie::<i32>::from_file(&path).unwrap());
+
+    println!("Skipping clean trie");
+    std::fs::remove_file(&path).unwrap();
+    assert!(!trie.save_if_dirty(&path).expect("Failed to skip saving"));
+    assert!(!path.exists(), "Clean trie should not be written");
+
+    println!("Saving again after modification");
+    assert!(trie.store(&"abc".as_alphachar(), 1));
+    assert!(trie.save_if_dirty(&path).expect("Failed to save trie"));
+    let loaded = Trie::<i32>::from_file(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.retrieve(&"abc".as_alphachar()), Some(&1));
+}
+
+#[test]
+fn test_snapshot() {
+    let mut trie = en_trie_new();
+    for word in DICT {
+        assert!(trie.store(&word.as_alphachar(), 1));
+    }
+    let snapshot = trie.snapshot();
+
+    println!("Modifying the trie after the snapshot");
+    assert!(trie.delete(&"abacus".as_alphachar()));
+    assert!(trie.store(&"zoo".as_alphachar(), 2));
+    assert!(trie.store(&"bat".as_alphachar(), 3));
+    *trie.retrieve_mut(&"angel".as_alphachar()).unwrap() = 4;
+    trie.validate().expect("Trie is corrupt after the snapshot");
+
+    println!("Restoring into another trie");
+    let mut other = en_trie_new();
+    other.restore(snapshot.clone());
+    assert!(other.is_dirty());
+    assert_dict_complete(&other);
+    assert_eq!(other.retrieve(&"zoo".as_alphachar()), None);
+
+    println!("Modifying the restored trie");
+    assert!(other.store(&"zoo".as_alphachar(), 5));
+    assert!(other.delete(&"home".as_alphachar()));
+    assert_eq!(trie.retrieve(&"zoo".as_alphachar()), Some(&2));
+    assert_eq!(trie.retrieve(&"home".as_alphachar()), Some(&1));
+
+    println!("Restoring the trie itself");
+    trie.restore(snapshot);
+    assert_dict_complete(&trie);
+    let again = trie.snapshot();
+    assert!(trie.store(&"abc".as_alphachar(), 1));
+    trie.clear();
+    assert!(trie.is_empty());
+    trie.restore(again);
+    assert_dict_complete(&trie);
+    assert_eq!(other.retrieve(&"zoo".as_alphachar()), Some(&5));
+}
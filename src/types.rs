@@ -1,8 +1,13 @@
 use alloc::string::String;
+#[cfg(feature = "std")]
 use alloc::vec;
 use alloc::vec::Vec;
+#[cfg(feature = "cffi")]
 use core::cmp::Ordering;
-use core::{iter, slice};
+use core::fmt::Write as _;
+#[cfg(feature = "cffi")]
+use core::slice;
+use core::{fmt, iter};
 #[cfg(feature = "std")]
 use std::io;
 #[cfg(feature = "std")]
@@ -35,9 +40,21 @@ impl AsAlphaChar for &str {
 }
 
 pub trait AlphaCharToString {
+    /// Convert the key to a string, up to its terminator. Returns None if
+    /// any character is not valid Unicode, see
+    /// [ac_to_string_lossy](AlphaCharToStringLossy::ac_to_string_lossy).
     fn ac_to_string(&self) -> Option<String>;
 }
 
+/// Kept apart from [AlphaCharToString] so that its implementors are not
+/// required to provide it
+pub trait AlphaCharToStringLossy {
+    /// Same as [ac_to_string](AlphaCharToString::ac_to_string), but replace
+    /// characters that are not valid Unicode, such as [ALPHA_CHAR_ERROR],
+    /// with U+FFFD REPLACEMENT CHARACTER instead of failing the whole key
+    fn ac_to_string_lossy(&self) -> String;
+}
+
 impl AlphaCharToString for &[AlphaChar] {
     fn ac_to_string(&self) -> Option<String> {
         self.iter()
@@ -55,6 +72,66 @@ impl AlphaCharToString for &[AlphaChar] {
     }
 }
 
+impl AlphaCharToStringLossy for &[AlphaChar] {
+    fn ac_to_string_lossy(&self) -> String {
+        self.iter()
+            .take_while(|v| **v != 0)
+            .map(|v| char::from_u32(*v).unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect()
+    }
+}
+
+/// Display a key as a string, up to its terminator. Unlike
+/// [ac_to_string](AlphaCharToString::ac_to_string), characters that are not
+/// valid Unicode, such as [ALPHA_CHAR_ERROR], are written as `\u{XXXX}`
+/// instead of failing the whole key, which suits logging.
+///
+/// ```
+/// use datrie::{AlphaChar, AsAlphaChar, DisplayKey, ALPHA_CHAR_ERROR};
+///
+/// assert_eq!(DisplayKey(&"hello".as_alphachar()).to_string(), "hello");
+/// let key: [AlphaChar; 4] = ['a' as AlphaChar, 0xd800, ALPHA_CHAR_ERROR, 0];
+/// assert_eq!(DisplayKey(&key).to_string(), "a\\u{d800}\\u{ffffffff}");
+/// ```
+#[derive(Clone, Copy)]
+pub struct DisplayKey<'a>(pub &'a [AlphaChar]);
+
+impl DisplayKey<'_> {
+    fn chars(&self) -> impl Iterator<Item = Result<char, AlphaChar>> + '_ {
+        self.0
+            .iter()
+            .copied()
+            .take_while(|ac| *ac != 0)
+            .map(|ac| char::from_u32(ac).ok_or(ac))
+    }
+}
+
+impl fmt::Display for DisplayKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.chars() {
+            match c {
+                Ok(c) => f.write_char(c)?,
+                Err(ac) => write!(f, "\\u{{{:x}}}", ac)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Same as [Display](fmt::Display), but quoted and escaped like a string
+impl fmt::Debug for DisplayKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+        for c in self.chars() {
+            match c {
+                Ok(c) => write!(f, "{}", c.escape_debug())?,
+                Err(ac) => write!(f, "\\u{{{:x}}}", ac)?,
+            }
+        }
+        f.write_char('"')
+    }
+}
+
 #[cfg(feature = "cffi")]
 #[no_mangle]
 pub extern "C" fn alpha_char_strlen(str: *const AlphaChar) -> i32 {
@@ -84,43 +161,119 @@ pub type TrieChar = u8;
 pub const TRIE_CHAR_TERM: TrieChar = '\0' as TrieChar;
 pub const TRIE_CHAR_MAX: TrieChar = TrieChar::MAX;
 
+/// Data that can be written to a trie file.
+///
+/// The data of each tail block is written back to back with no width
+/// prefix, so a type must always be read back with the same number of bytes
+/// it wrote: fixed-width types always write the same number of bytes, while
+/// variable-width ones such as `Vec<u8>` write their own length first.
+///
+/// As the blocks alone do not tell the data apart, the tail also records
+/// [DATA_TAG](Self::DATA_TAG), and a trie is only loaded as a type of the
+/// same tag. Tails without a tag, as written by libdatrie and before tags
+/// were recorded, are only loaded as types that
+/// [read untagged data](TrieDeserializable::READS_UNTAGGED).
+///
+/// A type keeping the default tag of 0 is written without a tag, so it is
+/// not told apart from `i32` or from other such types when loaded. Give it
+/// a tag of its own to have it checked.
 #[cfg(feature = "std")]
 pub trait TrieSerializable {
+    /// Tag of the data format, which must be the same as
+    /// [TrieDeserializable::DATA_TAG] of the type.
+    ///
+    /// The default of 0 is the `i32` data of libdatrie, and is not written
+    /// to the file so that libdatrie can still read it. Any other tag is
+    /// written to the tail header, which libdatrie cannot read. Types with
+    /// their own format should pick a tag not used by this crate, which uses
+    /// the tags up to 0xff for its own types and adds 0x100 for an `Option`.
+    const DATA_TAG: u32 = 0;
+
     fn serialize<T: Write>(&self, writer: &mut T) -> io::Result<()>;
 
+    /// Get the number of bytes [serialize](Self::serialize) would write.
+    ///
+    /// The default implementation runs `serialize` into a writer that only
+    /// counts the bytes. Types with costly serialization should override it
+    /// with a direct computation, as it is called for every entry when
+    /// computing the size of a trie.
     fn serialized_size(&self) -> usize {
-        let mut buf = Vec::new();
-        self.serialize(&mut buf).unwrap();
-        buf.len()
+        let mut counter = ByteCounter(0);
+        self.serialize(&mut counter).unwrap();
+        counter.0
     }
 }
 
+/// Writer that discards the data, counting the bytes written
 #[cfg(feature = "std")]
-pub trait TrieDeserializable {
-    fn deserialize<T: Read>(reader: &mut T) -> io::Result<Self>
-    where
-        Self: Sized;
-}
+struct ByteCounter(usize);
 
 #[cfg(feature = "std")]
-impl TrieSerializable for i32 {
-    fn serialize<T: Write>(&self, writer: &mut T) -> io::Result<()> {
-        writer.write_i32::<BigEndian>(*self)
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
     }
 
-    fn serialized_size(&self) -> usize {
-        size_of::<i32>()
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
     }
 }
 
 #[cfg(feature = "std")]
-impl TrieDeserializable for i32 {
+pub trait TrieDeserializable {
+    /// Tag of the data format, see [TrieSerializable::DATA_TAG]. Loading a
+    /// trie whose tail has another tag fails with
+    /// [InvalidData](io::ErrorKind::InvalidData).
+    const DATA_TAG: u32 = 0;
+
+    /// Whether a tail without a tag is loaded as this type, which is true
+    /// for types of tag 0 and for those of this crate written before tags
+    /// were recorded, namely `Vec<u8>` and `Option<T>` of such a `T`.
+    const READS_UNTAGGED: bool = Self::DATA_TAG == 0;
+
     fn deserialize<T: Read>(reader: &mut T) -> io::Result<Self>
     where
-        Self: Sized,
-    {
-        reader.read_i32::<BigEndian>()
-    }
+        Self: Sized;
+}
+
+macro_rules! impl_trie_serializable_int {
+    ($($ty:ty => $write:ident, $read:ident, $tag:literal;)*) => {$(
+        #[cfg(feature = "std")]
+        impl TrieSerializable for $ty {
+            const DATA_TAG: u32 = $tag;
+
+            fn serialize<T: Write>(&self, writer: &mut T) -> io::Result<()> {
+                writer.$write::<BigEndian>(*self)
+            }
+
+            fn serialized_size(&self) -> usize {
+                size_of::<$ty>()
+            }
+        }
+
+        #[cfg(feature = "std")]
+        impl TrieDeserializable for $ty {
+            const DATA_TAG: u32 = $tag;
+
+            fn deserialize<T: Read>(reader: &mut T) -> io::Result<Self>
+            where
+                Self: Sized,
+            {
+                reader.$read::<BigEndian>()
+            }
+        }
+    )*};
+}
+
+// i32 keeps the default tag as the data of libdatrie
+impl_trie_serializable_int! {
+    i16 => write_i16, read_i16, 1;
+    u16 => write_u16, read_u16, 2;
+    i32 => write_i32, read_i32, 0;
+    u32 => write_u32, read_u32, 3;
+    i64 => write_i64, read_i64, 4;
+    u64 => write_u64, read_u64, 5;
 }
 
 #[cfg(feature = "std")]
@@ -128,6 +281,8 @@ impl<T> TrieSerializable for Option<T>
 where
     T: TrieSerializable,
 {
+    const DATA_TAG: u32 = T::DATA_TAG + 0x100;
+
     fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
         match self {
             Some(v) => {
@@ -151,6 +306,9 @@ impl<T> TrieDeserializable for Option<T>
 where
     T: TrieDeserializable,
 {
+    const DATA_TAG: u32 = T::DATA_TAG + 0x100;
+    const READS_UNTAGGED: bool = T::READS_UNTAGGED;
+
     fn deserialize<R: Read>(reader: &mut R) -> io::Result<Self>
     where
         Self: Sized,
@@ -169,6 +327,8 @@ where
 
 #[cfg(feature = "std")]
 impl TrieSerializable for Vec<u8> {
+    const DATA_TAG: u32 = 6;
+
     fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
         writer.write_u64::<BigEndian>(self.len() as u64)?;
         writer.write(self)?;
@@ -182,6 +342,9 @@ impl TrieSerializable for Vec<u8> {
 
 #[cfg(feature = "std")]
 impl TrieDeserializable for Vec<u8> {
+    const DATA_TAG: u32 = 6;
+    const READS_UNTAGGED: bool = true;
+
     fn deserialize<R: Read>(reader: &mut R) -> io::Result<Self>
     where
         Self: Sized,
@@ -1,18 +1,28 @@
+#[cfg(feature = "std")]
 use crate::types::{TrieDeserializable, TrieSerializable};
+#[cfg(feature = "std")]
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "std")]
 use std::io;
+#[cfg(feature = "std")]
 use std::io::{Read, Write};
 
+#[cfg(feature = "cffi")]
 /// cbindgen:ignore
 #[derive(Eq, PartialEq, Copy, Clone)]
 #[repr(transparent)]
 pub struct Bool(u32);
 
+#[cfg(feature = "cffi")]
 pub(crate) const DA_TRUE: Bool = Bool(1);
+#[cfg(feature = "cffi")]
 pub(crate) const DA_FALSE: Bool = Bool(0);
+#[cfg(feature = "cffi")]
 pub(crate) const FALSE: Bool = DA_FALSE;
+#[cfg(feature = "cffi")]
 pub(crate) const TRUE: Bool = DA_TRUE;
 
+#[cfg(feature = "cffi")]
 impl From<bool> for Bool {
     fn from(value: bool) -> Self {
         match value {
@@ -22,6 +32,7 @@ impl From<bool> for Bool {
     }
 }
 
+#[cfg(feature = "cffi")]
 impl Into<bool> for Bool {
     fn into(self) -> bool {
         self.0 == 1
@@ -33,6 +44,7 @@ impl Into<bool> for Bool {
 pub struct CTrieData(pub i32);
 pub const TRIE_DATA_ERROR: CTrieData = CTrieData(-1);
 
+#[cfg(feature = "std")]
 impl TrieSerializable for Option<CTrieData> {
     fn serialize<T: Write>(&self, writer: &mut T) -> io::Result<()> {
         match self {
@@ -40,8 +52,13 @@ impl TrieSerializable for Option<CTrieData> {
             None => writer.write_i32::<BigEndian>(-1),
         }
     }
+
+    fn serialized_size(&self) -> usize {
+        size_of::<i32>()
+    }
 }
 
+#[cfg(feature = "std")]
 impl TrieDeserializable for Option<CTrieData> {
     fn deserialize<T: Read>(reader: &mut T) -> io::Result<Self> {
         Ok(match reader.read_i32::<BigEndian>()? {